@@ -3,7 +3,7 @@ use futures::{pin_mut, stream::StreamExt};
 use parsers::{dlt::DltParser, MessageStreamItem, ParseYield};
 use processor::grabber::LineRange;
 use rustyline::{error::ReadlineError, DefaultEditor};
-use session::session::Session;
+use session::{session::Session, state::DeltasRequest};
 use sources::{
     factory::{DltParserSettings, FileFormat, ObserveOptions, ParserType},
     producer::MessageProducer,
@@ -35,6 +35,7 @@ pub(crate) async fn handle_interactive_session(input: Option<PathBuf>) {
                         println!("  dlt -> start observing the dlt file that has been given as input");
                         println!("  udp -> start listening to udp server on port 5000");
                         println!("  grab -> after observing a file we can grab lines with this command");
+                        println!("  grab deltas -> grab lines together with their time deltas");
                         println!("  stop -> exit the interpreter");
                     }
                     Some(Command::Udp) => {
@@ -99,11 +100,11 @@ pub(crate) async fn handle_interactive_session(input: Option<PathBuf>) {
                         session.observe(uuid, ObserveOptions::file(file_path.clone(), FileFormat::Binary, ParserType::Dlt(dlt_parser_settings))).expect("observe failed");
                         println!("dlt session was destroyed");
                     }
-                    Some(Command::Grab) => {
+                    Some(Command::Grab { deltas }) => {
                         println!("grab command received");
                         start = Instant::now();
                         let start_op = Instant::now();
-                        let content = session.grab(LineRange::from(0u64..=1000)).await.expect("grab failed");
+                        let content = session.grab(LineRange::from(0u64..=1000), deltas.then(DeltasRequest::default)).await.expect("grab failed");
                         let len = content.len();
                         println!("content has {len} elemenst");
                         for elem in content {
@@ -144,7 +145,7 @@ pub(crate) async fn handle_interactive_session(input: Option<PathBuf>) {
 enum Command {
     Observe,
     Dlt,
-    Grab,
+    Grab { deltas: bool },
     Udp,
     Stop,
     Help,
@@ -171,7 +172,12 @@ async fn collect_user_input(tx: mpsc::UnboundedSender<Command>) -> JoinHandle<()
                         tx.send(Command::Udp).expect("send failed");
                     }
                     "grab" => {
-                        tx.send(Command::Grab).expect("send failed");
+                        tx.send(Command::Grab { deltas: false })
+                            .expect("send failed");
+                    }
+                    "grab deltas" => {
+                        tx.send(Command::Grab { deltas: true })
+                            .expect("send failed");
                     }
                     "help" => {
                         tx.send(Command::Help).expect("send failed");
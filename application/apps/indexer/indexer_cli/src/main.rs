@@ -38,17 +38,20 @@ use parsers::{
     dlt::{attachment::FileExtractor, DltParser, DltRangeParser},
     someip::SomeipParser,
     text::StringTokenizer,
-    LogMessage, MessageStreamItem, ParseYield,
+    timestamp::{detect_file_format, detect_format, DetectedFormat, TimestampFormat},
+    LogMessage, MessageStreamItem, ParseYield, Parser,
 };
 use processor::{export::export_raw, grabber::GrabError, text_source::TextFileSource};
 use serde::{Deserialize, Serialize};
 use sources::{
     binary::{pcap::ng::PcapngByteSource, raw::BinaryByteSource},
     producer::MessageProducer,
+    stdin::StdinSource,
+    ByteSource,
 };
 use std::{
     fs::File,
-    io::BufReader,
+    io::{BufRead, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
 };
 use structopt::StructOpt;
@@ -145,7 +148,6 @@ enum Chip {
             name = "FORMAT_STR",
             help = "format string to use"
         )]
-        #[allow(dead_code)]
         format_string: Option<String>,
         #[structopt(
             short = "t",
@@ -153,7 +155,6 @@ enum Chip {
             name = "SAMPLE",
             help = "test string to use"
         )]
-        #[allow(dead_code)]
         test_string: Option<String>,
         #[structopt(
             short = "c",
@@ -161,10 +162,8 @@ enum Chip {
             name = "CONFIG",
             help = "test a file using this configuration"
         )]
-        #[allow(dead_code)]
         test_config: Option<PathBuf>,
         #[structopt(short, long, help = "display duration info")]
-        #[allow(dead_code)]
         stdout: bool,
     },
     #[structopt(about = "handling dlt input")]
@@ -278,22 +277,19 @@ enum Chip {
             help = "string to extract date from",
             name = "INPUT"
         )]
-        #[allow(dead_code)]
         input_string: Option<String>,
         #[structopt(
             short,
             long = "config",
             name = "CONFIG",
-            help = "file that contains a list of files to analyze"
+            help = "json file that contains a list of files to analyze"
         )]
-        #[allow(dead_code)]
         config_file: Option<String>,
         #[structopt(
             short = "f",
             long = "file",
             help = "file where the timeformat should be detected"
         )]
-        #[allow(dead_code)]
         input_file: Option<PathBuf>,
     },
     #[structopt(about = "test date discovery, either from a string or from a file")]
@@ -360,6 +356,18 @@ enum Chip {
         #[structopt(short, long, help = "append to file if exists")]
         append: bool,
     },
+    #[structopt(about = "parse data piped in via stdin, e.g. `journalctl -f | chip stdin`")]
+    Stdin {
+        #[structopt(long, help = "parse the input as DLT messages without storage header")]
+        dlt: bool,
+        #[structopt(
+            short,
+            long = "out",
+            name = "OUT",
+            help = "Output file, stdout if not present"
+        )]
+        output: Option<PathBuf>,
+    },
     #[structopt(about = "enter interactive session")]
     Session {
         #[structopt(help = "Sets the input file path")]
@@ -374,11 +382,11 @@ pub async fn main() -> Result<()> {
     let opt = Chip::from_args();
     match opt {
         Chip::Format {
-            format_string: _,
-            test_string: _,
-            test_config: _,
-            stdout: _,
-        } => println!("handle_format_subcommand not currently implemented"),
+            format_string,
+            test_string,
+            test_config,
+            stdout,
+        } => handle_format_subcommand(format_string, test_string, test_config, stdout),
         Chip::Grab {
             input,
             start_pos,
@@ -466,10 +474,10 @@ pub async fn main() -> Result<()> {
         } => handle_dlt_stats_subcommand(&input, count, legacy, start, stdout).await,
         Chip::Detect { input } => handle_detect_file_type_subcommand(&input).await,
         Chip::Discover {
-            input_string: _,
-            config_file: _,
-            input_file: _,
-        } => println!("handle_discover_subcommand currently not implemented"),
+            input_string,
+            config_file,
+            input_file,
+        } => handle_discover_subcommand(input_string, config_file, input_file),
         Chip::Export {
             file,
             legacy,
@@ -491,6 +499,12 @@ pub async fn main() -> Result<()> {
             chunk_size,
             append,
         } => handle_merge_subcommand(merge_config, append, output, chunk_size).await,
+        Chip::Stdin { dlt, output } => {
+            if let Err(e) = handle_stdin_subcommand(dlt, output).await {
+                eprintln!("could not handle stdin command: {e}");
+                std::process::exit(2)
+            }
+        }
         Chip::Session { input } => handle_interactive_session(input).await,
     }
 
@@ -1240,6 +1254,23 @@ pub async fn main() -> Result<()> {
         // std::process::exit(0)
     }
 
+    /// Parses the standard input until its end and writes the messages line by line
+    async fn handle_stdin_subcommand(dlt: bool, output: Option<PathBuf>) -> Result<()> {
+        let mut out: Box<dyn Write> = match output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(std::io::stdout().lock()),
+        };
+        if dlt {
+            let dlt_parser = DltParser::new(None, None, None, None, false);
+            let producer = MessageProducer::new(dlt_parser, StdinSource::new(), None);
+            write_messages(producer, &mut out).await?;
+        } else {
+            let producer = MessageProducer::new(StringTokenizer {}, StdinSource::new(), None);
+            write_messages(producer, &mut out).await?;
+        }
+        Ok(())
+    }
+
     async fn handle_detect_file_type_subcommand(file_path: &Path) {
         let res = detect_messages_type(file_path).await;
 
@@ -1248,6 +1279,127 @@ pub async fn main() -> Result<()> {
         println!("res = {res:?}");
     }
 
+    fn handle_format_subcommand(
+        format_string: Option<String>,
+        test_string: Option<String>,
+        test_config: Option<PathBuf>,
+        stdout: bool,
+    ) {
+        let start_op = Instant::now();
+        if let Some(config) = test_config {
+            let options = fs::File::open(&config)
+                .map_err(|e| format!("could not open {}: {e}", config.display()))
+                .and_then(|mut f| read_format_string_options(&mut f));
+            match options {
+                Ok(options) => test_format_on_file(&options),
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1)
+                }
+            }
+        } else if let Some(format_string) = format_string {
+            let format = match TimestampFormat::new(&format_string) {
+                Ok(format) => format,
+                Err(e) => {
+                    eprintln!("{e}");
+                    std::process::exit(1)
+                }
+            };
+            match test_string {
+                Some(test_string) => match format.extract(&test_string, None) {
+                    Some(ts) => println!(
+                        "\"{}\" -> {ts} ({})",
+                        format.find(&test_string).unwrap_or_default(),
+                        utc_string(ts)
+                    ),
+                    None => println!("no timestamp of format \"{format_string}\" found"),
+                },
+                None => println!("format \"{format_string}\" is valid"),
+            }
+        } else {
+            eprintln!("format string or config file is required");
+            std::process::exit(1)
+        }
+        if stdout {
+            duration_report(start_op, "testing format".to_string());
+        }
+    }
+
+    fn test_format_on_file(options: &FormatTestOptions) {
+        let format = match TimestampFormat::new(&options.format) {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1)
+            }
+        };
+        let file = match fs::File::open(&options.file) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("could not open {}: {e}", options.file);
+                std::process::exit(1)
+            }
+        };
+        // Non-positive count of lines means all lines
+        let limit = usize::try_from(options.lines_to_test)
+            .ok()
+            .filter(|limit| *limit > 0)
+            .unwrap_or(usize::MAX);
+        let (mut matched, mut tested) = (0usize, 0usize);
+        for line in BufReader::new(file).lines().take(limit) {
+            let Ok(line) = line else {
+                break;
+            };
+            tested += 1;
+            match format.extract(&line, None) {
+                Some(ts) if matched == 0 => {
+                    matched += 1;
+                    println!("first timestamp in line {tested}: {}", utc_string(ts));
+                }
+                Some(_) => matched += 1,
+                None => {}
+            }
+        }
+        println!("{matched} of {tested} lines have a timestamp of the format");
+    }
+
+    fn handle_discover_subcommand(
+        input_string: Option<String>,
+        config_file: Option<String>,
+        input_file: Option<PathBuf>,
+    ) {
+        let print_detected = |source: &str, detected: Option<DetectedFormat>| match detected {
+            Some(detected) => println!(
+                "{source}: {} \"{}\" (confidence {:.2}), samples: {:?}",
+                detected.name, detected.format, detected.confidence, detected.samples
+            ),
+            None => println!("{source}: no known timestamp format found"),
+        };
+        let detect_in_file = |path: &Path| match detect_file_format(path) {
+            Ok(detected) => print_detected(&path.to_string_lossy(), detected),
+            Err(e) => eprintln!("could not read {}: {e}", path.display()),
+        };
+        if let Some(input_string) = input_string {
+            print_detected("input", detect_format([input_string]));
+        }
+        if let Some(input_file) = input_file {
+            detect_in_file(&input_file);
+        }
+        if let Some(config_file) = config_file {
+            let files: Result<Vec<String>, String> = fs::read_to_string(&config_file)
+                .map_err(|e| format!("could not read {config_file}: {e}"))
+                .and_then(|content| {
+                    serde_json::from_str(&content).map_err(|e| format!("could not parse json: {e}"))
+                });
+            match files {
+                Ok(files) => files
+                    .iter()
+                    .for_each(|file| detect_in_file(Path::new(file))),
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    }
+
     async fn handle_dlt_stats_subcommand(
         file_path: &Path,
         count: bool,
@@ -1318,6 +1470,12 @@ pub async fn main() -> Result<()> {
     Ok(())
 }
 
+fn utc_string(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|dt| dt.to_rfc3339())
+        .unwrap_or_else(|| String::from("out of range"))
+}
+
 fn duration_report(start: std::time::Instant, report: String) {
     let elapsed = start.elapsed();
     let us = elapsed.as_micros();
@@ -1362,6 +1520,28 @@ fn _initialize_progress_bar(len: u64) -> ProgressBar {
     progress_bar
 }
 
+/// Writes all messages of `producer` line by line; `out` is flushed after each batch, so
+/// the messages of live input show up right away
+async fn write_messages<T: LogMessage, P: Parser<T>, S: ByteSource>(
+    mut producer: MessageProducer<T, P, S>,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let msg_stream = producer.as_stream();
+    pin_mut!(msg_stream);
+    while let Some(items) = msg_stream.next().await {
+        for (_, item) in items {
+            if let MessageStreamItem::Item(
+                ParseYield::Message(msg) | ParseYield::MessageAndAttachment((msg, _)),
+            ) = item
+            {
+                writeln!(out, "{msg}")?;
+            }
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
 /// count how many recognizable DLT messages are stored in a file
 /// each message needs to be equiped with a storage header
 async fn count_dlt_messages(input: &Path) -> Result<u64, DltParseError> {
@@ -9,20 +9,25 @@
 // Dissemination of this information or reproduction of this material
 // is strictly forbidden unless prior written permission is obtained
 // from E.S.R.Labs.
-// use crossbeam_channel as cc;
-// use indexer_base::{
-//     error_reporter::*,
-//     progress::{IndexingProgress, ProgressReporter},
-//     timedline::*,
-//     utils,
-// };
-// use processor::parse::{line_to_timed_line, lookup_regex_for_format_str, DateParseError};
-// use regex::Regex;
+pub use parsers::timestamp::TimeCorrection;
+use parsers::{
+    dlt::{find_storage_header, DltParser, DLT_STORAGE_PATTERN},
+    timestamp::{detect_file_format, TimestampFormat},
+    Error as ParserError, LogMessage, ParseYield, Parser,
+};
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fmt::Debug,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
 use thiserror::Error;
-// use tokio_stream::Stream;
-// use tokio_util::sync::CancellationToken;
+
+/// Size of chunks DLT files are read with
+const DLT_READ_CHUNK_SIZE: usize = 64 * 1024;
 
 #[derive(Debug, Error)]
 pub enum MergeError {
@@ -36,6 +41,17 @@ pub enum MergeError {
     GeneralMergingProblem(String),
 }
 
+/// Defines how entries and their timestamps are read from a file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeInputKind {
+    /// Lines of text; the timestamp of a line is parsed with [`FileMergeOptions::format`].
+    /// Lines without timestamp belong to the previous line.
+    #[default]
+    Text,
+    /// DLT messages with storage header; the timestamp is taken from the storage header
+    Dlt,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileMergeOptions {
     /// Path to the file to merge
@@ -46,451 +62,457 @@ pub struct FileMergeOptions {
     pub year: Option<i32>,
     /// An identifier string for the file
     pub tag: String,
-    /// how we interpret the date string in each line
+    /// how we interpret the date string in each line; if empty, the format is detected
     pub format: String,
+    /// How entries of the file are read
+    #[serde(default)]
+    pub kind: MergeInputKind,
+    /// Correction of the clock of the file, applied after `offset`
+    #[serde(default)]
+    pub correction: Option<TimeCorrection>,
 }
 
-/*
-
-pub fn read_merge_options(
-    f: &mut File,
-    relative_path: Option<impl AsRef<Path>>,
-) -> Result<Vec<FileMergeOptions>, MergeError> {
-    let mut contents = String::new();
-    f.read_to_string(&mut contents)?;
-
-    let v: Vec<FileMergeOptions> = serde_json::from_str(&contents[..])?;
-    match relative_path {
-        Some(relative) => Ok(v
-            .into_iter()
-            .map(|option| FileMergeOptions {
-                path: relative.as_ref().join(option.path).to_string_lossy().into(),
-                ..option
-            })
-            .collect()),
-        None => Ok(v),
-    }
+/// Entry of an input with its timestamp (ms since the Unix epoch)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedEntry {
+    pub timestamp: i64,
+    pub content: String,
 }
 
-#[allow(dead_code)]
-pub struct FileLogEntryProducer {
-    timed_line_iterator: TimedLineIter,
+/// Entry produced by [`Merger`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedEntry {
+    /// Index of the input the entry comes from
+    pub input: usize,
+    pub timestamp: i64,
+    pub content: String,
 }
-#[allow(dead_code)]
-impl FileLogEntryProducer {
-    pub(crate) fn new(
-        read_from: File,
-        tag: String,
-        regex: Regex,
-        year: Option<i32>,
-        time_offset: Option<i64>,
-        current_line_nr: usize,
-    ) -> FileLogEntryProducer {
-        let iter = TimedLineIter::new(read_from, tag, regex, year, time_offset, current_line_nr);
-        FileLogEntryProducer {
-            timed_line_iterator: iter,
-        }
-    }
+
+/// Input of the merge; entries of an input have to be ordered by timestamp
+pub trait TimedInput {
+    /// Returns the next entry or `None` if the input is exhausted
+    fn next_entry(&mut self) -> Result<Option<TimedEntry>, MergeError>;
+
+    /// Count of bytes consumed from the input so far
+    fn read_bytes(&self) -> u64;
 }
 
-impl Stream for FileLogEntryProducer {
-    type Item = Result<Option<TimedLine>, MergeError>;
-    fn poll_next(
-        mut self: std::pin::Pin<&mut Self>,
-        _cx: &mut std::task::Context,
-    ) -> core::task::Poll<Option<Self::Item>> {
-        let next = self.timed_line_iterator.next();
-        match next {
-            Some(msg) => core::task::Poll::Ready(Some(Ok(Some(msg)))),
-            None => core::task::Poll::Ready(None),
+/// Opens the file of `options` as input of the merge
+pub fn open_input(options: &FileMergeOptions) -> Result<Box<dyn TimedInput + Send>, MergeError> {
+    let file = File::open(Path::new(&options.path))?;
+    let mut correction = options.correction.clone().unwrap_or_default();
+    correction.offset = correction
+        .offset
+        .saturating_add(options.offset.unwrap_or(0));
+    Ok(match options.kind {
+        MergeInputKind::Text => {
+            let format = if options.format.is_empty() {
+                detect_file_format(Path::new(&options.path))?
+                    .ok_or_else(|| {
+                        MergeError::WrongConfiguration(format!(
+                            "No timestamp format detected in {}",
+                            options.path
+                        ))
+                    })?
+                    .format
+            } else {
+                options.format.clone()
+            };
+            let format = TimestampFormat::new(&format)
+                .map_err(|e| MergeError::WrongConfiguration(e.to_string()))?;
+            Box::new(
+                TextInput::new(BufReader::new(file), format)
+                    .with_correction(correction)
+                    .with_year(options.year),
+            )
         }
-    }
+        MergeInputKind::Dlt => Box::new(DltInput::new(file).with_correction(correction)),
+    })
 }
 
-pub struct TimedLineIter {
-    reader: BufReader<File>,
-    tag: String,
-    regex: Regex,
+/// Text input; a line without timestamp gets the timestamp of the previous line, lines
+/// before the first timestamp are placed before all other entries.
+pub struct TextInput<R: BufRead> {
+    reader: R,
+    format: TimestampFormat,
+    correction: TimeCorrection,
     year: Option<i32>,
-    time_offset: Option<i64>,
     last_timestamp: i64,
-    pub current_line_nr: usize,
-    reporter: Reporter,
+    read_bytes: u64,
+    line: Vec<u8>,
 }
-impl TimedLineIter {
-    pub fn new(
-        read_from: File,
-        tag: String,
-        regex: Regex,
-        year: Option<i32>,
-        time_offset: Option<i64>,
-        current_line_nr: usize,
-    ) -> TimedLineIter {
-        trace!("create TimedLineIter for {:?}", read_from);
-        TimedLineIter {
-            reader: BufReader::new(read_from),
-            tag,
-            regex,
-            year,
-            time_offset,
-            last_timestamp: 0,
-            current_line_nr,
-            reporter: Default::default(),
+
+impl<R: BufRead> TextInput<R> {
+    pub fn new(reader: R, format: TimestampFormat) -> Self {
+        Self {
+            reader,
+            format,
+            correction: TimeCorrection::default(),
+            year: None,
+            last_timestamp: i64::MIN,
+            read_bytes: 0,
+            line: Vec::new(),
         }
     }
+
+    /// Offset in ms added to each timestamp
+    pub fn with_offset(mut self, offset: i64) -> Self {
+        self.correction = TimeCorrection::offset(offset);
+        self
+    }
+
+    /// Correction applied to each timestamp
+    pub fn with_correction(mut self, correction: TimeCorrection) -> Self {
+        self.correction = correction;
+        self
+    }
+
+    /// Year used if the timestamp format has no year
+    pub fn with_year(mut self, year: Option<i32>) -> Self {
+        self.year = year;
+        self
+    }
 }
-impl Iterator for TimedLineIter {
-    type Item = TimedLine;
-    fn next(&mut self) -> Option<TimedLine> {
-        let mut buf = vec![];
-        match self.reader.read_until(b'\n', &mut buf) {
-            Ok(len) => {
-                if len == 0 {
-                    self.reporter.flush();
-                    return None;
-                }
-                let original_line_length = len;
-                let s = unsafe { std::str::from_utf8_unchecked(&buf) };
-                let trimmed_line = s.trim_matches(utils::is_newline);
-                let timed_line = line_to_timed_line(
-                    trimmed_line,
-                    original_line_length,
-                    &self.tag,
-                    &self.regex,
-                    self.year,
-                    self.time_offset,
-                    self.current_line_nr,
-                    &mut self.reporter,
-                )
-                .unwrap_or_else(|_| TimedLine {
-                    content: trimmed_line.to_string(),
-                    tag: self.tag.to_string(),
-                    timestamp: self.last_timestamp,
-                    original_length: original_line_length,
-                    year_was_missing: false,
-                    line_nr: self.current_line_nr,
-                });
-                self.last_timestamp = timed_line.timestamp;
-                self.current_line_nr += 1;
-                Some(timed_line)
-            }
-            Err(e) => {
-                warn!("error iterating: {}", e);
-                None
-            }
+
+impl<R: BufRead> TimedInput for TextInput<R> {
+    fn next_entry(&mut self) -> Result<Option<TimedEntry>, MergeError> {
+        self.line.clear();
+        let len = self.reader.read_until(b'\n', &mut self.line)?;
+        if len == 0 {
+            return Ok(None);
+        }
+        self.read_bytes += len as u64;
+        let content = String::from_utf8_lossy(&self.line)
+            .trim_end_matches(['\n', '\r'])
+            .to_owned();
+        if let Some(timestamp) = self.format.extract(&content, self.year) {
+            self.last_timestamp = self.correction.apply(timestamp);
         }
+        Ok(Some(TimedEntry {
+            timestamp: self.last_timestamp,
+            content,
+        }))
     }
-}
 
-/// Merge files.
-///
-/// merge all files so that the resulting file contains the log entries in the correct timely order
-/// this function takes a list of `FileMergeOptions` which contains the file path together with
-/// the date format that was detected or specified for the file. Each enty also can contain an offest in ms
-/// that will be added/subtracted from each detected timestamp.
-///
-/// As a result, updates of the progress are reported through the `update_channel`
-/// when finished, the resulting output file is stored in `out_path`
-///
-pub fn merge_files_use_config(
-    options: Vec<FileMergeOptions>,
-    out_path: &Path,
-    append: bool,
-    chunk_size: usize, // used for mapping line numbers to byte positions
-    update_channel: cc::Sender<ChunkResults>,
-    shutdown_token: Option<CancellationToken>,
-) -> Result<(), MergeError> {
-    trace!("merge {} files", options.len());
-    do_the_merge(
-        append,
-        out_path,
-        chunk_size,
-        options,
-        update_channel,
-        shutdown_token,
-    )
+    fn read_bytes(&self) -> u64 {
+        self.read_bytes
+    }
 }
 
-/// Merge files using a config file
-///
-/// Convenience function that enables specifing a file that contains the merge-configuration
-/// important: when using this version of the merge function, the relative path to the config
-/// file is considered and prependend to the file paths in the config file
-///
-pub fn merge_files_use_config_file(
-    config_path: &Path,
-    out_path: &Path,
-    append: bool,
-    chunk_size: usize, // used for mapping line numbers to byte positions
-    update_channel: cc::Sender<ChunkResults>,
-    shutdown_token: Option<CancellationToken>,
-) -> Result<(), MergeError> {
-    trace!("merge files using config from {}", config_path.display());
-    let mut merge_option_file = File::open(config_path)?;
-    let options: Vec<FileMergeOptions> =
-        read_merge_options(&mut merge_option_file, config_path.parent())?;
-    trace!("with options...");
-    do_the_merge(
-        append,
-        out_path,
-        chunk_size,
-        options,
-        update_channel,
-        shutdown_token,
-    )
+/// Input of DLT messages with storage header. Messages are formatted the same way as in
+/// a regular DLT session.
+pub struct DltInput<R: Read> {
+    reader: R,
+    parser: DltParser<'static>,
+    correction: TimeCorrection,
+    buffer: Vec<u8>,
+    /// Position of the first pending byte in `buffer`
+    pos: usize,
+    read_bytes: u64,
+    eof: bool,
 }
 
-fn do_the_merge(
-    append: bool,
-    out_path: &Path,
-    chunk_size: usize,
-    merger_inputs: Vec<FileMergeOptions>,
-    update_channel: cc::Sender<ChunkResults>,
-    shutdown_token: Option<CancellationToken>,
-) -> Result<(), MergeError> {
-    let mut writer = IndexOutput::new(
-        append,
-        out_path,
-        chunk_size,
-        combined_file_size(&merger_inputs)?,
-        update_channel.clone(),
-    )?;
-    trace!("calling merge_inputs_with_writer");
-    merge_inputs_with_writer(&mut writer, merger_inputs, update_channel, shutdown_token)
-}
+impl<R: Read> DltInput<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            parser: DltParser::new(None, None, None, None, true),
+            correction: TimeCorrection::default(),
+            buffer: Vec::new(),
+            pos: 0,
+            read_bytes: 0,
+            eof: false,
+        }
+    }
 
-pub trait Len {
-    fn len(&self) -> Result<u64, std::io::Error>;
-    fn is_empty(&self) -> bool;
-}
+    /// Offset in ms added to each timestamp
+    pub fn with_offset(mut self, offset: i64) -> Self {
+        self.correction = TimeCorrection::offset(offset);
+        self
+    }
 
-impl Len for FileMergeOptions {
-    fn len(&self) -> Result<u64, std::io::Error> {
-        Ok(fs::metadata(&self.path)?.len())
+    /// Correction applied to each timestamp
+    pub fn with_correction(mut self, correction: TimeCorrection) -> Self {
+        self.correction = correction;
+        self
     }
 
-    fn is_empty(&self) -> bool {
-        matches!(self.len(), Ok(0))
+    /// Reads the next chunk into the buffer; returns `false` at the end of input. Consumed
+    /// data is dropped from the buffer before.
+    fn load(&mut self) -> Result<bool, MergeError> {
+        if self.eof {
+            return Ok(false);
+        }
+        self.buffer.drain(..self.pos);
+        self.pos = 0;
+        let pending = self.buffer.len();
+        self.buffer.resize(pending + DLT_READ_CHUNK_SIZE, 0);
+        let len = self.reader.read(&mut self.buffer[pending..])?;
+        self.buffer.truncate(pending + len);
+        self.eof = len == 0;
+        Ok(len > 0)
     }
-}
-impl Len for PathBuf {
-    fn len(&self) -> Result<u64, std::io::Error> {
-        Ok(fs::metadata(self)?.len())
+
+    fn pending(&self) -> usize {
+        self.buffer.len() - self.pos
     }
-    fn is_empty(&self) -> bool {
-        matches!(self.len(), Ok(0))
+
+    fn consume(&mut self, len: usize) {
+        self.pos += len;
+        self.read_bytes += len as u64;
     }
-}
 
-pub trait TimedLogEntry: PartialOrd {
-    fn get_timestamp() -> Option<i64>;
-    fn year_missing() -> bool;
+    /// Skips broken data up to the next storage header. Returns `false` if the end of input
+    /// is reached without finding one.
+    fn skip_broken(&mut self) -> Result<bool, MergeError> {
+        // The broken message itself may start with a valid pattern
+        let mut from = 1;
+        loop {
+            let skipped = self
+                .buffer
+                .get(self.pos + from..)
+                .and_then(find_storage_header);
+            if let Some(skipped) = skipped {
+                self.consume(from + skipped);
+                return Ok(true);
+            }
+            // The end of the buffer could be the beginning of the next pattern
+            let keep = (DLT_STORAGE_PATTERN.len() - 1).min(self.pending());
+            self.consume(self.pending() - keep);
+            from = 0;
+            if !self.load()? {
+                self.consume(self.pending());
+                return Ok(false);
+            }
+        }
+    }
 }
 
-// impl PartialOrd for TimedLogEntry {
-//     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-//         Some(self.get_timestamp().cmp(&other.get_timestamp()))
-//     }
-// }
-
-// impl TimedLogEntry for TimedLine {}
-// // Box<&dyn TimedLineIter> as Box<&dyn Iterator<Item = TimedLogEntry>>
-// // 1.TimedLine
-// // 2.DltRecord
-
-// pub(crate) fn merge_inputs_with_writer<T: Read>(
-//     writer: &mut IndexOutput,
-//     merger_inputs: Vec<impl Iterator<Item = TimedLogEntry>>,
-//     update_channel: cc::Sender<ChunkResults>,
-//     shutdown_rx: Option<&cc::Receiver<()>>,
-// ) -> Result<()> {
-
-pub(crate) fn merge_inputs_with_writer(
-    writer: &mut IndexOutput,
-    merger_inputs: Vec<FileMergeOptions>,
-    update_channel: cc::Sender<ChunkResults>,
-    shutdown_token: Option<CancellationToken>,
-) -> Result<(), MergeError> {
-    trace!("merge_inputs_with_writer ({} files)", merger_inputs.len());
-    let mut lines_with_year_missing = 0usize;
-    // create a peekable iterator for all file inputs
-    let mut readers: Vec<Peekable<TimedLineIter>> = merger_inputs
-        .into_iter()
-        .map(
-            |input: FileMergeOptions| -> Result<Peekable<TimedLineIter>, MergeError> {
-                let file_path = PathBuf::from(input.path);
-                let absolute_path = file_path;
-                trace!("absolute_path was: {:?}", absolute_path);
-                Ok(TimedLineIter::new(
-                    File::open(absolute_path)?,
-                    input.tag,
-                    lookup_regex_for_format_str(&input.format)?,
-                    input.year,
-                    input.offset,
-                    writer.line_nr,
-                )
-                .peekable())
-            },
-        )
-        .filter_map(Result::ok) // TODO better error handling
-        .collect();
-    let mut stopped = false;
-    loop {
-        if stopped {
-            info!("we where stopped while merging");
-            break;
-        }
-        // keep track of the min timestamp together with the index of the file it belongs to
-        // to do this, we peek an entry of each reader and of all those entries we
-        // find the minimum timestamp entry
-        let mut minimum: Option<(i64, usize)> = None;
-        for (i, iter) in readers.iter_mut().enumerate() {
-            if let Some(entry) = iter.peek() {
-                match minimum {
-                    Some((t_min, _)) => {
-                        if entry.timestamp < t_min {
-                            minimum = Some((entry.timestamp, i));
-                        }
-                    }
-                    None => {
-                        minimum = Some((entry.timestamp, i));
+impl<R: Read> TimedInput for DltInput<R> {
+    fn next_entry(&mut self) -> Result<Option<TimedEntry>, MergeError> {
+        loop {
+            if self.pending() == 0 && !self.load()? {
+                return Ok(None);
+            }
+            let parsed = self
+                .parser
+                .parse(&self.buffer[self.pos..], None)
+                .map(|mut items| items.next());
+            match parsed {
+                Ok(Some((consumed, item))) => {
+                    self.consume(consumed);
+                    let msg = match item {
+                        Some(ParseYield::Message(msg))
+                        | Some(ParseYield::MessageAndAttachment((msg, _))) => msg,
+                        Some(ParseYield::Attachment(_)) | None => continue,
+                    };
+                    let Some(timestamp) = msg.timestamp() else {
+                        continue;
+                    };
+                    return Ok(Some(TimedEntry {
+                        timestamp: self.correction.apply(timestamp),
+                        content: msg.to_string(),
+                    }));
+                }
+                Ok(None) | Err(ParserError::Eof) => return Ok(None),
+                Err(ParserError::Incomplete) => {
+                    if !self.load()? {
+                        // Incomplete message at the end of the file
+                        self.consume(self.pending());
+                        return Ok(None);
                     }
                 }
-                if entry.year_was_missing {
-                    lines_with_year_missing += 1
+                Err(ParserError::Parse(_)) => {
+                    // Following messages are found by their storage header
+                    if !self.skip_broken()? {
+                        return Ok(None);
+                    }
                 }
             }
         }
-        if let Some((_, min_index)) = minimum {
-            // we found an entry with a minimal timestamp
-            if let Some(line) = readers[min_index].next() {
-                // important: keep track of how many bytes we processed
-                let trimmed_len = line.content.len();
-                if trimmed_len > 0 {
-                    writer.add_to_chunk(&line.content, &line.tag, line.original_length)?;
-                    stopped = if let Some(shutdown_token) = &shutdown_token {
-                        shutdown_token.is_cancelled()
-                    } else {
-                        false
-                    };
-                }
-            } else {
-                break;
+    }
+
+    fn read_bytes(&self) -> u64 {
+        self.read_bytes
+    }
+}
+
+/// K-way merge of inputs ordered by timestamp. Entries with equal timestamps keep the order
+/// of inputs, entries of one input keep their order anyway.
+pub struct Merger {
+    inputs: Vec<Box<dyn TimedInput + Send>>,
+    /// Next entry of each input
+    heads: Vec<Option<TimedEntry>>,
+    /// Timestamps and indexes of inputs with a pending entry
+    queue: BinaryHeap<Reverse<(i64, usize)>>,
+}
+
+impl Merger {
+    pub fn new(mut inputs: Vec<Box<dyn TimedInput + Send>>) -> Result<Self, MergeError> {
+        let mut heads = Vec::with_capacity(inputs.len());
+        let mut queue = BinaryHeap::with_capacity(inputs.len());
+        for (index, input) in inputs.iter_mut().enumerate() {
+            let head = input.next_entry()?;
+            if let Some(entry) = head.as_ref() {
+                queue.push(Reverse((entry.timestamp, index)));
             }
-        } else {
-            break;
+            heads.push(head);
         }
+        Ok(Self {
+            inputs,
+            heads,
+            queue,
+        })
     }
-    if stopped {
-        debug!("sending IndexingProgress::Stopped");
-        update_channel
-            .send(Ok(IndexingProgress::Stopped))
-            .expect("sending to update_channel failed");
-    } else {
-        if lines_with_year_missing > 0 {
-            report_warning(format!(
-                "year was missing for {lines_with_year_missing} lines"
-            ));
+
+    /// Returns the entry with the lowest timestamp of all inputs or `None` if all inputs
+    /// are exhausted
+    pub fn next_entry(&mut self) -> Result<Option<MergedEntry>, MergeError> {
+        let Some(Reverse((_, index))) = self.queue.pop() else {
+            return Ok(None);
+        };
+        let entry = self.heads[index].take().ok_or_else(|| {
+            MergeError::GeneralMergingProblem(format!("No pending entry of input {index}"))
+        })?;
+        let next = self.inputs[index].next_entry()?;
+        if let Some(next) = next.as_ref() {
+            self.queue.push(Reverse((next.timestamp, index)));
         }
-        writer.write_rest()?;
+        self.heads[index] = next;
+        Ok(Some(MergedEntry {
+            input: index,
+            timestamp: entry.timestamp,
+            content: entry.content,
+        }))
     }
-    update_channel
-        .send(Ok(IndexingProgress::Finished))
-        .expect("sending to update channel failed");
-    Ok(())
-}
 
-pub struct IndexOutput {
-    pub(crate) line_nr: usize,
-    update_channel: cc::Sender<ChunkResults>,
-    chunk_count: usize,
-    chunk_factory: ChunkFactory,
-    buf_writer: BufWriter<File>,
-    progress_reporter: ProgressReporter<Chunk>,
+    /// Count of bytes consumed from all inputs so far
+    pub fn read_bytes(&self) -> u64 {
+        self.inputs.iter().map(|input| input.read_bytes()).sum()
+    }
 }
 
-pub(crate) fn combined_file_size<T>(paths: &[T]) -> Result<u64, MergeError>
-where
-    T: Len + Debug,
-{
-    paths.iter().try_fold(0, |acc, x| match x.len() {
-        Ok(len) => Ok(acc + len),
-        Err(e) => Err(MergeError::GeneralMergingProblem(format!(
-            "error getting combined file size ({e})"
-        ))),
-    })
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
 
-impl IndexOutput {
-    fn new(
-        append: bool,
-        out_path: &Path,
-        chunk_size: usize,
-        combined_size: u64,
-        update_channel: cc::Sender<ChunkResults>,
-    ) -> Result<Self, MergeError> {
-        let out_file: File = if append {
-            std::fs::OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(out_path)?
-        } else {
-            File::create(out_path)?
-        };
-        let line_nr = if append {
-            utils::next_line_nr(out_path)
-                .map_err(|e| MergeError::GeneralMergingProblem(format!("{e:?}")))?
-        } else {
-            0
-        };
-        let original_file_size = out_file.metadata()?.len() as usize;
-        let buf_writer = BufWriter::with_capacity(100 * 1024 * 1024, out_file);
-
-        let progress_reporter = ProgressReporter::new(combined_size, update_channel.clone());
-        Ok(IndexOutput {
-            line_nr,
-            update_channel,
-            chunk_factory: ChunkFactory::new(chunk_size, original_file_size),
-            chunk_count: 0usize,
-            buf_writer,
-            progress_reporter,
-        })
+    fn text_input(content: &str, offset: i64) -> Box<dyn TimedInput + Send> {
+        Box::new(
+            TextInput::new(
+                Cursor::new(content.as_bytes().to_vec()),
+                TimestampFormat::new("YYYY-MM-DD hh:mm:ss.s").unwrap(),
+            )
+            .with_offset(offset),
+        )
     }
 
-    pub(crate) fn add_to_chunk(
-        &mut self,
-        content: &str,
-        tag: &str,
-        original_len: usize,
-    ) -> Result<(), MergeError> {
-        let additional_bytes =
-            utils::write_tagged_line(tag, &mut self.buf_writer, content, self.line_nr, true, None)?;
-        self.line_nr += 1;
-        self.progress_reporter.make_progress(original_len);
-        if let Some(chunk) = self.chunk_factory.add_bytes(
-            self.line_nr, // TODO avoid passing in this line...error prone
-            additional_bytes,
-        ) {
-            self.chunk_count += 1;
-            self.buf_writer.flush()?;
-            self.update_channel
-                .send(Ok(IndexingProgress::GotItem { item: chunk }))
-                .expect("sending to update channel failed");
+    fn merge(inputs: Vec<Box<dyn TimedInput + Send>>) -> Vec<(usize, String)> {
+        let mut merger = Merger::new(inputs).unwrap();
+        let mut merged = Vec::new();
+        while let Some(entry) = merger.next_entry().unwrap() {
+            merged.push((entry.input, entry.content));
         }
-        Ok(())
+        merged
+    }
+
+    #[test]
+    fn test_merge_ordered_by_timestamp() {
+        let merged = merge(vec![
+            text_input(
+                "2024-03-05 10:00:00.000 a1\n2024-03-05 10:00:02.000 a2\n",
+                0,
+            ),
+            text_input(
+                "2024-03-05 10:00:01.000 b1\n  continued\n2024-03-05 10:00:03.000 b2\n",
+                0,
+            ),
+            text_input("", 0),
+        ]);
+        assert_eq!(
+            merged,
+            vec![
+                (0, String::from("2024-03-05 10:00:00.000 a1")),
+                (1, String::from("2024-03-05 10:00:01.000 b1")),
+                (1, String::from("  continued")),
+                (0, String::from("2024-03-05 10:00:02.000 a2")),
+                (1, String::from("2024-03-05 10:00:03.000 b2")),
+            ]
+        );
     }
-    pub(crate) fn write_rest(&mut self) -> Result<(), MergeError> {
-        self.buf_writer.flush()?;
-        if let Some(chunk) = self
-            .chunk_factory
-            .create_last_chunk(self.line_nr, self.chunk_count > 0)
-        {
-            self.update_channel
-                .send(Ok(IndexingProgress::GotItem { item: chunk }))
-                .expect("update_channel send failed");
+
+    #[test]
+    fn test_merge_with_offset() {
+        let merged = merge(vec![
+            text_input(
+                "2024-03-05 10:00:00.000 a1\n2024-03-05 10:00:02.000 a2\n",
+                0,
+            ),
+            text_input("2024-03-05 10:00:01.000 b1\n", 1500),
+        ]);
+        let inputs: Vec<usize> = merged.iter().map(|(input, _)| *input).collect();
+        assert_eq!(inputs, vec![0, 0, 1]);
+    }
+
+    /// DLT message with storage header and a non-verbose payload of the message id only
+    fn dlt_message(seconds: u32, counter: u8) -> Vec<u8> {
+        let mut message = DLT_STORAGE_PATTERN.to_vec();
+        message.extend_from_slice(&seconds.to_le_bytes());
+        message.extend_from_slice(&0_i32.to_le_bytes());
+        message.extend_from_slice(b"ECU1");
+        // Version 1 without extended header, length of standard header and payload
+        message.extend_from_slice(&[0x20, counter, 0x00, 0x08]);
+        message.extend_from_slice(&[0x00, 0x00, 0x00, counter]);
+        message
+    }
+
+    #[test]
+    fn test_dlt_input_skips_corrupt_message() {
+        let mut broken = dlt_message(1_700_000_001, 1);
+        // Standard header claims less than the header itself
+        broken[19] = 0x01;
+        let content = [
+            dlt_message(1_700_000_000, 0),
+            broken,
+            b"garbage".to_vec(),
+            dlt_message(1_700_000_002, 2),
+            b"DL".to_vec(),
+            dlt_message(1_700_000_003, 3),
+        ]
+        .concat();
+        let mut input = DltInput::new(Cursor::new(content.clone()));
+        let mut timestamps = Vec::new();
+        while let Some(entry) = input.next_entry().unwrap() {
+            timestamps.push(entry.timestamp);
         }
-        Ok(())
+        assert_eq!(
+            timestamps,
+            vec![1_700_000_000_000, 1_700_000_002_000, 1_700_000_003_000]
+        );
+        assert_eq!(input.read_bytes(), content.len() as u64);
     }
-}
 
-*/
+    #[test]
+    fn test_merge_with_drift() {
+        // The clock of b gains 1 s per 10 s since 10:00:00
+        let drift = TimeCorrection {
+            offset: 0,
+            drift: -0.1 / 1.1,
+            origin: 1_709_632_800_000,
+        };
+        let b = TextInput::new(
+            Cursor::new(b"2024-03-05 10:00:00.500 b1\n2024-03-05 10:00:11.000 b2\n".to_vec()),
+            TimestampFormat::new("YYYY-MM-DD hh:mm:ss.s").unwrap(),
+        )
+        .with_correction(drift);
+        let merged = merge(vec![
+            text_input(
+                "2024-03-05 10:00:01.000 a1\n2024-03-05 10:00:10.500 a2\n",
+                0,
+            ),
+            Box::new(b),
+        ]);
+        let inputs: Vec<usize> = merged.iter().map(|(input, _)| *input).collect();
+        assert_eq!(inputs, vec![1, 0, 1, 0]);
+    }
+}
@@ -0,0 +1,454 @@
+//! # DLT control messages
+//!
+//! Requests which can be sent to a dlt-daemon, serialized as DLT control frames, and the
+//! decoding of the control messages which are exchanged with a daemon.
+use dlt_core::service_id::service_id_lookup;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub const SERVICE_SET_LOG_LEVEL: u32 = 0x01;
+pub const SERVICE_GET_LOG_INFO: u32 = 0x03;
+pub const SERVICE_SET_DEFAULT_TRACE_STATUS: u32 = 0x12;
+pub const SERVICE_GET_SOFTWARE_VERSION: u32 = 0x13;
+const SERVICE_UNREGISTER_CONTEXT: u32 = 0xF01;
+const SERVICE_CONNECTION_INFO: u32 = 0xF02;
+const SERVICE_TIMEZONE: u32 = 0xF03;
+const SERVICE_MARKER: u32 = 0xF04;
+
+/// Version 1 with extended header and ECU id; the payload is little endian
+const HEADER_TYPE: u8 = 0x25;
+/// Verbose flag cleared, message type control, message info request
+const MESSAGE_INFO_CONTROL_REQUEST: u8 = 0x16;
+const ECU_ID: &str = "CHMK";
+const APP_ID: &str = "CHMK";
+const CONTEXT_ID: &str = "CTRL";
+/// Communication interface named in requests
+const COM_INTERFACE: &str = "remo";
+/// Option of the log info request to get levels, trace status and descriptions
+const LOG_INFO_WITH_DESCRIPTIONS: u8 = 7;
+
+#[derive(Error, Debug)]
+pub enum ControlError {
+    #[error("Invalid DLT id '{0}': up to 4 ASCII characters expected")]
+    InvalidId(String),
+}
+
+/// Log level which is set for a context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ControlLogLevel {
+    /// Level which is configured by default on the daemon
+    Default,
+    Off,
+    Fatal,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Verbose,
+}
+
+impl ControlLogLevel {
+    fn value(self) -> i8 {
+        match self {
+            Self::Default => -1,
+            Self::Off => 0,
+            Self::Fatal => 1,
+            Self::Error => 2,
+            Self::Warn => 3,
+            Self::Info => 4,
+            Self::Debug => 5,
+            Self::Verbose => 6,
+        }
+    }
+}
+
+/// Control request sent to a dlt-daemon
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ControlRequest {
+    /// Sets the log level of a context; without a context id the level is set for all
+    /// contexts of the application
+    SetLogLevel {
+        app_id: String,
+        context_id: Option<String>,
+        level: ControlLogLevel,
+    },
+    /// Switches the trace status of contexts without an own trace status
+    SetDefaultTraceStatus(bool),
+    /// Requests the levels, trace status and descriptions of the registered contexts;
+    /// without ids all applications or contexts are requested
+    GetLogInfo {
+        app_id: Option<String>,
+        context_id: Option<String>,
+    },
+    GetSoftwareVersion,
+}
+
+impl ControlRequest {
+    /// Serializes the request as a DLT message without storage header, as it is expected
+    /// by a dlt-daemon on its TCP port
+    pub fn to_frame(&self) -> Result<Vec<u8>, ControlError> {
+        let mut payload = Vec::new();
+        match self {
+            Self::SetLogLevel {
+                app_id,
+                context_id,
+                level,
+            } => {
+                payload.extend_from_slice(&SERVICE_SET_LOG_LEVEL.to_le_bytes());
+                payload.extend_from_slice(&id_bytes(app_id)?);
+                payload.extend_from_slice(&id_bytes(context_id.as_deref().unwrap_or_default())?);
+                payload.push(level.value() as u8);
+                payload.extend_from_slice(&id_bytes(COM_INTERFACE)?);
+            }
+            Self::SetDefaultTraceStatus(on) => {
+                payload.extend_from_slice(&SERVICE_SET_DEFAULT_TRACE_STATUS.to_le_bytes());
+                payload.push(u8::from(*on));
+                payload.extend_from_slice(&id_bytes(COM_INTERFACE)?);
+            }
+            Self::GetLogInfo { app_id, context_id } => {
+                payload.extend_from_slice(&SERVICE_GET_LOG_INFO.to_le_bytes());
+                payload.push(LOG_INFO_WITH_DESCRIPTIONS);
+                payload.extend_from_slice(&id_bytes(app_id.as_deref().unwrap_or_default())?);
+                payload.extend_from_slice(&id_bytes(context_id.as_deref().unwrap_or_default())?);
+                payload.extend_from_slice(&id_bytes(COM_INTERFACE)?);
+            }
+            Self::GetSoftwareVersion => {
+                payload.extend_from_slice(&SERVICE_GET_SOFTWARE_VERSION.to_le_bytes());
+            }
+        }
+        // Standard header with ECU id (8 bytes) and extended header (10 bytes)
+        let len = 8 + 10 + payload.len();
+        let mut frame = Vec::with_capacity(len);
+        frame.push(HEADER_TYPE);
+        frame.push(0);
+        frame.extend_from_slice(&(len as u16).to_be_bytes());
+        frame.extend_from_slice(&id_bytes(ECU_ID)?);
+        frame.push(MESSAGE_INFO_CONTROL_REQUEST);
+        frame.push(0);
+        frame.extend_from_slice(&id_bytes(APP_ID)?);
+        frame.extend_from_slice(&id_bytes(CONTEXT_ID)?);
+        frame.extend_from_slice(&payload);
+        Ok(frame)
+    }
+}
+
+/// DLT ids are padded with zeros to 4 bytes
+fn id_bytes(id: &str) -> Result<[u8; 4], ControlError> {
+    if id.len() > 4 || !id.is_ascii() {
+        return Err(ControlError::InvalidId(id.to_owned()));
+    }
+    let mut bytes = [0u8; 4];
+    bytes[..id.len()].copy_from_slice(id.as_bytes());
+    Ok(bytes)
+}
+
+/// Decoded payload of a control message
+#[derive(Debug)]
+pub struct ControlMessage {
+    pub service_id: Option<u32>,
+    /// Readable fields of a response, starting with its status; empty for requests
+    pub fields: Vec<String>,
+}
+
+impl ControlMessage {
+    /// Decodes the `payload` of a control message, which starts with the service id
+    pub fn parse(payload: &[u8], big_endian: bool, response: bool) -> Self {
+        let mut reader = Reader {
+            data: payload,
+            big_endian,
+        };
+        let Some(service_id) = reader.u32() else {
+            return Self {
+                service_id: None,
+                fields: Vec::new(),
+            };
+        };
+        let mut fields = Vec::new();
+        if response {
+            decode_response(service_id, &mut reader, &mut fields);
+            if !reader.data.is_empty() {
+                fields.push(format!("{:02X?}", reader.data));
+            }
+        }
+        Self {
+            service_id: Some(service_id),
+            fields,
+        }
+    }
+
+    /// Name of the service
+    pub fn name(&self) -> &'static str {
+        match self.service_id {
+            Some(SERVICE_UNREGISTER_CONTEXT) => "unregister_context",
+            Some(SERVICE_CONNECTION_INFO) => "connection_info",
+            Some(SERVICE_TIMEZONE) => "timezone",
+            Some(SERVICE_MARKER) => "marker",
+            Some(id) => u8::try_from(id)
+                .ok()
+                .and_then(service_id_lookup)
+                .map_or("[Unknown CtrlCommand]", |(name, _desc)| name),
+            None => "[Unknown CtrlCommand]",
+        }
+    }
+}
+
+fn decode_response(service_id: u32, reader: &mut Reader, fields: &mut Vec<String>) {
+    let Some(status) = reader.u8() else {
+        return;
+    };
+    let status_name = match status {
+        0 => Some("ok"),
+        1 => Some("not supported"),
+        2 => Some("error"),
+        3..=7 if service_id == SERVICE_GET_LOG_INFO => Some("ok"),
+        8 => Some("no matching context"),
+        9 => Some("response overflow"),
+        _ => None,
+    };
+    fields.push(status_name.map_or_else(|| format!("status {status}"), str::to_owned));
+    match service_id {
+        SERVICE_GET_SOFTWARE_VERSION if status == 0 => {
+            if let Some(version) = reader.u32().and_then(|len| reader.string(len as usize)) {
+                fields.push(version);
+            }
+        }
+        SERVICE_GET_LOG_INFO if (3..=7).contains(&status) => {
+            decode_log_info(status, reader, fields);
+        }
+        SERVICE_CONNECTION_INFO if status == 0 => match reader.u8() {
+            Some(1) => fields.push("disconnected".to_owned()),
+            Some(2) => fields.push("connected".to_owned()),
+            Some(state) => fields.push(format!("state {state}")),
+            None => {}
+        },
+        _ => {}
+    }
+}
+
+/// The log info contains the levels (status 4, 6, 7), the trace status (5, 6, 7) and the
+/// descriptions (7) of contexts
+fn decode_log_info(status: u8, reader: &mut Reader, fields: &mut Vec<String>) -> Option<()> {
+    let with_level = matches!(status, 4 | 6 | 7);
+    let with_trace_status = matches!(status, 5..=7);
+    let with_description = status == 7;
+    let apps = reader.u16()?;
+    for _ in 0..apps {
+        let app_id = reader.id()?;
+        let contexts = reader.u16()?;
+        for _ in 0..contexts {
+            let mut field = format!("{app_id}/{}", reader.id()?);
+            if with_level {
+                field.push_str(&format!(" level:{}", level_name(reader.u8()? as i8)));
+            }
+            if with_trace_status {
+                field.push_str(&format!(" trace:{}", trace_status_name(reader.u8()? as i8)));
+            }
+            if with_description {
+                let len = reader.u16()?;
+                let description = reader.string(len as usize)?;
+                if !description.is_empty() {
+                    field.push_str(&format!(" \"{description}\""));
+                }
+            }
+            fields.push(field);
+        }
+        if with_description {
+            let len = reader.u16()?;
+            let description = reader.string(len as usize)?;
+            if !description.is_empty() {
+                fields.push(format!("{app_id} \"{description}\""));
+            }
+        }
+    }
+    // Communication interface of the response
+    if reader.data.len() == 4 {
+        reader.id()?;
+    }
+    Some(())
+}
+
+fn level_name(level: i8) -> String {
+    match level {
+        -1 => "default".to_owned(),
+        0 => "off".to_owned(),
+        1 => "fatal".to_owned(),
+        2 => "error".to_owned(),
+        3 => "warn".to_owned(),
+        4 => "info".to_owned(),
+        5 => "debug".to_owned(),
+        6 => "verbose".to_owned(),
+        _ => level.to_string(),
+    }
+}
+
+fn trace_status_name(status: i8) -> String {
+    match status {
+        -1 => "default".to_owned(),
+        0 => "off".to_owned(),
+        1 => "on".to_owned(),
+        _ => status.to_string(),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.data.split_first_chunk::<N>()?;
+        self.data = rest;
+        Some(*bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take()?;
+        Some(if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let bytes = self.take()?;
+        Some(if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    }
+
+    fn string(&mut self, len: usize) -> Option<String> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .to_owned(),
+        )
+    }
+
+    fn id(&mut self) -> Option<String> {
+        self.string(4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dlt_core::{
+        dlt::{ControlType, MessageType, PayloadContent},
+        parse::{dlt_message, ParsedMessage},
+    };
+
+    #[test]
+    fn test_set_log_level_frame() {
+        let frame = ControlRequest::SetLogLevel {
+            app_id: String::from("APP"),
+            context_id: Some(String::from("CTX1")),
+            level: ControlLogLevel::Verbose,
+        }
+        .to_frame()
+        .unwrap();
+        assert_eq!(frame.len(), 18 + 17);
+        let (rest, ParsedMessage::Item(message)) = dlt_message(&frame, None, false).unwrap() else {
+            panic!("no message parsed");
+        };
+        assert!(rest.is_empty());
+        let header = message.extended_header.as_ref().unwrap();
+        assert_eq!(
+            header.message_type,
+            MessageType::Control(ControlType::Request)
+        );
+        let PayloadContent::ControlMsg(ctrl_id, data) = &message.payload else {
+            panic!("no control message");
+        };
+        let payload = [&[ctrl_id.value()], data.as_slice()].concat();
+        assert_eq!(
+            payload,
+            [
+                &[1, 0, 0, 0][..],
+                b"APP\0",
+                b"CTX1",
+                &[6],
+                COM_INTERFACE.as_bytes()
+            ]
+            .concat()
+        );
+        assert_eq!(
+            ControlMessage::parse(&payload, false, false).name(),
+            "set_log_level"
+        );
+    }
+
+    #[test]
+    fn test_invalid_id() {
+        let request = ControlRequest::GetLogInfo {
+            app_id: Some(String::from("TOOLONG")),
+            context_id: None,
+        };
+        assert!(matches!(
+            request.to_frame(),
+            Err(ControlError::InvalidId(id)) if id == "TOOLONG"
+        ));
+    }
+
+    #[test]
+    fn test_software_version_response() {
+        let payload = [&[0x13, 0, 0, 0, 0, 5, 0, 0, 0][..], b"2.18\0"].concat();
+        let message = ControlMessage::parse(&payload, false, true);
+        assert_eq!(message.service_id, Some(SERVICE_GET_SOFTWARE_VERSION));
+        assert_eq!(message.fields, vec!["ok", "2.18"]);
+    }
+
+    #[test]
+    fn test_log_info_response() {
+        let payload = [
+            &[0, 0, 0, 3, 7][..],
+            // One application with two contexts
+            &[0, 1],
+            b"APP1",
+            &[0, 2],
+            b"CTX1",
+            &[4, 0xFF, 0, 4],
+            b"Main",
+            b"CTX2",
+            &[0xFF, 1, 0, 0],
+            &[0, 3],
+            b"App",
+            b"remo",
+        ]
+        .concat();
+        let message = ControlMessage::parse(&payload, true, true);
+        assert_eq!(message.name(), "get_log_info");
+        assert_eq!(
+            message.fields,
+            vec![
+                "ok",
+                "APP1/CTX1 level:info trace:default \"Main\"",
+                "APP1/CTX2 level:default trace:on",
+                "APP1 \"App\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_status_response() {
+        let message = ControlMessage::parse(&[1, 0, 0, 0, 2], false, true);
+        assert_eq!(message.name(), "set_log_level");
+        assert_eq!(message.fields, vec!["error"]);
+        let message =
+            ControlMessage::parse(&[2, 0x0F, 0, 0, 0, 2, 0x72, 0x65, 0x6D, 0x6F], false, true);
+        assert_eq!(message.name(), "connection_info");
+        assert_eq!(message.fields, vec!["ok", "connected", "[72, 65, 6D, 6F]"]);
+    }
+}
@@ -11,18 +11,21 @@
 // from E.S.R.Labs.
 
 //! # Formatting dlt messages as text
-use crate::someip::{FibexMetadata as FibexSomeipMetadata, SomeipParser};
-use chrono::prelude::{DateTime, Utc};
+use crate::{
+    dlt::control::ControlMessage,
+    someip::{FibexMetadata as FibexSomeipMetadata, SomeipParser},
+    NetworkMeta,
+};
+use chrono::prelude::{DateTime, NaiveDateTime, Utc};
 use chrono_tz::Tz;
 use dlt_core::{
     dlt::{
-        Argument, ControlType, DltTimeStamp, ExtendedHeader, LogLevel, Message, MessageType,
-        NetworkTraceType, PayloadContent, StandardHeader, StorageHeader, StringCoding, TypeInfo,
-        TypeInfoKind, Value,
+        Argument, ControlType, DltTimeStamp, Endianness, ExtendedHeader, LogLevel, Message,
+        MessageType, NetworkTraceType, PayloadContent, StandardHeader, StorageHeader, StringCoding,
+        TypeInfo, TypeInfoKind, Value,
     },
     fibex::{extract_metadata, FibexMetadata as FibexDltMetadata},
     parse::construct_arguments,
-    service_id::service_id_lookup,
 };
 use log::trace;
 use someip_tools::parse_prefix;
@@ -31,7 +34,8 @@ use serde::ser::{Serialize, SerializeStruct, Serializer};
 
 use std::{
     fmt::{self, Formatter},
-    str,
+    iter, str,
+    time::Duration,
 };
 
 const DLT_COLUMN_SENTINAL: char = '\u{0004}';
@@ -201,6 +205,9 @@ pub struct FormattableMessage<'a> {
     pub fibex_dlt_metadata: Option<&'a FibexDltMetadata>,
     pub fibex_someip_metadata: Option<&'a FibexSomeipMetadata>,
     pub options: Option<&'a FormatOptions>,
+    /// Network metadata of the packet the message was received in; it's appended as
+    /// additional columns
+    pub network: Option<NetworkMeta>,
 }
 
 impl Serialize for FormattableMessage<'_> {
@@ -208,7 +215,8 @@ impl Serialize for FormattableMessage<'_> {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Message", 10)?;
+        let network_fields = if self.network.is_some() { 4 } else { 0 };
+        let mut state = serializer.serialize_struct("Message", 11 + network_fields)?;
         let header = &self.message.header;
         let storage_header = &self.message.storage_header;
         let ext_header = &self.message.extended_header;
@@ -275,14 +283,18 @@ impl Serialize for FormattableMessage<'_> {
                     };
                 }
             }
-            PayloadContent::ControlMsg(ctrl_id, _data) => {
+            PayloadContent::ControlMsg(ctrl_id, data) => {
                 state.serialize_field("app-id", &ext_header_app_id)?;
                 state.serialize_field("context-id", &ext_header_context_id)?;
                 state.serialize_field("message-type", &ext_header_msg_type)?;
-                match service_id_lookup(ctrl_id.value()) {
-                    Some((name, _desc)) => state.serialize_field("payload", name)?,
-                    None => state.serialize_field("payload", "[Unknown CtrlCommand]")?,
-                }
+                let control = self.control_message(ctrl_id, data);
+                state.serialize_field(
+                    "payload",
+                    &iter::once(control.name().to_owned())
+                        .chain(control.fields)
+                        .collect::<Vec<String>>()
+                        .join("|"),
+                )?;
             }
             PayloadContent::NetworkTrace(slices) => {
                 state.serialize_field("app-id", &ext_header_app_id)?;
@@ -296,6 +308,13 @@ impl Serialize for FormattableMessage<'_> {
                 state.serialize_field("payload", &arg_string)?;
             }
         }
+        if let Some(network) = &self.network {
+            let [source, destination, _, _] = network.columns();
+            state.serialize_field("source", &source)?;
+            state.serialize_field("destination", &destination)?;
+            state.serialize_field("vlan-id", &network.vlan)?;
+            state.serialize_field("interface", &network.interface)?;
+        }
         state.end()
     }
 }
@@ -307,6 +326,7 @@ impl From<Message> for FormattableMessage<'_> {
             fibex_dlt_metadata: None,
             fibex_someip_metadata: None,
             options: None,
+            network: None,
         }
     }
 }
@@ -389,11 +409,12 @@ impl FormattableMessage<'_> {
                     ))
                 }
             }
-            PayloadContent::ControlMsg(ctrl_id, _data) => {
-                let payload_string: String = match service_id_lookup(ctrl_id.value()) {
-                    Some((name, _desc)) => String::from(name),
-                    None => "[Unknown CtrlCommand]".to_owned(),
-                };
+            PayloadContent::ControlMsg(ctrl_id, data) => {
+                let control = self.control_message(ctrl_id, data);
+                let payload_string = iter::once(control.name().to_owned())
+                    .chain(control.fields)
+                    .collect::<Vec<String>>()
+                    .join("|");
                 Ok(PrintableMessage::new(
                     ext_h_app_id,
                     eh_ctx_id,
@@ -417,6 +438,23 @@ impl FormattableMessage<'_> {
         }
     }
 
+    /// Decodes a control message; the parser splits the first byte of the service id off
+    /// the payload
+    fn control_message(&self, ctrl_id: &ControlType, data: &[u8]) -> ControlMessage {
+        let payload = [&[ctrl_id.value()], data].concat();
+        let response = self.message.extended_header.as_ref().is_some_and(|ext| {
+            matches!(
+                ext.message_type,
+                MessageType::Control(ControlType::Response)
+            )
+        });
+        ControlMessage::parse(
+            &payload,
+            matches!(self.message.header.endianness, Endianness::Big),
+            response,
+        )
+    }
+
     fn write_app_id_context_id_and_message_type(
         &self,
         f: &mut fmt::Formatter,
@@ -556,7 +594,25 @@ impl fmt::Display for FormattableMessage<'_> {
     /// context-id
     ///
     /// payload
+    ///
+    /// ********* network (if known) ********
+    /// source
+    /// destination
+    /// vlan-id
+    /// interface
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
+        self.write_message(f)?;
+        if let Some(network) = &self.network {
+            for column in network.columns() {
+                write!(f, "{DLT_COLUMN_SENTINAL}{column}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FormattableMessage<'_> {
+    fn write_message(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         if let Some(h) = &self.message.storage_header {
             let tz = self.options.map(|o| o.tz);
             match tz {
@@ -580,12 +636,14 @@ impl fmt::Display for FormattableMessage<'_> {
                     .try_for_each(|arg| write!(f, "{}{}", DLT_ARGUMENT_SENTINAL, DltArgument(arg)))
             }
             PayloadContent::NonVerbose(id, data) => self.format_nonverbose_data(*id, data, f),
-            PayloadContent::ControlMsg(ctrl_id, _data) => {
+            PayloadContent::ControlMsg(ctrl_id, data) => {
                 self.write_app_id_context_id_and_message_type(f)?;
-                match service_id_lookup(ctrl_id.value()) {
-                    Some((name, _desc)) => write!(f, "[{name}]"),
-                    None => write!(f, "[Unknown CtrlCommand]"),
-                }
+                let control = self.control_message(ctrl_id, data);
+                write!(f, "[{}]", control.name())?;
+                control
+                    .fields
+                    .iter()
+                    .try_for_each(|field| write!(f, "{DLT_ARGUMENT_SENTINAL}{field}"))
             }
             PayloadContent::NetworkTrace(slices) => {
                 self.write_app_id_context_id_and_message_type(f)?;
@@ -646,6 +704,28 @@ fn write_tz_string(
     }
 }
 
+/// Shifts the date-time column of a formatted message by `delta` ms. Returns `None` if `row`
+/// doesn't start with a date-time column (message without storage header).
+pub fn shift_datetime_column(row: &str, delta: i64) -> Option<String> {
+    let (column, rest) = row.split_once(DLT_COLUMN_SENTINAL)?;
+    let shifted = if let Ok(time) = humantime::parse_rfc3339(column) {
+        let delta_abs = Duration::from_millis(delta.unsigned_abs());
+        let time = if delta < 0 {
+            time.checked_sub(delta_abs)
+        } else {
+            time.checked_add(delta_abs)
+        }?;
+        humantime::format_rfc3339(time).to_string()
+    } else {
+        // Formatted with a time zone: local date-time followed by the zone
+        let (local, zone) = column.rsplit_once(' ')?;
+        let local = NaiveDateTime::parse_from_str(local, "%Y-%m-%d %H:%M:%S%.f").ok()?;
+        let local = local.checked_add_signed(chrono::Duration::milliseconds(delta))?;
+        format!("{local} {zone}")
+    };
+    Some(format!("{shifted}{DLT_COLUMN_SENTINAL}{rest}"))
+}
+
 pub fn utc_string(time_stamp: &DltTimeStamp) -> String {
     let dt: Option<DateTime<Utc>> = DateTime::from_timestamp(
         i64::from(time_stamp.seconds),
@@ -705,3 +785,27 @@ struct NonVerboseInfo<'a> {
     msg_type: Option<MessageType>,
     arguments: Vec<Argument>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_datetime_column() {
+        let row = |datetime: &str| format!("{datetime}{DLT_COLUMN_SENTINAL}ECU1");
+        assert_eq!(
+            shift_datetime_column(&row("2023-11-14T22:13:20.123456000Z"), 1_500),
+            Some(row("2023-11-14T22:13:21.623456000Z"))
+        );
+        assert_eq!(
+            shift_datetime_column(&row("2023-11-14T22:13:20.123000000Z"), -200),
+            Some(row("2023-11-14T22:13:19.923000000Z"))
+        );
+        assert_eq!(
+            shift_datetime_column(&row("2023-11-14 23:13:20.123456 CET"), 1_000),
+            Some(row("2023-11-14 23:13:21.123456 CET"))
+        );
+        // Message without storage header starts with the version of the standard header
+        assert_eq!(shift_datetime_column(&row("1"), 1_000), None);
+    }
+}
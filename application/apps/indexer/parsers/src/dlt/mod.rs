@@ -1,9 +1,10 @@
 pub mod attachment;
+pub mod control;
 pub mod fmt;
 
 use crate::{
-    dlt::fmt::FormattableMessage, someip::FibexMetadata as FibexSomeipMetadata, Error, LogMessage,
-    ParseYield, Parser,
+    dlt::fmt::FormattableMessage, someip::FibexMetadata as FibexSomeipMetadata,
+    timestamp::TimeCorrection, Error, LogMessage, NetworkMeta, ParseYield, Parser,
 };
 use byteorder::{BigEndian, WriteBytesExt};
 use dlt_core::{
@@ -20,6 +21,78 @@ use std::{io::Write, iter, ops::Range};
 
 use self::{attachment::FtScanner, fmt::FormatOptions};
 
+/// Pattern each storage header of a DLT message starts with
+pub const DLT_STORAGE_PATTERN: &[u8] = b"DLT\x01";
+/// Length of the storage header of a DLT message
+pub const DLT_STORAGE_HEADER_LEN: usize = 16;
+/// Length of the standard header fields up to and including the length of the message
+pub const DLT_FRAME_HEADER_LEN: usize = 4;
+/// Flag of the header type: the standard header contains the ECU id
+const DLT_WITH_ECU_ID: u8 = 0x04;
+
+/// Position of the first storage header pattern in `bytes`
+pub fn find_storage_header(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .windows(DLT_STORAGE_PATTERN.len())
+        .position(|window| window == DLT_STORAGE_PATTERN)
+}
+
+/// Length of the DLT message at the start of `bytes` as given by its standard header.
+/// Returns `None` if the header isn't complete.
+pub fn dlt_message_len(bytes: &[u8]) -> Option<usize> {
+    let header = bytes.get(..DLT_FRAME_HEADER_LEN)?;
+    Some(u16::from_be_bytes([header[2], header[3]]) as usize)
+}
+
+/// Timestamp (ms) of the storage header at the start of `bytes` and the length of the
+/// header together with the DLT message following it. Returns `None` if `bytes` don't start
+/// with a storage header or the standard header of the message isn't complete.
+pub fn storage_header_frame(bytes: &[u8]) -> Option<(u64, usize)> {
+    let header = bytes
+        .get(..DLT_STORAGE_HEADER_LEN)
+        .filter(|header| header.starts_with(DLT_STORAGE_PATTERN))?;
+    let seconds = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    let microseconds = i32::from_le_bytes([header[8], header[9], header[10], header[11]]);
+    let len = dlt_message_len(&bytes[DLT_STORAGE_HEADER_LEN..])?;
+    Some((
+        u64::from(seconds) * 1000 + microseconds.max(0) as u64 / 1000,
+        DLT_STORAGE_HEADER_LEN + len,
+    ))
+}
+
+/// Storage header of the DLT `message` received at `timestamp` (ms). The ECU id is taken
+/// from the standard header of the message; it's empty if the message has none.
+pub fn storage_header(timestamp: u64, message: &[u8]) -> [u8; DLT_STORAGE_HEADER_LEN] {
+    let mut header = [0; DLT_STORAGE_HEADER_LEN];
+    header[..4].copy_from_slice(DLT_STORAGE_PATTERN);
+    header[4..8].copy_from_slice(&((timestamp / 1000) as u32).to_le_bytes());
+    header[8..12].copy_from_slice(&((timestamp % 1000 * 1000) as u32).to_le_bytes());
+    match message.get(4..8) {
+        Some(ecu) if message[0] & DLT_WITH_ECU_ID != 0 => header[12..].copy_from_slice(ecu),
+        _ => {}
+    }
+    header
+}
+
+/// Applies `correction` to the timestamp of the storage header of `message`; digits below
+/// milliseconds are kept. Returns `false` if `message` has no storage header.
+pub fn correct_storage_header(message: &mut dlt::Message, correction: &TimeCorrection) -> bool {
+    let Some(header) = message.storage_header.as_mut() else {
+        return false;
+    };
+    let us =
+        i64::from(header.timestamp.seconds) * 1_000_000 + i64::from(header.timestamp.microseconds);
+    let ms = us.div_euclid(1_000);
+    let corrected = us
+        .saturating_add((correction.apply(ms) - ms).saturating_mul(1_000))
+        .clamp(0, i64::from(u32::MAX) * 1_000_000 + 999_999);
+    header.timestamp = dlt::DltTimeStamp {
+        seconds: (corrected / 1_000_000) as u32,
+        microseconds: (corrected % 1_000_000) as u32,
+    };
+    true
+}
+
 impl LogMessage for FormattableMessage<'_> {
     fn to_writer<W: Write>(&self, writer: &mut W) -> Result<usize, std::io::Error> {
         let bytes = self.message.as_bytes();
@@ -27,6 +100,14 @@ impl LogMessage for FormattableMessage<'_> {
         writer.write_all(&bytes)?;
         Ok(len)
     }
+
+    /// Timestamp of the storage header
+    fn timestamp(&self) -> Option<i64> {
+        self.message.storage_header.as_ref().map(|header| {
+            i64::from(header.timestamp.seconds) * 1000
+                + i64::from(header.timestamp.microseconds) / 1000
+        })
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -76,6 +157,7 @@ pub struct DltParser<'m> {
     ft_scanner: FtScanner,
     fibex_someip_metadata: Option<&'m FibexSomeipMetadata>,
     offset: usize,
+    network: Option<NetworkMeta>,
 }
 
 #[derive(Default)]
@@ -117,6 +199,7 @@ impl<'m> DltParser<'m> {
             ft_scanner: FtScanner::new(),
             fibex_someip_metadata,
             offset: 0,
+            network: None,
         }
     }
 }
@@ -161,6 +244,7 @@ impl<'m> Parser<FormattableMessage<'m>> for DltParser<'m> {
                     fibex_dlt_metadata: self.fibex_dlt_metadata,
                     options: self.fmt_options,
                     fibex_someip_metadata: self.fibex_someip_metadata,
+                    network: self.network.clone(),
                 };
                 let consumed = input.len() - rest.len();
                 self.offset += consumed;
@@ -177,6 +261,10 @@ impl<'m> Parser<FormattableMessage<'m>> for DltParser<'m> {
             }
         }
     }
+
+    fn set_network_meta(&mut self, meta: Option<NetworkMeta>) {
+        self.network = meta;
+    }
 }
 
 impl Parser<RangeMessage> for DltRangeParser {
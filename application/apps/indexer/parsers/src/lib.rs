@@ -2,8 +2,9 @@
 pub mod dlt;
 pub mod someip;
 pub mod text;
+pub mod timestamp;
 use serde::Serialize;
-use std::{fmt::Display, io::Write};
+use std::{fmt::Display, io::Write, net::IpAddr};
 use thiserror::Error;
 
 extern crate log;
@@ -52,6 +53,41 @@ pub trait Parser<T> {
         input: &[u8],
         timestamp: Option<u64>,
     ) -> Result<impl Iterator<Item = (usize, Option<ParseYield<T>>)>, Error>;
+
+    /// Sets the network metadata of the packet the following input was received in. Parsers
+    /// which support it add the metadata to the produced messages.
+    fn set_network_meta(&mut self, _meta: Option<NetworkMeta>) {}
+}
+
+/// Network metadata of the captured packet a message was received in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NetworkMeta {
+    pub source: IpAddr,
+    pub source_port: Option<u16>,
+    pub destination: IpAddr,
+    pub destination_port: Option<u16>,
+    /// VLAN id of the outermost VLAN tag
+    pub vlan: Option<u16>,
+    /// Id of the capture interface
+    pub interface: Option<u32>,
+}
+
+impl NetworkMeta {
+    /// Returns the values of the columns: source, destination, VLAN id and interface.
+    /// Unknown values are empty.
+    pub fn columns(&self) -> [String; 4] {
+        let endpoint = |addr: &IpAddr, port: Option<u16>| match (addr, port) {
+            (IpAddr::V4(addr), Some(port)) => format!("{addr}:{port}"),
+            (IpAddr::V6(addr), Some(port)) => format!("[{addr}]:{port}"),
+            (addr, None) => addr.to_string(),
+        };
+        [
+            endpoint(&self.source, self.source_port),
+            endpoint(&self.destination, self.destination_port),
+            self.vlan.map(|vlan| vlan.to_string()).unwrap_or_default(),
+            self.interface.map(|id| id.to_string()).unwrap_or_default(),
+        ]
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -89,6 +125,11 @@ pub trait LogMessage: Display + Serialize {
     /// Serializes a message directly into a Writer
     /// returns the size of the serialized message
     fn to_writer<W: Write>(&self, writer: &mut W) -> Result<usize, std::io::Error>;
+
+    /// Timestamp of the message in milliseconds since the Unix epoch, if the message has one
+    fn timestamp(&self) -> Option<i64> {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -1,4 +1,4 @@
-use crate::{Error, LogMessage, ParseYield, Parser};
+use crate::{Error, LogMessage, NetworkMeta, ParseYield, Parser};
 use std::{
     borrow::Cow,
     cmp::Ordering,
@@ -169,6 +169,7 @@ impl FibexTypeCache {
 /// A parser for SOME/IP log messages.
 pub struct SomeipParser {
     fibex_metadata: Option<FibexMetadata>,
+    network: Option<NetworkMeta>,
 }
 
 impl Default for SomeipParser {
@@ -182,6 +183,7 @@ impl SomeipParser {
     pub fn new() -> Self {
         SomeipParser {
             fibex_metadata: None,
+            network: None,
         }
     }
 
@@ -189,6 +191,7 @@ impl SomeipParser {
     pub fn from_fibex_files(paths: Vec<PathBuf>) -> Self {
         SomeipParser {
             fibex_metadata: FibexMetadata::from_fibex_files(paths),
+            network: None,
         }
     }
 
@@ -283,10 +286,19 @@ impl Parser<SomeipLogMessage> for SomeipParser {
         timestamp: Option<u64>,
     ) -> Result<impl Iterator<Item = (usize, Option<ParseYield<SomeipLogMessage>>)>, Error> {
         let item = SomeipParser::parse_message(self.fibex_metadata.as_ref(), input, timestamp)
-            .map(|(rest, message)| (rest, Some(ParseYield::from(message))))?;
+            .map(|(rest, message)| {
+                let message = message
+                    .with_network(self.network.clone())
+                    .with_timestamp(timestamp);
+                (rest, Some(ParseYield::from(message)))
+            })?;
 
         Ok(iter::once(item))
     }
+
+    fn set_network_meta(&mut self, meta: Option<NetworkMeta>) {
+        self.network = meta;
+    }
 }
 
 fn header_string(header: &Header) -> String {
@@ -476,12 +488,35 @@ fn rpc_message_string(
 pub struct SomeipLogMessage {
     description: String,
     bytes: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    network: Option<NetworkMeta>,
+    /// Time the message was received at (pcap or live sources)
+    #[serde(skip)]
+    timestamp: Option<i64>,
 }
 
 impl SomeipLogMessage {
     /// Creates a new log message for the given values.
     pub fn from(description: String, bytes: Vec<u8>) -> Self {
-        SomeipLogMessage { description, bytes }
+        SomeipLogMessage {
+            description,
+            bytes,
+            network: None,
+            timestamp: None,
+        }
+    }
+
+    /// Attaches the network metadata of the packet the message was received in; it's
+    /// appended as additional columns.
+    pub fn with_network(mut self, network: Option<NetworkMeta>) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Sets the time (ms since the Unix epoch) the message was received at
+    pub fn with_timestamp(mut self, timestamp: Option<u64>) -> Self {
+        self.timestamp = timestamp.map(|ts| ts as i64);
+        self
     }
 }
 
@@ -490,11 +525,21 @@ impl LogMessage for SomeipLogMessage {
         writer.write_all(&self.bytes)?;
         Ok(self.bytes.len())
     }
+
+    fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
 }
 
 impl Display for SomeipLogMessage {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.description)
+        write!(f, "{}", self.description)?;
+        if let Some(network) = &self.network {
+            for column in network.columns() {
+                write!(f, "{COLUMN_SEP}{column}")?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -614,6 +659,36 @@ mod test {
         }
     }
 
+    #[test]
+    fn parse_with_network_meta() {
+        let input: &[u8] = &[
+            0xFF, 0xFF, 0x00, 0x00, // serviceId(u16), methodId(u16)
+            0x00, 0x00, 0x00, 0x08, // length(u32)
+            0xDE, 0xAD, 0xBE, 0xEF, // clientId(u16), sessionId(u16)
+            0x01, 0x01, 0x01, 0x00, // proto(u8), version(u8), messageType,(u8) returnCode(u8)
+        ];
+
+        let mut parser = SomeipParser::new();
+        parser.set_network_meta(Some(NetworkMeta {
+            source: "192.168.1.1".parse().unwrap(),
+            source_port: Some(30490),
+            destination: "fe80::1".parse().unwrap(),
+            destination_port: Some(30501),
+            vlan: Some(3),
+            interface: None,
+        }));
+        let (_, message) = parser.parse(input, None).unwrap().next().unwrap();
+
+        if let ParseYield::Message(item) = message.unwrap() {
+            assert_eq!(
+                format!("MCC{COLUMN_SEP}192.168.1.1:30490{COLUMN_SEP}[fe80::1]:30501{COLUMN_SEP}3{COLUMN_SEP}"),
+                format!("{}", item)
+            );
+        } else {
+            panic!("unexpected parse yield");
+        }
+    }
+
     #[test]
     fn parse_cookie_client() {
         let input: &[u8] = &[
@@ -7,6 +7,9 @@ pub struct StringTokenizer {}
 #[derive(Debug, PartialEq, Eq, Serialize)]
 pub struct StringMessage {
     content: String,
+    /// Time the line was received at, given by live sources
+    #[serde(skip)]
+    timestamp: Option<i64>,
 }
 
 impl fmt::Display for StringMessage {
@@ -21,6 +24,10 @@ impl LogMessage for StringMessage {
         writer.write_all(self.content.as_bytes())?;
         Ok(len)
     }
+
+    fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
 }
 
 impl Parser<StringMessage> for StringTokenizer
@@ -30,17 +37,19 @@ where
     fn parse(
         &mut self,
         input: &[u8],
-        _timestamp: Option<u64>,
+        timestamp: Option<u64>,
     ) -> Result<impl Iterator<Item = (usize, Option<ParseYield<StringMessage>>)>, Error> {
         // TODO: support non-utf8 encodings
         use memchr::memchr;
         if input.is_empty() {
             return Ok(iter::once((input.len(), None)));
         }
+        let timestamp = timestamp.map(|ts| ts as i64);
         let item = if let Some(msg_size) = memchr(b'\n', input) {
             let content = String::from_utf8_lossy(&input[..msg_size]);
             let string_msg = StringMessage {
                 content: content.to_string(),
+                timestamp,
             };
             (msg_size + 1, Some(string_msg.into()))
         } else {
@@ -48,6 +57,7 @@ where
                 input.len(),
                 Some(ParseYield::from(StringMessage {
                     content: String::new(),
+                    timestamp,
                 })),
             )
         };
@@ -62,14 +72,14 @@ fn test_string_tokenizer() {
     let content = b"hello\nworld\n";
     let (consumed_1, first_msg) = parser.parse(content, None).unwrap().next().unwrap();
     match first_msg {
-        Some(ParseYield::Message(StringMessage { content })) if content.eq("hello") => {}
+        Some(ParseYield::Message(StringMessage { content, .. })) if content.eq("hello") => {}
         _ => panic!("First message did not match"),
     }
     let rest_1 = &content[consumed_1..];
     println!("rest_1 = {:?}", String::from_utf8_lossy(rest_1));
     let (consumed_2, second_msg) = parser.parse(rest_1, None).unwrap().next().unwrap();
     match second_msg {
-        Some(ParseYield::Message(StringMessage { content })) if content.eq("world") => {}
+        Some(ParseYield::Message(StringMessage { content, .. })) if content.eq("world") => {}
         _ => panic!("Second message did not match"),
     }
     let rest_2 = &rest_1[consumed_2..];
@@ -80,3 +90,17 @@ fn test_string_tokenizer() {
     );
     assert!(third_msg.is_none());
 }
+
+#[test]
+fn test_string_tokenizer_timestamp() {
+    let mut parser = StringTokenizer {};
+    let (_, msg) = parser
+        .parse(b"hello\n", Some(1_709_633_472_345))
+        .unwrap()
+        .next()
+        .unwrap();
+    match msg {
+        Some(ParseYield::Message(msg)) => assert_eq!(msg.timestamp(), Some(1_709_633_472_345)),
+        _ => panic!("Message did not match"),
+    }
+}
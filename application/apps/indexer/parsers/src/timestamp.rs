@@ -0,0 +1,604 @@
+//! Extraction of timestamps from text lines.
+//!
+//! A timestamp format is described with placeholders, e.g. `YYYY-MM-DD hh:mm:ss.s TZD`:
+//!
+//! | Placeholder | Meaning                                      |
+//! |-------------|----------------------------------------------|
+//! | `YYYY`      | year, 4 digits                               |
+//! | `YY`        | year, 2 digits (20YY)                        |
+//! | `MM`        | month, 1-2 digits                            |
+//! | `MMM`       | month as English abbreviation (`Jan`)        |
+//! | `DD`        | day, 1-2 digits                              |
+//! | `hh`        | hour, 1-2 digits                             |
+//! | `mm`        | minute                                       |
+//! | `ss`        | second                                       |
+//! | `s`         | fraction of second, any count of digits      |
+//! | `TZD`       | time zone: `Z`, `+hh:mm` or `+hhmm`          |
+//! | `EPOCH_MS`  | milliseconds since the Unix epoch, 13 digits |
+//!
+//! A run of `s` following `.` or `,` is always a fraction of second, so `ss.sss` can be
+//! used as well. Whitespace matches any non-empty whitespace; everything else has to
+//! match literally. Timestamps without time zone are taken as UTC.
+//!
+//! [`detect_format`] finds the best matching format of [`KNOWN_FORMATS`] for given lines.
+//! [`TimeCorrection`] corrects timestamps of a clock with an offset and a linear drift.
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::Path,
+};
+use thiserror::Error;
+
+/// Count of lines of a file taken to detect the timestamp format
+pub const DETECTION_SAMPLE_LINES: usize = 1000;
+/// Count of matched timestamps given as samples of a detected format
+const DETECTION_SAMPLES: usize = 3;
+
+/// Catalogue of common timestamp formats as `(name, format)`. More specific formats go first,
+/// because they win against more generic formats matching the same lines.
+pub const KNOWN_FORMATS: &[(&str, &str)] = &[
+    ("ISO-8601", "YYYY-MM-DDThh:mm:ss.sTZD"),
+    ("ISO-8601", "YYYY-MM-DDThh:mm:ssTZD"),
+    ("ISO-8601", "YYYY-MM-DDThh:mm:ss.s"),
+    ("ISO-8601", "YYYY-MM-DDThh:mm:ss"),
+    ("ISO-8601", "YYYY-MM-DD hh:mm:ss.s"),
+    ("Java", "YYYY-MM-DD hh:mm:ss,s"),
+    ("ISO-8601", "YYYY-MM-DD hh:mm:ss"),
+    ("journal", "MMM DD hh:mm:ss.s"),
+    ("syslog", "MMM DD hh:mm:ss"),
+    ("logcat", "MM-DD hh:mm:ss.s"),
+    ("epoch ms", "EPOCH_MS"),
+];
+
+/// Abbreviations of months as used in timestamps
+const MONTH_NAMES: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    #[error("Invalid timestamp format \"{0}\": {1}")]
+    Invalid(String, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Year,
+    ShortYear,
+    MonthName,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Fraction,
+    Zone,
+    EpochMillis,
+}
+
+impl Placeholder {
+    fn group(&self) -> &'static str {
+        match self {
+            Self::Year => "Y",
+            Self::ShortYear => "y",
+            Self::MonthName => "b",
+            Self::Month => "m",
+            Self::Day => "d",
+            Self::Hour => "H",
+            Self::Minute => "M",
+            Self::Second => "S",
+            Self::Fraction => "f",
+            Self::Zone => "z",
+            Self::EpochMillis => "e",
+        }
+    }
+
+    fn pattern(&self) -> &'static str {
+        match self {
+            Self::Year => r"\d{4}",
+            Self::ShortYear => r"\d{2}",
+            Self::MonthName => r"[A-Za-z]{3}",
+            Self::Month | Self::Day | Self::Hour => r"\d{1,2}",
+            Self::Minute | Self::Second => r"\d{2}",
+            Self::Fraction => r"\d{1,9}",
+            Self::Zone => r"Z|[+-]\d{2}:?\d{2}",
+            Self::EpochMillis => r"\b\d{13}\b",
+        }
+    }
+
+    /// Recognizes a run of `len` characters `c` of the format
+    fn from_run(c: char, len: usize, after_separator: bool) -> Option<Self> {
+        match (c, len) {
+            ('Y', 4) => Some(Self::Year),
+            ('Y', 2) => Some(Self::ShortYear),
+            ('M', 3) => Some(Self::MonthName),
+            ('M', 2) => Some(Self::Month),
+            ('D', 2) => Some(Self::Day),
+            ('h', 2) => Some(Self::Hour),
+            ('m', 2) => Some(Self::Minute),
+            ('s', _) if after_separator => Some(Self::Fraction),
+            ('s', 2) => Some(Self::Second),
+            ('s', 1) => Some(Self::Fraction),
+            _ => None,
+        }
+    }
+}
+
+/// Compiled timestamp format
+#[derive(Debug, Clone)]
+pub struct TimestampFormat {
+    format: String,
+    regex: Regex,
+}
+
+impl TimestampFormat {
+    pub fn new(format: &str) -> Result<Self, FormatError> {
+        let invalid = |reason: &str| FormatError::Invalid(format.to_owned(), reason.to_owned());
+        let chars: Vec<char> = format.chars().collect();
+        let mut placeholders: Vec<Placeholder> = Vec::new();
+        let mut pattern = String::new();
+        let mut pos = 0;
+        while pos < chars.len() {
+            let c = chars[pos];
+            let named = [
+                ("TZD", Placeholder::Zone),
+                ("EPOCH_MS", Placeholder::EpochMillis),
+            ]
+            .into_iter()
+            .find(|(name, _)| chars[pos..].starts_with(&name.chars().collect::<Vec<char>>()));
+            if let Some((name, placeholder)) = named {
+                if placeholders.contains(&placeholder) {
+                    return Err(invalid(&format!("{name} is defined twice")));
+                }
+                pattern.push_str(&format!(
+                    "(?P<{}>{})",
+                    placeholder.group(),
+                    placeholder.pattern()
+                ));
+                placeholders.push(placeholder);
+                pos += name.len();
+                continue;
+            }
+            let len = chars[pos..].iter().take_while(|next| **next == c).count();
+            if c.is_whitespace() {
+                pattern.push_str(r"\s+");
+                pos += chars[pos..]
+                    .iter()
+                    .take_while(|next| next.is_whitespace())
+                    .count();
+                continue;
+            }
+            let after_separator = pos > 0 && matches!(chars[pos - 1], '.' | ',');
+            match Placeholder::from_run(c, len, after_separator) {
+                Some(placeholder) => {
+                    if placeholders.contains(&placeholder) {
+                        return Err(invalid(&format!(
+                            "{} is defined twice",
+                            chars[pos..pos + len].iter().collect::<String>()
+                        )));
+                    }
+                    pattern.push_str(&format!(
+                        "(?P<{}>{})",
+                        placeholder.group(),
+                        placeholder.pattern()
+                    ));
+                    placeholders.push(placeholder);
+                }
+                None => {
+                    let literal: String = chars[pos..pos + len].iter().collect();
+                    pattern.push_str(&regex::escape(&literal));
+                }
+            }
+            pos += len;
+        }
+        let is_epoch = placeholders.contains(&Placeholder::EpochMillis);
+        if !is_epoch
+            && !placeholders.contains(&Placeholder::Month)
+            && !placeholders.contains(&Placeholder::MonthName)
+        {
+            return Err(invalid("month (MM or MMM) is missing"));
+        }
+        for (placeholder, name) in [
+            (Placeholder::Day, "day (DD)"),
+            (Placeholder::Hour, "hour (hh)"),
+            (Placeholder::Minute, "minute (mm)"),
+        ] {
+            if !is_epoch && !placeholders.contains(&placeholder) {
+                return Err(invalid(&format!("{name} is missing")));
+            }
+        }
+        let regex = Regex::new(&pattern).map_err(|e| invalid(&e.to_string()))?;
+        Ok(Self {
+            format: format.to_owned(),
+            regex,
+        })
+    }
+
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+
+    /// Returns the text of the first timestamp in `line`
+    pub fn find<'a>(&self, line: &'a str) -> Option<&'a str> {
+        self.regex.find(line).map(|m| m.as_str())
+    }
+
+    /// Finds the first timestamp in `line` and returns it as milliseconds since the Unix
+    /// epoch. If the format has no year, `year` is used; without `year` the current year.
+    pub fn extract(&self, line: &str, year: Option<i32>) -> Option<i64> {
+        let caps = self.regex.captures(line)?;
+        if let Some(epoch) = caps.name("e") {
+            return epoch.as_str().parse::<i64>().ok();
+        }
+        let number = |name: &str| -> Option<u32> {
+            caps.name(name).and_then(|m| m.as_str().parse::<u32>().ok())
+        };
+        let year = if let Some(y) = caps.name("Y") {
+            y.as_str().parse::<i32>().ok()?
+        } else if let Some(y) = caps.name("y") {
+            2000 + y.as_str().parse::<i32>().ok()?
+        } else {
+            year.unwrap_or_else(|| Utc::now().year())
+        };
+        let month = match caps.name("b") {
+            Some(name) => {
+                let name = name.as_str().to_lowercase();
+                MONTHS.iter().position(|month| *month == name)? as u32 + 1
+            }
+            None => number("m")?,
+        };
+        let nanos = match caps.name("f") {
+            Some(fraction) => format!("{:0<9}", fraction.as_str()).parse::<u32>().ok()?,
+            None => 0,
+        };
+        let utc_ms = NaiveDate::from_ymd_opt(year, month, number("d")?)?
+            .and_hms_nano_opt(number("H")?, number("M")?, number("S").unwrap_or(0), nanos)?
+            .and_utc()
+            .timestamp_millis();
+        Some(utc_ms - zone_offset(caps.name("z").map(|z| z.as_str()))?)
+    }
+
+    /// Finds the first timestamp in `line` and moves it by `delta` ms. The timestamp keeps
+    /// its format and time zone. Returns `None` if `line` has no timestamp.
+    pub fn shift(&self, line: &str, year: Option<i32>, delta: i64) -> Option<String> {
+        let caps = self.regex.captures(line)?;
+        let whole = caps.get(0)?;
+        let shifted = self.extract(whole.as_str(), year)?.checked_add(delta)?;
+        let zone_ms = zone_offset(caps.name("z").map(|z| z.as_str()))?;
+        let local = DateTime::from_timestamp_millis(shifted + zone_ms)?.naive_utc();
+        let mut parts: Vec<(regex::Match, String)> = Vec::new();
+        for (name, value) in [
+            ("e", shifted.to_string()),
+            ("Y", format!("{:04}", local.year())),
+            ("y", format!("{:02}", local.year().rem_euclid(100))),
+            ("m", local.month().to_string()),
+            ("d", local.day().to_string()),
+            ("H", local.hour().to_string()),
+            ("M", format!("{:02}", local.minute())),
+            ("S", format!("{:02}", local.second())),
+            ("f", format!("{:09}", local.nanosecond() % 1_000_000_000)),
+        ] {
+            let Some(found) = caps.name(name) else {
+                continue;
+            };
+            let len = found.as_str().len();
+            let value = match name {
+                // Keeps the precision of the fraction
+                "f" => value[..len.min(9)].to_owned(),
+                _ => format!("{value:0>len$}"),
+            };
+            parts.push((found, value));
+        }
+        if let Some(found) = caps.name("b") {
+            let name = MONTH_NAMES[local.month0() as usize];
+            let value = if found.as_str().chars().all(|c| c.is_ascii_uppercase()) {
+                name.to_uppercase()
+            } else if found.as_str().chars().all(|c| c.is_ascii_lowercase()) {
+                name.to_lowercase()
+            } else {
+                name.to_owned()
+            };
+            parts.push((found, value));
+        }
+        parts.sort_by_key(|(found, _)| found.start());
+        let mut shifted_line = String::with_capacity(line.len());
+        let mut pos = 0;
+        for (found, value) in parts {
+            shifted_line.push_str(&line[pos..found.start()]);
+            shifted_line.push_str(&value);
+            pos = found.end();
+        }
+        shifted_line.push_str(&line[pos..]);
+        Some(shifted_line)
+    }
+}
+
+/// Offset in ms of the time zone of a timestamp; no zone is UTC
+fn zone_offset(zone: Option<&str>) -> Option<i64> {
+    match zone {
+        None | Some("Z") => Some(0),
+        Some(zone) => {
+            let digits = zone[1..].replace(':', "");
+            let hours = digits[..2].parse::<i64>().ok()?;
+            let minutes = digits[2..].parse::<i64>().ok()?;
+            let offset = (hours * 60 + minutes) * 60_000;
+            Some(if zone.starts_with('-') {
+                -offset
+            } else {
+                offset
+            })
+        }
+    }
+}
+
+/// Correction of a clock, which differs from the reference clock by a constant offset and
+/// drifts linearly. A timestamp `t` is corrected to `t + offset + drift * (t - origin)`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimeCorrection {
+    /// Offset in ms
+    #[serde(default)]
+    pub offset: i64,
+    /// Drift as ms per ms of the clock, e.g. `0.0001` is 360 ms per hour
+    #[serde(default)]
+    pub drift: f64,
+    /// Timestamp (ms) of the clock at which the drift is zero
+    #[serde(default)]
+    pub origin: i64,
+}
+
+impl TimeCorrection {
+    pub fn offset(offset: i64) -> Self {
+        Self {
+            offset,
+            ..Default::default()
+        }
+    }
+
+    /// Fits the correction to anchors. An anchor is a pair of timestamps `(clock, reference)`
+    /// of the same moment. One anchor defines the offset only; the drift of two or more
+    /// anchors is fitted with least squares. Returns `None` without anchors.
+    pub fn from_anchors(anchors: &[(i64, i64)]) -> Option<Self> {
+        let (base, _) = *anchors.first()?;
+        // Relative to the first anchor to keep the precision of f64
+        let points: Vec<(f64, f64)> = anchors
+            .iter()
+            .map(|(clock, reference)| ((clock - base) as f64, (reference - base) as f64))
+            .collect();
+        let count = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+        let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        let covariance: f64 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let slope = if variance > 0.0 {
+            covariance / variance
+        } else {
+            1.0
+        };
+        Some(Self {
+            offset: (mean_y - mean_x).round() as i64,
+            drift: slope - 1.0,
+            origin: base + mean_x.round() as i64,
+        })
+    }
+
+    /// Returns the corrected `timestamp`
+    pub fn apply(&self, timestamp: i64) -> i64 {
+        let drift = (self.drift * (timestamp.saturating_sub(self.origin)) as f64).round() as i64;
+        timestamp.saturating_add(self.offset).saturating_add(drift)
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.offset == 0 && self.drift == 0.0
+    }
+}
+
+/// Timestamp format detected in lines of a log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectedFormat {
+    /// Name of the format in [`KNOWN_FORMATS`]
+    pub name: String,
+    pub format: String,
+    /// Share of the non-empty sampled lines with a timestamp of the format, `0.0..=1.0`
+    pub confidence: f32,
+    /// First timestamps found with the format
+    pub samples: Vec<String>,
+}
+
+/// Tries all [`KNOWN_FORMATS`] on `lines` and returns the format matching most of the lines,
+/// or `None` if no format matches any line.
+pub fn detect_format<I, S>(lines: I) -> Option<DetectedFormat>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let formats: Vec<(&str, TimestampFormat)> = KNOWN_FORMATS
+        .iter()
+        .filter_map(|(name, format)| TimestampFormat::new(format).ok().map(|f| (*name, f)))
+        .collect();
+    let mut matches = vec![0usize; formats.len()];
+    let mut samples: Vec<Vec<String>> = vec![Vec::new(); formats.len()];
+    let mut total = 0usize;
+    for line in lines {
+        let line = line.as_ref();
+        if line.trim().is_empty() {
+            continue;
+        }
+        total += 1;
+        for (index, (_, format)) in formats.iter().enumerate() {
+            // The year is given, so lines of formats without year are checked the same way
+            // independently of the current date
+            if format.extract(line, Some(2000)).is_none() {
+                continue;
+            }
+            matches[index] += 1;
+            if samples[index].len() < DETECTION_SAMPLES {
+                if let Some(found) = format.find(line) {
+                    samples[index].push(found.to_owned());
+                }
+            }
+        }
+    }
+    // Keeps the first (most specific) of the formats with equal count of matches
+    let best = matches
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .fold(
+            None,
+            |best: Option<(usize, usize)>, (index, count)| match best {
+                Some((_, best_count)) if best_count >= *count => best,
+                _ => Some((index, *count)),
+            },
+        )?;
+    let (name, format) = &formats[best.0];
+    Some(DetectedFormat {
+        name: name.to_string(),
+        format: format.format().to_owned(),
+        confidence: best.1 as f32 / total as f32,
+        samples: std::mem::take(&mut samples[best.0]),
+    })
+}
+
+/// Detects the timestamp format of the text file `path` using its first
+/// [`DETECTION_SAMPLE_LINES`] lines
+pub fn detect_file_format(path: &Path) -> Result<Option<DetectedFormat>, io::Error> {
+    let mut lines = Vec::new();
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buf = Vec::new();
+    while lines.len() < DETECTION_SAMPLE_LINES {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        lines.push(String::from_utf8_lossy(&buf).into_owned());
+    }
+    Ok(detect_format(lines))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let format = TimestampFormat::new("YYYY-MM-DD hh:mm:ss.s").unwrap();
+        assert_eq!(
+            format.extract("2024-03-05 10:11:12.345 INFO started", None),
+            Some(1_709_633_472_345)
+        );
+        assert_eq!(format.extract("INFO started", None), None);
+        let format = TimestampFormat::new("DD/MMM/YYYY:hh:mm:ss TZD").unwrap();
+        assert_eq!(
+            format.extract("127.0.0.1 - [05/Mar/2024:11:11:12 +01:00] GET /", None),
+            Some(1_709_633_472_000)
+        );
+        let format = TimestampFormat::new("MM-DD hh:mm:ss.sss").unwrap();
+        assert_eq!(
+            format.extract("03-05 10:11:12.5 D tag: message", Some(2024)),
+            Some(1_709_633_472_500)
+        );
+    }
+
+    #[test]
+    fn test_shift() {
+        let format = TimestampFormat::new("YYYY-MM-DD hh:mm:ss.sss").unwrap();
+        assert_eq!(
+            format
+                .shift("2024-03-05 23:59:59.500 INFO started", None, 750)
+                .as_deref(),
+            Some("2024-03-06 00:00:00.250 INFO started")
+        );
+        assert_eq!(format.shift("INFO started", None, 750), None);
+        let format = TimestampFormat::new("MMM DD hh:mm:ss TZD").unwrap();
+        assert_eq!(
+            format
+                .shift("Mar  5 10:11:12 +01:00 host", Some(2024), -3_600_000)
+                .as_deref(),
+            // Whitespace of the original line is kept
+            Some("Mar  5 09:11:12 +01:00 host")
+        );
+        let format = TimestampFormat::new("EPOCH_MS").unwrap();
+        assert_eq!(
+            format.shift("1709633472345;x", None, 5).as_deref(),
+            Some("1709633472350;x")
+        );
+    }
+
+    #[test]
+    fn test_time_correction() {
+        let correction = TimeCorrection::offset(-500);
+        assert_eq!(correction.apply(10_000), 9_500);
+        // The clock is 1 s ahead at 0 and gains 1 ms per second
+        let correction =
+            TimeCorrection::from_anchors(&[(1_000, 0), (11_010, 10_000), (21_020, 20_000)])
+                .unwrap();
+        assert_eq!(correction.apply(1_000), 0);
+        assert_eq!(correction.apply(31_030), 30_000);
+        assert!((correction.drift - (1.0 / 1.001 - 1.0)).abs() < 1e-9);
+        let correction = TimeCorrection::from_anchors(&[(5_000, 4_000)]).unwrap();
+        assert_eq!(
+            correction,
+            TimeCorrection {
+                offset: -1_000,
+                drift: 0.0,
+                origin: 5_000,
+            }
+        );
+        assert!(TimeCorrection::from_anchors(&[]).is_none());
+    }
+
+    #[test]
+    fn test_epoch() {
+        let format = TimestampFormat::new("EPOCH_MS").unwrap();
+        assert_eq!(
+            format.extract("1709633472345 started", None),
+            Some(1_709_633_472_345)
+        );
+        assert_eq!(format.extract("17096334723456 started", None), None);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let detected = detect_format([
+            "2024-03-05 10:11:12.345 INFO started",
+            "  continued",
+            "2024-03-05 10:11:13.001 INFO stopped",
+        ])
+        .unwrap();
+        assert_eq!(detected.format, "YYYY-MM-DD hh:mm:ss.s");
+        assert!((detected.confidence - 2.0 / 3.0).abs() < f32::EPSILON);
+        assert_eq!(
+            detected.samples,
+            vec!["2024-03-05 10:11:12.345", "2024-03-05 10:11:13.001"]
+        );
+        let detected = detect_format([
+            "Mar  5 10:11:12 host sshd[42]: accepted",
+            "Mar  5 10:11:13 host sshd[42]: closed",
+        ])
+        .unwrap();
+        assert_eq!(detected.name, "syslog");
+        let detected = detect_format(["03-05 10:11:12.345  1234  1250 D tag: message"]).unwrap();
+        assert_eq!(detected.name, "logcat");
+        let detected = detect_format(["2024-03-05 10:11:12,345 [main] INFO App"]).unwrap();
+        assert_eq!(detected.name, "Java");
+        let detected = detect_format(["1709633472345;sensor;42"]).unwrap();
+        assert_eq!(detected.name, "epoch ms");
+        assert!(detect_format(["no timestamp here"]).is_none());
+    }
+
+    #[test]
+    fn test_invalid_format() {
+        assert!(TimestampFormat::new("hh:mm:ss").is_err());
+        assert!(TimestampFormat::new("MM-DD-MM hh:mm").is_err());
+    }
+}
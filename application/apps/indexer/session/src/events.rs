@@ -146,6 +146,16 @@ impl From<GrabError> for NativeError {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum StreamConnectionState {
+    /// Connection to the stream source has been lost
+    Disconnected,
+    /// Reconnection attempt (1-based) is going to be made
+    Reconnecting { attempt: u32 },
+    /// Connection has been re-established with the given attempt
+    Reconnected { attempt: u32 },
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OperationDone {
     pub uuid: Uuid,
@@ -225,6 +235,20 @@ pub enum CallbackEvent {
      * >> Kind: repeated
      */
     Progress { uuid: Uuid, progress: Progress },
+    /**
+     * Triggered when the connection of a stream source (TCP, serial port) is lost or
+     * re-established
+     * @event StreamConnection { uuid: Uuid, source_id: u16, state: StreamConnectionState }
+     * uuid - UUID of observe operation
+     * source_id - id of source, which connection has been changed
+     * >> Scope: async operation
+     * >> Kind: repeated
+     */
+    StreamConnection {
+        uuid: Uuid,
+        source_id: u16,
+        state: StreamConnectionState,
+    },
     /**
      * Triggered on error in the scope of session
      * >> Scope: session
@@ -298,6 +322,11 @@ impl std::fmt::Display for CallbackEvent {
                 uuid: _,
                 progress: _,
             } => write!(f, "Progress"),
+            Self::StreamConnection {
+                uuid: _,
+                source_id,
+                state,
+            } => write!(f, "StreamConnection({source_id}): {state:?}"),
             Self::SessionError(err) => write!(f, "SessionError: {err:?}"),
             Self::OperationError { uuid, error } => {
                 write!(f, "OperationError: {uuid}: {error:?}")
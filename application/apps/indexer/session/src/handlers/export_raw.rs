@@ -4,17 +4,20 @@ use crate::{
     progress::Severity,
     state::SessionStateAPI,
 };
+use futures::StreamExt;
 use indexer_base::config::IndexSection;
 use log::debug;
 use parsers::{
-    dlt::{fmt::FormatOptions, DltParser},
+    dlt::{correct_storage_header, fmt::FormatOptions, DltParser},
     someip::SomeipParser,
     text::StringTokenizer,
-    LogMessage, MessageStreamItem,
+    timestamp::TimeCorrection,
+    LogMessage, MessageStreamItem, ParseYield,
 };
 use processor::export::{export_raw, ExportError};
 use sources::{
     binary::{
+        compressed::DecompressedReader,
         pcap::{legacy::PcapLegacyByteSource, ng::PcapngByteSource},
         raw::BinaryByteSource,
     },
@@ -22,10 +25,7 @@ use sources::{
     producer::MessageProducer,
     ByteSource,
 };
-use std::{
-    fs::File,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 use tokio_util::sync::CancellationToken;
 
 pub async fn execute_export(
@@ -45,12 +45,28 @@ pub async fn execute_export(
             )),
         });
     }
+    let corrections = state.get_time_corrections().await?;
+    let files = observed.get_files();
+    // Corrected timestamps can be written into DLT storage headers only; other raw data is
+    // exported unchanged, so the corrections would be lost
+    if files.iter().any(|(alias, parser, _, _)| {
+        corrections.contains_key(alias)
+            && !matches!(parser, ParserType::Dlt(settings) if settings.with_storage_header)
+    }) {
+        return Err(NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Configuration,
+            message: Some(String::from(
+                "Raw export of sources with time correction is possible for DLT files with storage headers only; export as text instead.",
+            )),
+        });
+    }
     let mut indexes = ranges
         .iter()
         .map(IndexSection::from)
         .collect::<Vec<IndexSection>>();
-    let count = observed.get_files().len();
-    for (i, (parser, file_format, filename)) in observed.get_files().iter().enumerate() {
+    let count = files.len();
+    for (i, (alias, parser, file_format, filename)) in files.iter().enumerate() {
         if indexes.is_empty() {
             break;
         }
@@ -58,6 +74,7 @@ pub async fn execute_export(
             filename,
             &out_path,
             parser,
+            corrections.get(alias),
             file_format,
             &indexes,
             i != (count - 1),
@@ -78,16 +95,19 @@ pub async fn execute_export(
     Ok(Some(true))
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn assing_source(
     src: &PathBuf,
     dest: &Path,
     parser: &ParserType,
+    correction: Option<&TimeCorrection>,
     file_format: &FileFormat,
     sections: &Vec<IndexSection>,
     read_to_end: bool,
     cancel: &CancellationToken,
 ) -> Result<Option<usize>, NativeError> {
-    let reader = File::open(src).map_err(|e| NativeError {
+    // Compressed files are decompressed on the fly, so raw export is working on the original file
+    let reader = DecompressedReader::open(src).map_err(|e| NativeError {
         severity: Severity::ERROR,
         kind: NativeErrorKind::Io,
         message: Some(format!("Fail open file {}: {}", src.to_string_lossy(), e)),
@@ -97,6 +117,7 @@ async fn assing_source(
             export(
                 dest,
                 parser,
+                correction,
                 BinaryByteSource::new(reader),
                 sections,
                 read_to_end,
@@ -108,6 +129,7 @@ async fn assing_source(
             export(
                 dest,
                 parser,
+                correction,
                 PcapngByteSource::new(reader)?,
                 sections,
                 read_to_end,
@@ -119,6 +141,7 @@ async fn assing_source(
             export(
                 dest,
                 parser,
+                correction,
                 PcapLegacyByteSource::new(reader)?,
                 sections,
                 read_to_end,
@@ -132,6 +155,7 @@ async fn assing_source(
 async fn export<S: ByteSource>(
     dest: &Path,
     parser: &ParserType,
+    correction: Option<&TimeCorrection>,
     source: S,
     sections: &Vec<IndexSection>,
     read_to_end: bool,
@@ -165,15 +189,21 @@ async fn export<S: ByteSource>(
                 settings.with_storage_header,
             );
             let mut producer = MessageProducer::new(parser, source, None);
-            export_runner(
-                Box::pin(producer.as_stream()),
-                dest,
-                sections,
-                read_to_end,
-                false,
-                cancel,
-            )
-            .await
+            // Corrected timestamps are written into the storage headers
+            let stream = producer.as_stream().map(|mut items| {
+                if let Some(correction) = correction {
+                    for (_, item) in items.iter_mut() {
+                        if let MessageStreamItem::Item(
+                            ParseYield::Message(msg) | ParseYield::MessageAndAttachment((msg, _)),
+                        ) = item
+                        {
+                            correct_storage_header(&mut msg.message, correction);
+                        }
+                    }
+                }
+                items
+            });
+            export_runner(Box::pin(stream), dest, sections, read_to_end, false, cancel).await
         }
         ParserType::Text => {
             let mut producer = MessageProducer::new(StringTokenizer {}, source, None);
@@ -0,0 +1,156 @@
+use crate::{
+    events::{CallbackEvent, NativeError, NativeErrorKind},
+    operations::{OperationAPI, OperationResult},
+    progress::{Progress, Severity, Ticks},
+    state::SessionStateAPI,
+};
+use log::debug;
+use merging::merger::{open_input, FileMergeOptions, MergeError, MergedEntry, Merger};
+use std::{fs, time::Instant};
+use tokio::task::spawn_blocking;
+
+/// Count of entries written between checks of cancellation
+const MERGE_BATCH_SIZE: usize = 1000;
+/// Interval of flushing the session file and reporting the progress
+const MERGE_UPDATE_INTERVAL_MS: u128 = 500;
+
+fn merge_error(err: MergeError) -> NativeError {
+    NativeError {
+        severity: Severity::ERROR,
+        kind: NativeErrorKind::Io,
+        message: Some(format!("Fail to merge files: {err}")),
+    }
+}
+
+fn join_error(err: tokio::task::JoinError) -> NativeError {
+    NativeError {
+        severity: Severity::ERROR,
+        kind: NativeErrorKind::Interrupted,
+        message: Some(format!("Merging task failed: {err}")),
+    }
+}
+
+/// Opens the inputs and detects their timestamp formats. Returns the merger and the total
+/// size of the files.
+fn open_merger(files: &[FileMergeOptions]) -> Result<(Merger, u64), NativeError> {
+    let mut inputs = Vec::with_capacity(files.len());
+    for file in files.iter() {
+        inputs.push(open_input(file).map_err(|err| NativeError {
+            severity: Severity::ERROR,
+            kind: match &err {
+                MergeError::WrongConfiguration(_) => NativeErrorKind::Configuration,
+                _ => NativeErrorKind::Io,
+            },
+            message: Some(format!("Fail to open file {}: {err}", file.path)),
+        })?);
+    }
+    let total: u64 = files
+        .iter()
+        .filter_map(|file| fs::metadata(&file.path).ok())
+        .map(|md| md.len())
+        .sum();
+    Ok((Merger::new(inputs).map_err(merge_error)?, total))
+}
+
+/// Reads the next batch of merged entries; the batch is shorter at the end of inputs
+fn next_batch(merger: &mut Merger) -> Result<Vec<MergedEntry>, NativeError> {
+    let mut batch = Vec::with_capacity(MERGE_BATCH_SIZE);
+    while batch.len() < MERGE_BATCH_SIZE {
+        match merger.next_entry().map_err(merge_error)? {
+            Some(entry) => batch.push(entry),
+            None => break,
+        }
+    }
+    Ok(batch)
+}
+
+/// Merges `files` ordered by timestamp into the session. Each file becomes a source with the
+/// alias `<source_id>:<tag>`; the merged content is written into the session incrementally.
+pub async fn merge_files(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    files: &[FileMergeOptions],
+    source_id: &str,
+) -> OperationResult<()> {
+    if files.is_empty() {
+        return Err(NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Configuration,
+            message: Some(String::from("No files are defined for Merge operation")),
+        });
+    }
+    if matches!(state.get_session_file_origin().await?, Some(origin) if origin.is_linked()) {
+        return Err(NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Configuration,
+            message: Some(String::from(
+                "Cannot merge files, because session is linked to other text file",
+            )),
+        });
+    }
+    let mut source_ids = Vec::with_capacity(files.len());
+    for file in files.iter() {
+        source_ids.push(
+            state
+                .add_source(&format!("{source_id}:{}", file.tag))
+                .await?,
+        );
+    }
+    // Files are read with blocking IO; detection of formats reads the beginning of files
+    let opening = files.to_vec();
+    let (mut merger, total) = spawn_blocking(move || open_merger(&opening))
+        .await
+        .map_err(join_error)??;
+    let report = |count: u64| {
+        operation_api.emit(CallbackEvent::Progress {
+            uuid: operation_api.id(),
+            progress: Progress::Ticks(Ticks {
+                count,
+                state: Some(String::from("merging")),
+                total: Some(total),
+            }),
+        });
+    };
+    state.set_session_file(None).await?;
+    operation_api.processing();
+    let cancel = operation_api.cancellation_token();
+    let mut updated = Instant::now();
+    loop {
+        let (returned, batch) = spawn_blocking(move || {
+            let batch = next_batch(&mut merger);
+            (merger, batch)
+        })
+        .await
+        .map_err(join_error)?;
+        merger = returned;
+        let batch = batch?;
+        let exhausted = batch.len() < MERGE_BATCH_SIZE;
+        for entry in batch {
+            // Rows before the first timestamp of a file have no timestamp
+            let timestamp = (entry.timestamp != i64::MIN).then_some(entry.timestamp);
+            state
+                .write_timed_session_file(
+                    source_ids[entry.input],
+                    format!("{}\n", entry.content),
+                    timestamp,
+                )
+                .await?;
+        }
+        if exhausted {
+            break;
+        }
+        if cancel.is_cancelled() {
+            debug!("merging is cancelled");
+            break;
+        }
+        if updated.elapsed().as_millis() > MERGE_UPDATE_INTERVAL_MS {
+            state.flush_session_file().await?;
+            report(merger.read_bytes());
+            updated = Instant::now();
+        }
+    }
+    state.flush_session_file().await?;
+    report(merger.read_bytes());
+    state.file_read().await?;
+    Ok(None)
+}
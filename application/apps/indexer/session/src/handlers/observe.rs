@@ -7,7 +7,7 @@ use crate::{
 };
 use log::error;
 use sources::{
-    factory::{ObserveOptions, ObserveOrigin, ParserType},
+    factory::{ConcatFile, ObserveOptions, ObserveOrigin, ParserType},
     producer::SdeReceiver,
 };
 
@@ -20,6 +20,13 @@ pub async fn start_observing(
     if let ParserType::Dlt(ref mut settings) = options.parser {
         settings.load_fibex_metadata();
     };
+    if let ObserveOrigin::Concat(ref mut files) = options.origin {
+        files.iter_mut().for_each(|file| {
+            if let Some(ParserType::Dlt(ref mut settings)) = file.parser {
+                settings.load_fibex_metadata();
+            }
+        });
+    }
     if let Err(err) = state.add_executed_observe(options.clone()).await {
         error!("Fail to store observe options: {:?}", err);
     }
@@ -43,7 +50,11 @@ pub async fn start_observing(
                     observing::concat::concat_files(
                         operation_api,
                         state,
-                        &[(uuid.clone(), file_origin.clone(), filename.clone())],
+                        &[ConcatFile::new(
+                            uuid.clone(),
+                            file_origin.clone(),
+                            filename.clone(),
+                        )],
                         &options.parser,
                     )
                     .await
@@ -56,6 +67,7 @@ pub async fn start_observing(
                         file_origin,
                         filename,
                         &options.parser,
+                        options.pick_up_rotated,
                     )
                     .await
                 }
@@ -72,6 +84,34 @@ pub async fn start_observing(
                 observing::concat::concat_files(operation_api, state, files, &options.parser).await
             }
         }
+        ObserveOrigin::Archive(archive, members) => {
+            if members.is_empty() {
+                Err(NativeError {
+                    severity: Severity::ERROR,
+                    kind: NativeErrorKind::Configuration,
+                    message: Some(String::from("No archive members are defined")),
+                })
+            } else {
+                observing::archive::observe_members(
+                    operation_api,
+                    state,
+                    archive,
+                    members,
+                    &options.parser,
+                )
+                .await
+            }
+        }
+        ObserveOrigin::Directory(uuid, config) => {
+            observing::directory::observe_directory(
+                operation_api,
+                state,
+                uuid,
+                config,
+                &options.parser,
+            )
+            .await
+        }
         ObserveOrigin::Stream(uuid, transport) => {
             observing::stream::observe_stream(
                 operation_api,
@@ -80,6 +120,7 @@ pub async fn start_observing(
                 transport,
                 &options.parser,
                 rx_sde,
+                options.recording.as_deref(),
             )
             .await
         }
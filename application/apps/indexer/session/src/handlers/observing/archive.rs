@@ -0,0 +1,55 @@
+use crate::{
+    events::{NativeError, NativeErrorKind},
+    operations::{OperationAPI, OperationResult},
+    progress::Severity,
+    state::SessionStateAPI,
+};
+use sources::{
+    binary::{archive::ArchiveMemberReader, compressed::DecompressedReader},
+    factory::{FileFormat, ParserType},
+};
+use std::path::Path;
+
+/// Observes members of an archive one by one, without extracting them to disk. Compressed
+/// members are decompressed on the fly.
+pub async fn observe_members(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    archive: &Path,
+    members: &[(String, FileFormat, String)],
+    parser: &ParserType,
+) -> OperationResult<()> {
+    for (uuid, _, _) in members.iter() {
+        state.add_source(uuid).await?;
+    }
+    for (uuid, file_format, member) in members.iter() {
+        let source_id = state.get_source(uuid).await?.ok_or(NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Io,
+            message: Some(format!(
+                "Cannot find source id for archive member {member} with alias {uuid}",
+            )),
+        })?;
+        let input = ArchiveMemberReader::open(archive, member)
+            .and_then(DecompressedReader::new)
+            .map_err(|e| NativeError {
+                severity: Severity::ERROR,
+                kind: NativeErrorKind::Io,
+                message: Some(format!(
+                    "Fail open {member} of archive {}: {e}",
+                    archive.to_string_lossy(),
+                )),
+            })?;
+        super::run_file_source(
+            operation_api.clone(),
+            state.clone(),
+            input,
+            file_format,
+            source_id,
+            parser,
+            None,
+        )
+        .await?;
+    }
+    Ok(Some(()))
+}
@@ -5,27 +5,24 @@ use crate::{
     state::SessionStateAPI,
 };
 use sources::{
-    binary::{
-        pcap::{legacy::PcapLegacyByteSource, ng::PcapngByteSource},
-        raw::BinaryByteSource,
-    },
-    factory::{FileFormat, ParserType},
+    binary::compressed::DecompressedReader,
+    factory::{ConcatFile, ParserType},
 };
-use std::{fs::File, path::PathBuf};
 
-#[allow(clippy::type_complexity)]
+/// Observes files one by one. Each file is parsed with its own parser if it has one,
+/// otherwise with `parser`.
 pub async fn concat_files(
     operation_api: OperationAPI,
     state: SessionStateAPI,
-    files: &[(String, FileFormat, PathBuf)],
+    files: &[ConcatFile],
     parser: &ParserType,
 ) -> OperationResult<()> {
     for file in files.iter() {
-        let (uuid, _file_type, _filename) = file;
-        state.add_source(uuid).await?;
+        state.add_source(&file.uuid).await?;
     }
     for file in files.iter() {
-        let (uuid, file_type, filename) = file;
+        let (uuid, file_type, filename) = (&file.uuid, &file.format, &file.path);
+        let parser = file.parser(parser);
         let source_id = state.get_source(uuid).await?.ok_or(NativeError {
             severity: Severity::ERROR,
             kind: NativeErrorKind::Io,
@@ -35,7 +32,7 @@ pub async fn concat_files(
                 uuid,
             )),
         })?;
-        let input_file = File::open(filename).map_err(|e| NativeError {
+        let input_file = DecompressedReader::open(filename).map_err(|e| NativeError {
             severity: Severity::ERROR,
             kind: NativeErrorKind::Io,
             message: Some(format!(
@@ -44,56 +41,16 @@ pub async fn concat_files(
                 e
             )),
         })?;
-        match file_type {
-            FileFormat::Binary => {
-                super::run_source(
-                    operation_api.clone(),
-                    state.clone(),
-                    BinaryByteSource::new(input_file),
-                    source_id,
-                    parser,
-                    None,
-                    None,
-                )
-                .await?
-            }
-            FileFormat::PcapLegacy => {
-                super::run_source(
-                    operation_api.clone(),
-                    state.clone(),
-                    PcapLegacyByteSource::new(input_file)?,
-                    source_id,
-                    parser,
-                    None,
-                    None,
-                )
-                .await?
-            }
-            FileFormat::PcapNG => {
-                super::run_source(
-                    operation_api.clone(),
-                    state.clone(),
-                    PcapngByteSource::new(input_file)?,
-                    source_id,
-                    parser,
-                    None,
-                    None,
-                )
-                .await?
-            }
-            FileFormat::Text => {
-                super::run_source(
-                    operation_api.clone(),
-                    state.clone(),
-                    BinaryByteSource::new(input_file),
-                    source_id,
-                    parser,
-                    None,
-                    None,
-                )
-                .await?
-            }
-        };
+        super::run_file_source(
+            operation_api.clone(),
+            state.clone(),
+            input_file,
+            file_type,
+            source_id,
+            parser,
+            None,
+        )
+        .await?;
     }
     Ok(Some(()))
 }
@@ -0,0 +1,486 @@
+use crate::{
+    events::{NativeError, NativeErrorKind},
+    operations::{OperationAPI, OperationResult},
+    progress::Severity,
+    state::SessionStateAPI,
+    tail,
+};
+use dlt_core::parse::{dlt_message, ParsedMessage};
+use futures::{executor::block_on, future::pending};
+use globset::{GlobBuilder, GlobMatcher};
+use log::{debug, warn};
+use notify::RecommendedWatcher;
+use parsers::timestamp::{detect_format, TimestampFormat};
+use sources::{
+    binary::{
+        compressed::DecompressedReader,
+        pcap::{legacy::PcapLegacyByteSource, ng::PcapngByteSource},
+    },
+    factory::{DirectoryConfig, FileFormat, FileOrder, ParserType},
+    ByteSource,
+};
+use std::{
+    cmp::Reverse,
+    collections::{HashSet, VecDeque},
+    fs::{self, Metadata},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tokio::{
+    join, select,
+    sync::mpsc::{channel, Receiver, UnboundedReceiver},
+    task::spawn_blocking,
+    time::{sleep, Duration},
+};
+use tokio_util::sync::CancellationToken;
+
+/// Interval of checking the folder for new files if there are no notifications from the
+/// file system (or file system notifications are not available)
+const WATCHING_INTERVAL_MS: u64 = 1000;
+/// Count of bytes read from the beginning of a file to find the timestamp of the first message
+const TIMESTAMP_PROBE_LEN: u64 = 64 * 1024;
+/// Count of loads from a source to find the timestamp of the first message
+const TIMESTAMP_PROBE_LOADS: usize = 16;
+/// Extensions of compressed rotated files, which are ignored to find the rotation index
+const COMPRESSION_EXTENSIONS: [&str; 4] = ["gz", "zst", "xz", "bz2"];
+
+type Notifications = Option<(RecommendedWatcher, UnboundedReceiver<()>)>;
+
+/// Identifies a file which was added to the session already. Rotated files are renamed,
+/// therefore files are identified by inode where possible.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum KnownFile {
+    Inode(u64, u64),
+    Path(PathBuf),
+}
+
+/// Observes the files of a folder matching the glob one by one in the defined order. Each
+/// file is registered as its own source. If watching is enabled, the most recent file is
+/// tailed and newly created files are appended to the session until the operation is
+/// cancelled.
+pub async fn observe_directory(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    uuid: &str,
+    config: &DirectoryConfig,
+    parser: &ParserType,
+) -> OperationResult<()> {
+    let matcher = glob_matcher(&config.glob)?;
+    let mut known = HashSet::new();
+    let found = new_files(config, &matcher, parser, &mut known).await?;
+    if found.is_empty() && !config.watch {
+        return Err(NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::FileNotFound,
+            message: Some(format!(
+                "No files matching {} in {}",
+                config.glob,
+                config.path.display()
+            )),
+        });
+    }
+    let mut queue = VecDeque::new();
+    add_sources(&state, uuid, found, &mut queue).await?;
+    let mut notifications = if config.watch {
+        tail::watch_folder(&config.path, |_| true)
+    } else {
+        None
+    };
+    let cancel = operation_api.cancellation_token();
+    loop {
+        let Some((filename, source_id)) = queue.pop_front() else {
+            if !config.watch || !wait(&mut notifications, &cancel).await {
+                break;
+            }
+            let found = new_files(config, &matcher, parser, &mut known).await?;
+            add_sources(&state, uuid, found, &mut queue).await?;
+            continue;
+        };
+        if !config.watch || !queue.is_empty() {
+            observe_file(
+                operation_api.clone(),
+                state.clone(),
+                &filename,
+                &config.format,
+                source_id,
+                parser,
+                None,
+            )
+            .await?;
+            continue;
+        }
+        // The most recent file is tailed until a newer file appears
+        let (tx_tail, rx_tail) = channel(1);
+        let (observed, found) = join!(
+            observe_file(
+                operation_api.clone(),
+                state.clone(),
+                &filename,
+                &config.format,
+                source_id,
+                parser,
+                Some(rx_tail),
+            ),
+            async {
+                // Tailing is finished as soon as the sender is dropped
+                let tx_tail = tx_tail;
+                let mut len = file_len(&filename).await;
+                while wait(&mut notifications, &cancel).await {
+                    let found = new_files(config, &matcher, parser, &mut known).await?;
+                    let updated = file_len(&filename).await;
+                    if updated != len || !found.is_empty() {
+                        len = updated;
+                        // A pending signal is enough to read the rest of the file
+                        let _ = tx_tail.try_send(Ok(tail::Change::Appended));
+                    }
+                    if !found.is_empty() {
+                        debug!(
+                            "Found {} new file(s) in {}",
+                            found.len(),
+                            config.path.display()
+                        );
+                        return Ok(found);
+                    }
+                }
+                Ok::<Vec<PathBuf>, NativeError>(Vec::new())
+            }
+        );
+        observed?;
+        add_sources(&state, uuid, found?, &mut queue).await?;
+        if cancel.is_cancelled() {
+            break;
+        }
+    }
+    Ok(Some(()))
+}
+
+/// Waits for the next change in the folder. Returns `false` if the operation was cancelled.
+async fn wait(notifications: &mut Notifications, cancel: &CancellationToken) -> bool {
+    select! {
+        _ = cancel.cancelled() => false,
+        _ = async {
+            match notifications.as_mut() {
+                Some((_, rx_notification)) => {
+                    rx_notification.recv().await;
+                }
+                None => pending::<()>().await,
+            }
+        } => true,
+        _ = sleep(Duration::from_millis(WATCHING_INTERVAL_MS)) => true,
+    }
+}
+
+async fn file_len(filename: &Path) -> u64 {
+    tokio::fs::metadata(filename)
+        .await
+        .map(|md| md.len())
+        .unwrap_or(0)
+}
+
+async fn add_sources(
+    state: &SessionStateAPI,
+    uuid: &str,
+    files: Vec<PathBuf>,
+    queue: &mut VecDeque<(PathBuf, u16)>,
+) -> Result<(), NativeError> {
+    for filename in files {
+        let name = filename
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let source_id = state.add_source(&format!("{uuid}:{name}")).await?;
+        debug!("File {} is added as source {source_id}", filename.display());
+        queue.push_back((filename, source_id));
+    }
+    Ok(())
+}
+
+/// Returns files of the folder matching the glob which aren't known yet, in the defined order
+async fn new_files(
+    config: &DirectoryConfig,
+    matcher: &GlobMatcher,
+    parser: &ParserType,
+    known: &mut HashSet<KnownFile>,
+) -> Result<Vec<PathBuf>, NativeError> {
+    let (folder, matcher) = (config.path.clone(), matcher.clone());
+    let entries = spawn_blocking(move || scan_folder(&folder, &matcher))
+        .await
+        .map_err(|e| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Interrupted,
+            message: Some(format!("Fail to scan folder: {e}")),
+        })?
+        .map_err(|e| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Io,
+            message: Some(format!(
+                "Fail to read folder {}: {e}",
+                config.path.display()
+            )),
+        })?;
+    let mut files = Vec::new();
+    for (filename, md) in entries {
+        let key = match tail::file_id(&md) {
+            Some((dev, ino)) => KnownFile::Inode(dev, ino),
+            None => KnownFile::Path(filename.clone()),
+        };
+        if known.insert(key) {
+            files.push((filename, md.modified().ok()));
+        }
+    }
+    Ok(order_files(files, &config.order, &config.format, parser).await)
+}
+
+/// Lists the files of `folder` with names matching the glob
+fn scan_folder(folder: &Path, matcher: &GlobMatcher) -> io::Result<Vec<(PathBuf, Metadata)>> {
+    Ok(fs::read_dir(folder)?
+        .flatten()
+        .filter(|entry| matcher.is_match(entry.file_name()))
+        .filter_map(|entry| {
+            let md = entry.metadata().ok()?;
+            md.is_file().then(|| (entry.path(), md))
+        })
+        .collect())
+}
+
+/// Compiles the glob of file names. `*` and `?` don't match path separators.
+fn glob_matcher(glob: &str) -> Result<GlobMatcher, NativeError> {
+    GlobBuilder::new(glob)
+        .literal_separator(true)
+        .build()
+        .map(|glob| glob.compile_matcher())
+        .map_err(|e| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Configuration,
+            message: Some(format!("Invalid glob {glob}: {e}")),
+        })
+}
+
+async fn order_files(
+    mut files: Vec<(PathBuf, Option<SystemTime>)>,
+    order: &FileOrder,
+    format: &FileFormat,
+    parser: &ParserType,
+) -> Vec<PathBuf> {
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    match order {
+        FileOrder::Name => {}
+        FileOrder::Rotation => {
+            files.sort_by_key(|(filename, _)| Reverse(rotation_index(filename)));
+        }
+        FileOrder::ModificationTime => {
+            files.sort_by_key(|(_, modified)| *modified);
+        }
+        FileOrder::FirstTimestamp => {
+            let filenames: Vec<PathBuf> =
+                files.iter().map(|(filename, _)| filename.clone()).collect();
+            let (format, parser) = (format.clone(), parser.clone());
+            let timestamps = spawn_blocking(move || {
+                filenames
+                    .iter()
+                    .map(|filename| first_timestamp(filename, &format, &parser))
+                    .collect()
+            })
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Fail to read first timestamps of files: {e}");
+                vec![None; files.len()]
+            });
+            let mut stamped: Vec<_> = timestamps.into_iter().zip(files).collect();
+            // Stable sort keeps files without timestamp in order of names
+            stamped.sort_by_key(|(ts, _)| (ts.is_none(), *ts));
+            return stamped
+                .into_iter()
+                .map(|(_, (filename, _))| filename)
+                .collect();
+        }
+    }
+    files.into_iter().map(|(filename, _)| filename).collect()
+}
+
+/// Index of a rotated file: `app.log.3` and `app.log.3.gz` are 3, `app.log` is 0
+fn rotation_index(filename: &Path) -> u32 {
+    let name = filename
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let mut parts = name.rsplit('.').peekable();
+    if parts
+        .peek()
+        .is_some_and(|ext| COMPRESSION_EXTENSIONS.contains(ext))
+    {
+        parts.next();
+    }
+    parts
+        .next()
+        .and_then(|ext| ext.parse::<u32>().ok())
+        .unwrap_or(0)
+}
+
+/// Timestamp (ms) of the first message of the file; `None` if the timestamp cannot be
+/// extracted for the combination of file format and parser. Reading is blocking.
+fn first_timestamp(filename: &Path, format: &FileFormat, parser: &ParserType) -> Option<u64> {
+    let input = DecompressedReader::open(filename)
+        .map_err(|e| warn!("Fail to open {}: {e}", filename.display()))
+        .ok()?;
+    match format {
+        // Sources of files don't wait for data, so blocking on them is fine
+        FileFormat::PcapNG => block_on(first_source_timestamp(PcapngByteSource::new(input).ok()?)),
+        FileFormat::PcapLegacy => block_on(first_source_timestamp(
+            PcapLegacyByteSource::new(input).ok()?,
+        )),
+        FileFormat::Binary => match parser {
+            ParserType::Dlt(settings) if settings.with_storage_header => first_dlt_timestamp(input),
+            _ => None,
+        },
+        FileFormat::Text => first_text_timestamp(input),
+    }
+}
+
+async fn first_source_timestamp<S: ByteSource>(mut source: S) -> Option<u64> {
+    for _ in 0..TIMESTAMP_PROBE_LOADS {
+        let info = source.load(None).await.ok()??;
+        if info.last_known_ts.is_some() {
+            return info.last_known_ts;
+        }
+        if info.newly_loaded_bytes == 0 {
+            return None;
+        }
+        source.consume(source.len());
+    }
+    None
+}
+
+fn first_dlt_timestamp(input: DecompressedReader) -> Option<u64> {
+    let mut content = Vec::new();
+    input
+        .take(TIMESTAMP_PROBE_LEN)
+        .read_to_end(&mut content)
+        .ok()?;
+    match dlt_message(&content, None, true) {
+        Ok((_, ParsedMessage::Item(msg))) => msg.storage_header.map(|header| {
+            u64::from(header.timestamp.seconds) * 1000
+                + u64::from(header.timestamp.microseconds) / 1000
+        }),
+        _ => None,
+    }
+}
+
+/// Detects the timestamp format of the first lines and extracts the first timestamp
+fn first_text_timestamp(input: DecompressedReader) -> Option<u64> {
+    let mut content = Vec::new();
+    input
+        .take(TIMESTAMP_PROBE_LEN)
+        .read_to_end(&mut content)
+        .ok()?;
+    let content = String::from_utf8_lossy(&content);
+    let detected = detect_format(content.lines())?;
+    let format = TimestampFormat::new(&detected.format).ok()?;
+    content
+        .lines()
+        .find_map(|line| format.extract(line, None))
+        .and_then(|ts| u64::try_from(ts).ok())
+}
+
+async fn observe_file(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    filename: &Path,
+    format: &FileFormat,
+    source_id: u16,
+    parser: &ParserType,
+    rx_tail: Option<Receiver<Result<tail::Change, tail::Error>>>,
+) -> OperationResult<()> {
+    let input = DecompressedReader::open(filename).map_err(|e| NativeError {
+        severity: Severity::ERROR,
+        kind: NativeErrorKind::Io,
+        message: Some(format!(
+            "Fail open file {}: {}",
+            filename.to_string_lossy(),
+            e
+        )),
+    })?;
+    super::run_file_source(
+        operation_api,
+        state,
+        input,
+        format,
+        source_id,
+        parser,
+        rx_tail,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matcher() {
+        let matches = |glob: &str, name: &str| glob_matcher(glob).unwrap().is_match(name);
+        assert!(matches("*.dlt", "trace.dlt"));
+        assert!(matches("*.dlt", ".dlt"));
+        assert!(!matches("*.dlt", "trace.dlt.1"));
+        assert!(matches("app.log*", "app.log"));
+        assert!(matches("app.log*", "app.log.12.gz"));
+        assert!(matches("app?.log", "app1.log"));
+        assert!(!matches("app?.log", "app.log"));
+        assert!(matches("*a*b*", "xxaxxbxx"));
+        assert!(!matches("*a*b", "xxbxxa"));
+        assert!(matches("trace.{dlt,pcap}", "trace.pcap"));
+        assert!(glob_matcher("app[.log").is_err());
+    }
+
+    #[test]
+    fn test_first_text_timestamp() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("app.log");
+        fs::write(
+            &path,
+            "starting\n2024-03-05 10:11:12.345 INFO started\n2024-03-05 10:11:13.000 INFO ready\n",
+        )
+        .unwrap();
+        assert_eq!(
+            first_timestamp(&path, &FileFormat::Text, &ParserType::Text),
+            Some(1_709_633_472_345)
+        );
+        fs::write(&path, "no timestamps here\n").unwrap();
+        assert_eq!(
+            first_timestamp(&path, &FileFormat::Text, &ParserType::Text),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rotation_index() {
+        assert_eq!(rotation_index(Path::new("app.log")), 0);
+        assert_eq!(rotation_index(Path::new("app.log.1")), 1);
+        assert_eq!(rotation_index(Path::new("/var/log/app.log.12")), 12);
+        assert_eq!(rotation_index(Path::new("app.log.3.gz")), 3);
+        assert_eq!(rotation_index(Path::new("app.log.gz")), 0);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_order() {
+        let files = ["app.log", "app.log.10", "app.log.2", "app.log.1.gz"]
+            .into_iter()
+            .map(|name| (PathBuf::from(name), None))
+            .collect();
+        let ordered = order_files(
+            files,
+            &FileOrder::Rotation,
+            &FileFormat::Text,
+            &ParserType::Text,
+        )
+        .await;
+        assert_eq!(
+            ordered,
+            ["app.log.10", "app.log.2", "app.log.1.gz", "app.log"]
+                .into_iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,23 +1,29 @@
 use crate::{
-    events::{NativeError, NativeErrorKind},
+    events::{CallbackEvent, NativeError, NativeErrorKind},
     operations::{OperationAPI, OperationResult},
-    progress::Severity,
+    progress::{Progress, Severity, Ticks},
     state::SessionStateAPI,
-    tail,
+    tail::{self, RotatingFile},
 };
+use log::debug;
 use sources::{
-    binary::{
-        pcap::{legacy::PcapLegacyByteSource, ng::PcapngByteSource},
-        raw::BinaryByteSource,
-    },
+    binary::compressed::DecompressedReader,
     factory::{FileFormat, ParserType},
 };
-use std::{fs::File, path::Path};
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    sync::atomic::Ordering,
+};
 use tokio::{
     join, select,
     sync::mpsc::{channel, Receiver, Sender},
+    time::{sleep, Duration},
 };
 
+const DECOMPRESSION_PROGRESS_INTERVAL_MS: u64 = 500;
+
 #[allow(clippy::type_complexity)]
 pub async fn observe_file<'a>(
     operation_api: OperationAPI,
@@ -26,62 +32,30 @@ pub async fn observe_file<'a>(
     file_format: &FileFormat,
     filename: &Path,
     parser: &'a ParserType,
+    pick_up_rotated: bool,
 ) -> OperationResult<()> {
     let source_id = state.add_source(uuid).await?;
+    let input = input_file(filename, pick_up_rotated)?;
+    if input.compression().is_some() {
+        return observe_compressed_file(
+            operation_api,
+            state,
+            source_id,
+            file_format,
+            filename,
+            input,
+            parser,
+        )
+        .await;
+    }
     let (tx_tail, mut rx_tail): (
-        Sender<Result<(), tail::Error>>,
-        Receiver<Result<(), tail::Error>>,
+        Sender<Result<tail::Change, tail::Error>>,
+        Receiver<Result<tail::Change, tail::Error>>,
     ) = channel(1);
     match file_format {
-        FileFormat::Binary => {
-            let source = BinaryByteSource::new(input_file(filename)?);
-            let (_, listening) = join!(
-                tail::track(filename, tx_tail, operation_api.cancellation_token()),
-                super::run_source(
-                    operation_api,
-                    state,
-                    source,
-                    source_id,
-                    parser,
-                    None,
-                    Some(rx_tail)
-                )
-            );
-            listening
-        }
-        FileFormat::PcapLegacy => {
-            let source = PcapLegacyByteSource::new(input_file(filename)?)?;
-            let (_, listening) = join!(
-                tail::track(filename, tx_tail, operation_api.cancellation_token()),
-                super::run_source(
-                    operation_api,
-                    state,
-                    source,
-                    source_id,
-                    parser,
-                    None,
-                    Some(rx_tail)
-                )
-            );
-            listening
-        }
-        FileFormat::PcapNG => {
-            let source = PcapngByteSource::new(input_file(filename)?)?;
-            let (_, listening) = join!(
-                tail::track(filename, tx_tail, operation_api.cancellation_token()),
-                super::run_source(
-                    operation_api,
-                    state,
-                    source,
-                    source_id,
-                    parser,
-                    None,
-                    Some(rx_tail)
-                )
-            );
-            listening
-        }
         FileFormat::Text => {
+            // Keeps the linked content available, if the file is rotated later
+            let linked = File::open(filename).map_err(|e| open_error(filename, e))?;
             state.set_session_file(Some(filename.to_path_buf())).await?;
             // Grab main file content
             state.update_session(source_id).await?;
@@ -90,41 +64,181 @@ pub async fn observe_file<'a>(
             state.file_read().await?;
             // Switching to tail
             let cancel = operation_api.cancellation_token();
+            // Tracking is stopped separately, if the file cannot be followed anymore
+            let tracking = cancel.child_token();
             let (result, tracker) = join!(
                 async {
                     let result = select! {
-                        res = async move {
-                            while let Some(update) = rx_tail.recv().await {
-                                update.map_err(|err| NativeError {
-                                    severity: Severity::ERROR,
-                                    kind: NativeErrorKind::Interrupted,
-                                    message: Some(err.to_string()),
-                                })?;
-                                state.update_session(source_id).await?;
-                            }
-                            Ok(())
-                        } => res,
-                        _ = cancel.cancelled() => Ok(())
+                        res = follow_linked(
+                            &state,
+                            source_id,
+                            filename,
+                            linked,
+                            pick_up_rotated,
+                            &mut rx_tail,
+                        ) => res,
+                        _ = cancel.cancelled() => Ok(None)
                     };
+                    let result = match result {
+                        // The file was rotated; its new content is read like a binary file
+                        Ok(Some(rest)) => {
+                            super::run_file_source(
+                                operation_api.clone(),
+                                state.clone(),
+                                rest,
+                                file_format,
+                                source_id,
+                                parser,
+                                Some(rx_tail),
+                            )
+                            .await
+                        }
+                        result => result.map(|_| None),
+                    };
+                    tracking.cancel();
                     result
                 },
-                tail::track(filename, tx_tail, operation_api.cancellation_token()),
+                tail::track(filename, tx_tail, tracking.clone()),
             );
-            result
-                .and_then(|_| {
-                    tracker.map_err(|e| NativeError {
+            result.and_then(|result| {
+                tracker
+                    .map_err(|e| NativeError {
                         severity: Severity::ERROR,
                         kind: NativeErrorKind::Interrupted,
                         message: Some(format!("Tailing error: {e}")),
                     })
-                })
-                .map(|_| None)
+                    .map(|_| result)
+            })
+        }
+        FileFormat::Binary | FileFormat::PcapLegacy | FileFormat::PcapNG => {
+            let (_, listening) = join!(
+                tail::track(filename, tx_tail, operation_api.cancellation_token()),
+                super::run_file_source(
+                    operation_api.clone(),
+                    state,
+                    input,
+                    file_format,
+                    source_id,
+                    parser,
+                    Some(rx_tail)
+                )
+            );
+            listening
+        }
+    }
+}
+
+/// Follows the text file the session is linked to, until the file is rotated. The session is
+/// detached from the file in this case, because the indexed content isn't available at its
+/// path anymore. Returns the content to read after the rotation.
+async fn follow_linked(
+    state: &SessionStateAPI,
+    source_id: u16,
+    filename: &Path,
+    linked: File,
+    pick_up_rotated: bool,
+    rx_tail: &mut Receiver<Result<tail::Change, tail::Error>>,
+) -> Result<Option<Box<dyn Read + Send>>, NativeError> {
+    while let Some(update) = rx_tail.recv().await {
+        let change = update.map_err(|err| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Interrupted,
+            message: Some(err.to_string()),
+        })?;
+        if change == tail::Change::Appended {
+            state.update_session(source_id).await?;
+            continue;
         }
+        debug!(
+            "Linked file {} was rotated ({change:?})",
+            filename.display()
+        );
+        // A renamed file is still available via the opened handle; the content of a file
+        // truncated in place is available in its rotated copy only.
+        let previous = match change {
+            tail::Change::Truncated => File::open(tail::rotated_path(filename)).unwrap_or(linked),
+            _ => linked,
+        };
+        // Handles share the position, so `previous` continues right after the copied content
+        state.detach_session_file(previous.try_clone()?).await?;
+        let current = RotatingFile::open(filename)
+            .map_err(|e| open_error(filename, e))?
+            .pick_up_rotated(pick_up_rotated);
+        // Content written to a truncated file between the last update and the truncation is
+        // left in its rotated copy
+        let rest: Box<dyn Read + Send> = if change == tail::Change::Replaced || pick_up_rotated {
+            Box::new(previous.chain(current))
+        } else {
+            Box::new(current)
+        };
+        return Ok(Some(rest));
     }
+    Ok(None)
 }
 
-fn input_file(filename: &Path) -> Result<File, NativeError> {
-    File::open(filename).map_err(|e| NativeError {
+/// Observes a compressed file. Compressed files aren't tailed; the progress of reading is
+/// reported against the size of the compressed file.
+async fn observe_compressed_file(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    source_id: u16,
+    file_format: &FileFormat,
+    filename: &Path,
+    input: DecompressedReader,
+    parser: &ParserType,
+) -> OperationResult<()> {
+    let consumed = input.consumed();
+    let total = filename.metadata().ok().map(|meta| meta.len());
+    let report = |count: u64| {
+        operation_api.emit(CallbackEvent::Progress {
+            uuid: operation_api.id(),
+            progress: Progress::Ticks(Ticks {
+                count,
+                state: Some(String::from("decompressing")),
+                total,
+            }),
+        });
+    };
+    // Text files can't be linked as session file, because content has to be decompressed
+    let observing = super::run_file_source(
+        operation_api.clone(),
+        state,
+        input,
+        file_format,
+        source_id,
+        parser,
+        None,
+    );
+    let result = select! {
+        result = observing => result,
+        _ = async {
+            loop {
+                report(consumed.load(Ordering::Relaxed));
+                sleep(Duration::from_millis(DECOMPRESSION_PROGRESS_INTERVAL_MS)).await;
+            }
+        } => Err(NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Interrupted,
+            message: Some(format!(
+                "Reporting the progress of decompressing {} has stopped",
+                filename.display()
+            )),
+        }),
+    };
+    report(consumed.load(Ordering::Relaxed));
+    result
+}
+
+/// Opens the file following its rotation, so the content of the file is still read
+/// correctly while tailing it.
+fn input_file(filename: &Path, pick_up_rotated: bool) -> Result<DecompressedReader, NativeError> {
+    RotatingFile::open(filename)
+        .and_then(|file| DecompressedReader::new(file.pick_up_rotated(pick_up_rotated)))
+        .map_err(|e| open_error(filename, e))
+}
+
+fn open_error(filename: &Path, e: io::Error) -> NativeError {
+    NativeError {
         severity: Severity::ERROR,
         kind: NativeErrorKind::Io,
         message: Some(format!(
@@ -132,5 +246,5 @@ fn input_file(filename: &Path) -> Result<File, NativeError> {
             filename.to_string_lossy(),
             e
         )),
-    })
+    }
 }
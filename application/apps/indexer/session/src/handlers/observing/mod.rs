@@ -1,11 +1,12 @@
-use std::path::PathBuf;
+use std::{io::Read, path::PathBuf};
 
 use crate::{
     operations::{OperationAPI, OperationResult},
+    progress::Severity,
     state::SessionStateAPI,
     tail,
 };
-use log::trace;
+use log::{trace, warn};
 use parsers::{
     dlt::{fmt::FormatOptions, DltParser},
     someip::{FibexMetadata as FibexSomeipMetadata, SomeipParser},
@@ -13,7 +14,11 @@ use parsers::{
     LogMessage, MessageStreamItem, ParseYield, Parser,
 };
 use sources::{
-    factory::ParserType,
+    binary::{
+        pcap::{legacy::PcapLegacyByteSource, ng::PcapngByteSource},
+        raw::BinaryByteSource,
+    },
+    factory::{FileFormat, ParserType},
     producer::{MessageProducer, SdeReceiver},
     ByteSource,
 };
@@ -30,7 +35,9 @@ enum Next<T: LogMessage> {
     Waiting,
 }
 
+pub mod archive;
 pub mod concat;
+pub mod directory;
 pub mod file;
 pub mod stream;
 
@@ -43,7 +50,7 @@ pub async fn run_source<S: ByteSource>(
     source_id: u16,
     parser: &ParserType,
     rx_sde: Option<SdeReceiver>,
-    rx_tail: Option<Receiver<Result<(), tail::Error>>>,
+    rx_tail: Option<Receiver<Result<tail::Change, tail::Error>>>,
 ) -> OperationResult<()> {
     let cancel = operation_api.cancellation_token();
 
@@ -67,6 +74,60 @@ pub async fn run_source<S: ByteSource>(
     operation_result
 }
 
+/// Runs the source matching `file_format` on top of `input`, e.g. a decompressed file or a
+/// member of an archive
+pub async fn run_file_source<R: Read + Send>(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    input: R,
+    file_format: &FileFormat,
+    source_id: u16,
+    parser: &ParserType,
+    rx_tail: Option<Receiver<Result<tail::Change, tail::Error>>>,
+) -> OperationResult<()> {
+    match file_format {
+        FileFormat::Binary | FileFormat::Text => {
+            let source = BinaryByteSource::new(input);
+            run_source(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                None,
+                rx_tail,
+            )
+            .await
+        }
+        FileFormat::PcapLegacy => {
+            let source = PcapLegacyByteSource::new(input)?;
+            run_source(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                None,
+                rx_tail,
+            )
+            .await
+        }
+        FileFormat::PcapNG => {
+            let source = PcapngByteSource::new(input)?;
+            run_source(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                None,
+                rx_tail,
+            )
+            .await
+        }
+    }
+}
+
 /// Contains all implementation details for running the source and the producer in the session
 async fn run_source_intern<S: ByteSource>(
     operation_api: OperationAPI,
@@ -75,7 +136,7 @@ async fn run_source_intern<S: ByteSource>(
     source_id: u16,
     parser: &ParserType,
     rx_sde: Option<SdeReceiver>,
-    rx_tail: Option<Receiver<Result<(), tail::Error>>>,
+    rx_tail: Option<Receiver<Result<tail::Change, tail::Error>>>,
 ) -> OperationResult<()> {
     match parser {
         ParserType::SomeIp(settings) => {
@@ -115,95 +176,111 @@ async fn run_producer<T: LogMessage, P: Parser<T>, S: ByteSource>(
     state: SessionStateAPI,
     source_id: u16,
     mut producer: MessageProducer<T, P, S>,
-    mut rx_tail: Option<Receiver<Result<(), tail::Error>>>,
+    mut rx_tail: Option<Receiver<Result<tail::Change, tail::Error>>>,
 ) -> OperationResult<()> {
     use log::debug;
     state.set_session_file(None).await?;
     operation_api.processing();
     let cancel = operation_api.cancellation_token();
-    let stream = producer.as_stream();
-    futures::pin_mut!(stream);
-    let cancel_on_tail = cancel.clone();
-    while let Some(next) = select! {
-        next_from_stream = async {
-            match timeout(Duration::from_millis(FLUSH_TIMEOUT_IN_MS as u64), stream.next()).await {
-                Ok(items) => {
-                    if let Some(items) = items {
-                        Some(Next::Items(items))
-                    } else {
-                        Some(Next::Waiting)
+    let mut reported_problems = None;
+    loop {
+        {
+            let stream = producer.as_stream();
+            futures::pin_mut!(stream);
+            while let Some(next) = select! {
+                next_from_stream = async {
+                    match timeout(Duration::from_millis(FLUSH_TIMEOUT_IN_MS as u64), stream.next()).await {
+                        Ok(items) => {
+                            if let Some(items) = items {
+                                Some(Next::Items(items))
+                            } else {
+                                Some(Next::Waiting)
+                            }
+                        },
+                        Err(_) => Some(Next::Timeout),
                     }
-                },
-                Err(_) => Some(Next::Timeout),
-            }
-        } => next_from_stream,
-        _ = cancel.cancelled() => None,
-    } {
-        match next {
-            Next::Items(items) => {
-                for (_, item) in items {
-                    match item {
-                        MessageStreamItem::Item(ParseYield::Message(item)) => {
-                            state
-                                .write_session_file(source_id, format!("{item}\n"))
-                                .await?;
-                        }
-                        MessageStreamItem::Item(ParseYield::MessageAndAttachment((
-                            item,
-                            attachment,
-                        ))) => {
-                            state
-                                .write_session_file(source_id, format!("{item}\n"))
-                                .await?;
-                            state.add_attachment(attachment)?;
-                        }
-                        MessageStreamItem::Item(ParseYield::Attachment(attachment)) => {
-                            state.add_attachment(attachment)?;
+                } => next_from_stream,
+                _ = cancel.cancelled() => None,
+            } {
+                match next {
+                    Next::Items(items) => {
+                        for (_, item) in items {
+                            match item {
+                                MessageStreamItem::Item(ParseYield::Message(item)) => {
+                                    state
+                                        .write_timed_session_file(
+                                            source_id,
+                                            format!("{item}\n"),
+                                            item.timestamp(),
+                                        )
+                                        .await?;
+                                }
+                                MessageStreamItem::Item(ParseYield::MessageAndAttachment((
+                                    item,
+                                    attachment,
+                                ))) => {
+                                    state
+                                        .write_timed_session_file(
+                                            source_id,
+                                            format!("{item}\n"),
+                                            item.timestamp(),
+                                        )
+                                        .await?;
+                                    state.add_attachment(attachment)?;
+                                }
+                                MessageStreamItem::Item(ParseYield::Attachment(attachment)) => {
+                                    state.add_attachment(attachment)?;
+                                }
+                                MessageStreamItem::Done => {
+                                    trace!("observe, message stream is done");
+                                    state.flush_session_file().await?;
+                                    state.file_read().await?;
+                                }
+                                // MessageStreamItem::FileRead => {
+                                //     state.file_read().await?;
+                                // }
+                                MessageStreamItem::Skipped => {
+                                    trace!("observe: skipped a message");
+                                }
+                                MessageStreamItem::Incomplete => {
+                                    trace!("observe: incomplete message");
+                                }
+                                MessageStreamItem::Empty => {
+                                    trace!("observe: empty message");
+                                }
+                            }
                         }
-                        MessageStreamItem::Done => {
-                            trace!("observe, message stream is done");
+                    }
+                    Next::Timeout => {
+                        if !state.is_closing() {
                             state.flush_session_file().await?;
-                            state.file_read().await?;
-                        }
-                        // MessageStreamItem::FileRead => {
-                        //     state.file_read().await?;
-                        // }
-                        MessageStreamItem::Skipped => {
-                            trace!("observe: skipped a message");
                         }
-                        MessageStreamItem::Incomplete => {
-                            trace!("observe: incomplete message");
-                        }
-                        MessageStreamItem::Empty => {
-                            trace!("observe: empty message");
-                        }
-                    }
-                }
-            }
-            Next::Timeout => {
-                if !state.is_closing() {
-                    state.flush_session_file().await?;
-                }
-            }
-            Next::Waiting => {
-                if let Some(mut rx_tail) = rx_tail.take() {
-                    if select! {
-                        next_from_stream = rx_tail.recv() => {
-                            if let Some(result) = next_from_stream {
-                                result.is_err()
-                            } else {
-                                true
-                            }
-                        },
-                        _ = cancel_on_tail.cancelled() => true,
-                    } {
-                        break;
                     }
-                } else {
-                    break;
+                    Next::Waiting => break,
                 }
             }
         }
+        // Problems of the source are reported as soon as all its data is read
+        if let Some(problems) = producer
+            .problems()
+            .filter(|problems| reported_problems.as_ref() != Some(problems))
+        {
+            warn!("Source {source_id}: {problems}");
+            operation_api.notify(Severity::WARNING, problems.clone());
+            reported_problems = Some(problems);
+        }
+        // Source has no more data; continue as soon as the tailed file is changed
+        let Some(rx_tail) = rx_tail.as_mut() else {
+            break;
+        };
+        let changed = select! {
+            change = rx_tail.recv() => matches!(change, Some(Ok(_))),
+            _ = cancel.cancelled() => false,
+        };
+        if !changed {
+            break;
+        }
+        producer.resume();
     }
     debug!("listen done");
     Ok(None)
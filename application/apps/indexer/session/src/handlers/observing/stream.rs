@@ -1,18 +1,52 @@
 use crate::{
-    events::{NativeError, NativeErrorKind},
+    events::{CallbackEvent, NativeError, NativeErrorKind, StreamConnectionState},
     handlers::observing,
     operations::{OperationAPI, OperationResult},
     progress::Severity,
     state::SessionStateAPI,
 };
+use futures::{
+    future::{pending, ready},
+    stream::{FuturesUnordered, StreamExt},
+    Future,
+};
+use log::{debug, warn};
 use sources::{
+    binary::{
+        pcap::{legacy::PcapLegacyByteSource, ng::PcapngByteSource},
+        raw::BinaryByteSource,
+    },
     command::process::ProcessSource,
-    factory::{ParserType, Transport},
-    producer::SdeReceiver,
+    factory::{
+        FileFormat, ParserType, ReconnectPolicy, ReplayTransportConfig, TCPTransportConfig,
+        TCPTransportMode, Transport,
+    },
+    producer::{SdeReceiver, SdeSender},
+    recorder::{Recording, RecordingFormat},
+    replay::{ReplayFraming, ReplaySource},
     serial::serialport::SerialSource,
     socket::{tcp::TcpSource, udp::UdpSource},
+    stdin::StdinSource,
+    AddressFilter, ByteSource, SourceFilter,
+};
+#[cfg(unix)]
+use sources::{
+    factory::{UnixSocketKind, UnixSocketMode, UnixTransportConfig},
+    fifo::FifoSource,
+    socket::unix::{self, UnixDatagramSource, UnixStreamSource},
+};
+use std::{fmt::Display, fs::File, io, net::SocketAddr, path::Path};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::{
+    net::{lookup_host, TcpListener},
+    select,
+    sync::mpsc::unbounded_channel,
+    time::sleep,
 };
 
+/// Observes the stream of `transport`. If a `recording` path is given, the received data
+/// is recorded into this file (see [`RecordingFormat::of_stream`] for the format).
 pub async fn observe_stream<'a>(
     operation_api: OperationAPI,
     state: SessionStateAPI,
@@ -20,7 +54,48 @@ pub async fn observe_stream<'a>(
     transport: &Transport,
     parser: &'a ParserType,
     rx_sde: Option<SdeReceiver>,
+    recording: Option<&Path>,
 ) -> OperationResult<()> {
+    let recording = recording
+        .map(|path| {
+            Recording::create(path, RecordingFormat::of_stream(transport, parser)).map_err(|e| {
+                NativeError {
+                    severity: Severity::ERROR,
+                    kind: NativeErrorKind::Io,
+                    message: Some(format!("Fail to create recording {}: {e}", path.display())),
+                }
+            })
+        })
+        .transpose()?;
+    let recording = recording.as_ref();
+    if let Transport::TCP(TCPTransportConfig {
+        bind_addr,
+        mode: TCPTransportMode::Listen,
+        filter,
+        ..
+    }) = transport
+    {
+        return listen_tcp(
+            operation_api,
+            state,
+            uuid,
+            bind_addr,
+            filter,
+            parser,
+            recording,
+        )
+        .await;
+    }
+    #[cfg(unix)]
+    if let Transport::Unix(UnixTransportConfig {
+        path,
+        kind: UnixSocketKind::Stream,
+        mode: UnixSocketMode::Listen,
+        ..
+    }) = transport
+    {
+        return listen_unix(operation_api, state, uuid, path, parser, recording).await;
+    }
     let source_id = state.add_source(uuid).await?;
     match transport {
         Transport::UDP(settings) => {
@@ -31,50 +106,58 @@ pub async fn observe_stream<'a>(
                     kind: NativeErrorKind::Interrupted,
                     message: Some(format!("{e}")),
                 })?;
-            observing::run_source(
+            let udp_source = if settings.filter.is_empty() {
+                udp_source
+            } else {
+                udp_source.with_filter(SourceFilter::remote(settings.filter.clone()))
+            };
+            let udp_source = match settings.send_to.as_deref() {
+                Some(peer) => udp_source.with_peer(resolve_peer(peer).await?),
+                None => udp_source,
+            };
+            run_recorded(
                 operation_api,
                 state,
                 udp_source,
                 source_id,
                 parser,
                 rx_sde,
-                None,
+                recording,
             )
             .await
         }
         Transport::TCP(settings) => {
-            let tcp_source = TcpSource::new(settings.bind_addr.clone())
-                .await
-                .map_err(|e| NativeError {
-                    severity: Severity::ERROR,
-                    kind: NativeErrorKind::Interrupted,
-                    message: Some(format!("{e}")),
-                })?;
-            observing::run_source(
+            observe_with_reconnect(
                 operation_api,
                 state,
-                tcp_source,
                 source_id,
                 parser,
                 rx_sde,
-                None,
+                &settings.bind_addr,
+                settings.reconnect.as_ref(),
+                recording,
+                || async {
+                    let source = TcpSource::new(settings.bind_addr.as_str()).await?;
+                    Ok::<_, io::Error>(if settings.filter.is_empty() {
+                        source
+                    } else {
+                        source.with_filter(SourceFilter::remote(settings.filter.clone()))
+                    })
+                },
             )
             .await
         }
         Transport::Serial(settings) => {
-            let serial_source = SerialSource::new(settings).map_err(|e| NativeError {
-                severity: Severity::ERROR,
-                kind: NativeErrorKind::Interrupted,
-                message: Some(format!("{e}")),
-            })?;
-            observing::run_source(
+            observe_with_reconnect(
                 operation_api,
                 state,
-                serial_source,
                 source_id,
                 parser,
                 rx_sde,
-                None,
+                &settings.path,
+                settings.reconnect.as_ref(),
+                recording,
+                || ready(SerialSource::new(settings)),
             )
             .await
         }
@@ -90,16 +173,605 @@ pub async fn observe_stream<'a>(
                 kind: NativeErrorKind::Interrupted,
                 message: Some(format!("{e}")),
             })?;
-            observing::run_source(
+            run_recorded(
                 operation_api,
                 state,
                 process_source,
                 source_id,
                 parser,
                 rx_sde,
+                recording,
+            )
+            .await
+        }
+        #[cfg(unix)]
+        Transport::Unix(settings) => match (&settings.kind, &settings.mode) {
+            (UnixSocketKind::Stream, _) => {
+                observe_with_reconnect(
+                    operation_api,
+                    state,
+                    source_id,
+                    parser,
+                    rx_sde,
+                    &settings.path.display().to_string(),
+                    settings.reconnect.as_ref(),
+                    recording,
+                    || UnixStreamSource::new(&settings.path),
+                )
+                .await
+            }
+            (UnixSocketKind::Datagram, mode) => {
+                let datagram_source = if mode == &UnixSocketMode::Listen {
+                    UnixDatagramSource::bind(&settings.path)
+                } else {
+                    UnixDatagramSource::connect(&settings.path)
+                }
+                .map_err(|e| NativeError {
+                    severity: Severity::ERROR,
+                    kind: NativeErrorKind::Interrupted,
+                    message: Some(format!("{}: {e}", settings.path.display())),
+                })?;
+                run_recorded(
+                    operation_api,
+                    state,
+                    datagram_source,
+                    source_id,
+                    parser,
+                    rx_sde,
+                    recording,
+                )
+                .await
+            }
+        },
+        #[cfg(unix)]
+        Transport::Fifo(settings) => {
+            let fifo_source = FifoSource::new(&settings.path).map_err(|e| NativeError {
+                severity: Severity::ERROR,
+                kind: NativeErrorKind::Interrupted,
+                message: Some(format!("{}: {e}", settings.path.display())),
+            })?;
+            run_recorded(
+                operation_api,
+                state,
+                fifo_source,
+                source_id,
+                parser,
+                rx_sde,
+                recording,
+            )
+            .await
+        }
+        Transport::Replay(settings) => {
+            replay_file(
+                operation_api,
+                state,
+                source_id,
+                parser,
+                rx_sde,
+                settings,
+                recording,
+            )
+            .await
+        }
+        Transport::Stdin => {
+            run_recorded(
+                operation_api,
+                state,
+                StdinSource::new(),
+                source_id,
+                parser,
+                rx_sde,
+                recording,
+            )
+            .await
+        }
+        #[cfg(not(unix))]
+        Transport::Unix(_) | Transport::Fifo(_) => Err(NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Interrupted,
+            message: Some(String::from(
+                "Unix domain sockets and FIFOs are supported on Unix platforms only",
+            )),
+        }),
+    }
+}
+
+/// Resolves the address `peer` given as `host:port`; the first resolved address is used
+async fn resolve_peer(peer: &str) -> Result<SocketAddr, NativeError> {
+    let invalid = |reason: String| NativeError {
+        severity: Severity::ERROR,
+        kind: NativeErrorKind::Configuration,
+        message: Some(format!("Invalid peer address {peer}: {reason}")),
+    };
+    lookup_host(peer)
+        .await
+        .map_err(|e| invalid(e.to_string()))?
+        .next()
+        .ok_or_else(|| invalid(String::from("no address found")))
+}
+
+/// Replays the file of `settings` with the original timing of its messages. The replay is
+/// controlled with [`sources::sde::ReplayControl`] requests.
+async fn replay_file(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    source_id: u16,
+    parser: &ParserType,
+    rx_sde: Option<SdeReceiver>,
+    settings: &ReplayTransportConfig,
+    recording: Option<&Recording>,
+) -> OperationResult<()> {
+    let input = File::open(&settings.path).map_err(|e| NativeError {
+        severity: Severity::ERROR,
+        kind: NativeErrorKind::Io,
+        message: Some(format!("{}: {e}", settings.path.display())),
+    })?;
+    let speed = settings.speed;
+    match settings.format {
+        FileFormat::PcapNG => {
+            let source =
+                ReplaySource::new(PcapngByteSource::new(input)?, ReplayFraming::Packets, speed)?;
+            run_recorded(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                rx_sde,
+                recording,
+            )
+            .await
+        }
+        FileFormat::PcapLegacy => {
+            let source = ReplaySource::new(
+                PcapLegacyByteSource::new(input)?,
+                ReplayFraming::Packets,
+                speed,
+            )?;
+            run_recorded(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                rx_sde,
+                recording,
+            )
+            .await
+        }
+        FileFormat::Binary => {
+            let framing = match parser {
+                ParserType::Dlt(dlt) if dlt.with_storage_header => ReplayFraming::Dlt,
+                ParserType::Text => ReplayFraming::lines_of(&settings.path)?,
+                _ => return Err(NativeError {
+                    severity: Severity::ERROR,
+                    kind: NativeErrorKind::Configuration,
+                    message: Some(String::from(
+                        "Binary files can be replayed as DLT with storage header or as text only",
+                    )),
+                }),
+            };
+            let source = ReplaySource::new(BinaryByteSource::new(input), framing, speed)?;
+            run_recorded(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                rx_sde,
+                recording,
+            )
+            .await
+        }
+        FileFormat::Text => {
+            let source = ReplaySource::new(
+                BinaryByteSource::new(input),
+                ReplayFraming::lines_of(&settings.path)?,
+                speed,
+            )?;
+            run_recorded(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                rx_sde,
+                recording,
+            )
+            .await
+        }
+    }
+}
+
+/// Client accepted by a [`Listener`]; it's observed as its own source
+struct Client<S> {
+    /// Identifies the client in the alias of its source
+    id: String,
+    /// Describes the client in reports
+    name: String,
+    source: S,
+}
+
+/// Listening socket accepting clients, which are observed by [`accept_clients`]
+trait Listener {
+    type Source: ByteSource;
+
+    /// Waits for the next client. Returns `None` if the client was rejected or couldn't be
+    /// accepted; the listener keeps running in both cases.
+    async fn accept(&mut self) -> Option<Client<Self::Source>>;
+}
+
+struct TcpClients<'a> {
+    listener: TcpListener,
+    bind_addr: &'a str,
+    filter: &'a AddressFilter,
+}
+
+impl Listener for TcpClients<'_> {
+    type Source = TcpSource;
+
+    async fn accept(&mut self) -> Option<Client<TcpSource>> {
+        let (stream, peer) = match self.listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                warn!("Fail to accept TCP client on {}: {err}", self.bind_addr);
+                return None;
+            }
+        };
+        if !self.filter.matches(&peer) {
+            debug!("TCP client {peer} is rejected by filter");
+            return None;
+        }
+        Some(Client {
+            id: peer.to_string(),
+            name: format!("TCP client {peer}"),
+            source: TcpSource::from_stream(stream),
+        })
+    }
+}
+
+/// Clients of a Unix stream socket, numbered in the order of connecting
+#[cfg(unix)]
+struct UnixClients<'a> {
+    listener: UnixListener,
+    path: &'a Path,
+    count: usize,
+}
+
+#[cfg(unix)]
+impl Listener for UnixClients<'_> {
+    type Source = UnixStreamSource;
+
+    async fn accept(&mut self) -> Option<Client<UnixStreamSource>> {
+        let stream = match self.listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                warn!("Fail to accept client on {}: {err}", self.path.display());
+                return None;
+            }
+        };
+        self.count += 1;
+        Some(Client {
+            id: self.count.to_string(),
+            name: format!("Client #{}", self.count),
+            source: UnixStreamSource::from_stream(stream),
+        })
+    }
+}
+
+/// Binds `bind_addr` and observes every accepted TCP client (see [`accept_clients`]).
+/// Clients not matching `filter` are dropped right away.
+async fn listen_tcp(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    uuid: &str,
+    bind_addr: &str,
+    filter: &AddressFilter,
+    parser: &ParserType,
+    recording: Option<&Recording>,
+) -> OperationResult<()> {
+    let listener = TcpListener::bind(bind_addr)
+        .await
+        .map_err(|e| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Interrupted,
+            message: Some(format!("Fail to listen on {bind_addr}: {e}")),
+        })?;
+    let clients = TcpClients {
+        listener,
+        bind_addr,
+        filter,
+    };
+    accept_clients(operation_api, state, uuid, clients, parser, recording).await
+}
+
+/// Binds the Unix stream socket `path` and observes every accepted client (see
+/// [`accept_clients`]). The socket file is removed as soon as nobody is listening on it.
+#[cfg(unix)]
+async fn listen_unix(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    uuid: &str,
+    path: &Path,
+    parser: &ParserType,
+    recording: Option<&Recording>,
+) -> OperationResult<()> {
+    let listener = unix::listen(path).map_err(|e| NativeError {
+        severity: Severity::ERROR,
+        kind: NativeErrorKind::Interrupted,
+        message: Some(format!("Fail to listen on {}: {e}", path.display())),
+    })?;
+    let clients = UnixClients {
+        listener,
+        path,
+        count: 0,
+    };
+    let result = accept_clients(operation_api, state, uuid, clients, parser, recording).await;
+    if let Err(err) = std::fs::remove_file(path) {
+        warn!("Fail to remove socket file {}: {err}", path.display());
+    }
+    result
+}
+
+/// Observes every client accepted by `listener`. Each client is registered as its own
+/// source; disconnected clients are reported and new clients are accepted until the
+/// operation is cancelled. A failing client is reported and ends on its own, other clients
+/// aren't affected.
+async fn accept_clients<L: Listener>(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    uuid: &str,
+    mut listener: L,
+    parser: &ParserType,
+    recording: Option<&Recording>,
+) -> OperationResult<()> {
+    state.set_session_file(None).await?;
+    operation_api.processing();
+    let cancel = operation_api.cancellation_token();
+    let mut clients = FuturesUnordered::new();
+    loop {
+        select! {
+            accepted = listener.accept() => {
+                let Some(client) = accepted else {
+                    continue;
+                };
+                let source_id = state.add_source(&format!("{uuid}:{}", client.id)).await?;
+                debug!("{} is connected as source {source_id}", client.name);
+                let client_api = operation_api.child();
+                let state = state.clone();
+                clients.push(async move {
+                    let result = run_recorded(
+                        client_api,
+                        state,
+                        client.source,
+                        source_id,
+                        parser,
+                        None,
+                        recording,
+                    )
+                    .await;
+                    (client.name, source_id, result)
+                });
+            }
+            Some((name, source_id, result)) = clients.next(), if !clients.is_empty() => {
+                report_client(&operation_api, &name, result);
+                operation_api.emit(CallbackEvent::StreamConnection {
+                    uuid: operation_api.id(),
+                    source_id,
+                    state: StreamConnectionState::Disconnected,
+                });
+            }
+            _ = cancel.cancelled() => break,
+        }
+    }
+    drop(listener);
+    // Tokens of clients are children of the cancelled token; clients are finishing on their own.
+    while let Some((name, _, result)) = clients.next().await {
+        report_client(&operation_api, &name, result);
+    }
+    Ok(None)
+}
+
+/// Reports the end of a client accepted by a listener. Errors of a client are reported as
+/// warnings, since the listener and other clients keep running.
+fn report_client(operation_api: &OperationAPI, client: &str, result: OperationResult<()>) {
+    match result {
+        Ok(_) => debug!("{client} is disconnected"),
+        Err(err) => {
+            let reason = err.message.unwrap_or_else(|| format!("{:?}", err.kind));
+            warn!("{client} failed: {reason}");
+            operation_api.notify(Severity::WARNING, format!("{client} failed: {reason}"));
+        }
+    }
+}
+
+/// Observes a stream source created with `connect`. If the connection gets lost and a
+/// reconnect `policy` is defined, the source is re-created with growing delays between
+/// attempts. Each disconnect and reconnect is marked in the session file and reported
+/// with [`CallbackEvent::StreamConnection`].
+#[allow(clippy::too_many_arguments)]
+async fn observe_with_reconnect<S, E, F, Fut>(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    source_id: u16,
+    parser: &ParserType,
+    mut rx_sde: Option<SdeReceiver>,
+    target: &str,
+    policy: Option<&ReconnectPolicy>,
+    recording: Option<&Recording>,
+    connect: F,
+) -> OperationResult<()>
+where
+    S: ByteSource,
+    E: Display,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<S, E>>,
+{
+    let cancel = operation_api.cancellation_token();
+    let mut source = connect().await.map_err(|e| NativeError {
+        severity: Severity::ERROR,
+        kind: NativeErrorKind::Interrupted,
+        message: Some(format!("{e}")),
+    })?;
+    loop {
+        let result = if let Some(rx_sde) = rx_sde.as_mut() {
+            // Producer takes ownership of SDE receiver. To keep the original receiver alive
+            // between connections, messages are forwarded into a receiver per connection.
+            let (tx_connection_sde, rx_connection_sde): (SdeSender, SdeReceiver) =
+                unbounded_channel();
+            select! {
+                result = run_recorded(
+                    operation_api.clone(),
+                    state.clone(),
+                    source,
+                    source_id,
+                    parser,
+                    Some(rx_connection_sde),
+                    recording,
+                ) => result,
+                _ = forward_sde(rx_sde, &tx_connection_sde) => Ok(None),
+            }
+        } else {
+            run_recorded(
+                operation_api.clone(),
+                state.clone(),
+                source,
+                source_id,
+                parser,
                 None,
+                recording,
             )
             .await
+        };
+        let Some(policy) = policy else {
+            return result;
+        };
+        result?;
+        if cancel.is_cancelled() {
+            return Ok(None);
         }
+        warn!("Connection to {target} is lost");
+        write_marker(
+            &state,
+            source_id,
+            &format!("connection to {target} is lost"),
+        )
+        .await?;
+        operation_api.emit(CallbackEvent::StreamConnection {
+            uuid: operation_api.id(),
+            source_id,
+            state: StreamConnectionState::Disconnected,
+        });
+        let mut attempt: u32 = 0;
+        source = loop {
+            attempt += 1;
+            if policy.max_attempts.is_some_and(|max| attempt > max) {
+                let msg = format!(
+                    "fail to reconnect to {target} after {} attempts",
+                    attempt - 1
+                );
+                write_marker(&state, source_id, &msg).await?;
+                return Err(NativeError {
+                    severity: Severity::ERROR,
+                    kind: NativeErrorKind::Interrupted,
+                    message: Some(msg),
+                });
+            }
+            operation_api.emit(CallbackEvent::StreamConnection {
+                uuid: operation_api.id(),
+                source_id,
+                state: StreamConnectionState::Reconnecting { attempt },
+            });
+            let connected = select! {
+                connected = async {
+                    sleep(policy.delay(attempt)).await;
+                    connect().await
+                } => connected,
+                _ = cancel.cancelled() => return Ok(None),
+            };
+            match connected {
+                Ok(source) => break source,
+                Err(err) => debug!("Reconnect attempt {attempt} to {target} failed: {err}"),
+            }
+        };
+        debug!("Reconnected to {target} with attempt {attempt}");
+        write_marker(
+            &state,
+            source_id,
+            &format!("reconnected to {target} (attempt {attempt})"),
+        )
+        .await?;
+        operation_api.emit(CallbackEvent::StreamConnection {
+            uuid: operation_api.id(),
+            source_id,
+            state: StreamConnectionState::Reconnected { attempt },
+        });
     }
 }
+
+/// Runs `source` like [`observing::run_source`]; if a `recording` is defined, the data of
+/// the source is recorded into it.
+async fn run_recorded<S: ByteSource>(
+    operation_api: OperationAPI,
+    state: SessionStateAPI,
+    source: S,
+    source_id: u16,
+    parser: &ParserType,
+    rx_sde: Option<SdeReceiver>,
+    recording: Option<&Recording>,
+) -> OperationResult<()> {
+    match recording {
+        Some(recording) => {
+            observing::run_source(
+                operation_api,
+                state,
+                recording.tee(source),
+                source_id,
+                parser,
+                rx_sde,
+                None,
+            )
+            .await
+        }
+        None => {
+            observing::run_source(
+                operation_api,
+                state,
+                source,
+                source_id,
+                parser,
+                rx_sde,
+                None,
+            )
+            .await
+        }
+    }
+}
+
+/// Forwards SDE messages into the receiver of the current connection. Never returns while
+/// the connection is alive, so it can be raced against the running source.
+async fn forward_sde(rx_sde: &mut SdeReceiver, tx_connection_sde: &SdeSender) {
+    while let Some(msg) = rx_sde.recv().await {
+        if let Err(err) = tx_connection_sde.send(msg) {
+            let (_, tx_response) = err.0;
+            if tx_response
+                .send(Err(String::from("Source is disconnected")))
+                .is_err()
+            {
+                warn!("Fail to send back SDE response");
+            }
+        }
+    }
+    pending::<()>().await
+}
+
+async fn write_marker(
+    state: &SessionStateAPI,
+    source_id: u16,
+    msg: &str,
+) -> Result<(), NativeError> {
+    state
+        .write_session_file(source_id, format!("[chipmunk] {msg}\n"))
+        .await?;
+    state.flush_session_file().await
+}
@@ -1,7 +1,7 @@
 use crate::{
     events::{CallbackEvent, ComputationError, NativeError, NativeErrorKind, OperationDone},
     handlers,
-    progress::Severity,
+    progress::{Notification, Progress, Severity},
     state::SessionStateAPI,
     tracker::OperationTrackerAPI,
 };
@@ -131,8 +131,6 @@ pub enum OperationKind {
     },
     Merge {
         files: Vec<FileMergeOptions>,
-        out_path: PathBuf,
-        append: bool,
         source_id: String,
     },
     GetNearestPosition(u64),
@@ -225,6 +223,18 @@ impl OperationAPI {
         self.emit(CallbackEvent::OperationProcessing(self.id()));
     }
 
+    /// Reports an issue of the operation which doesn't end it
+    pub fn notify(&self, severity: Severity, content: String) {
+        self.emit(CallbackEvent::Progress {
+            uuid: self.id(),
+            progress: Progress::Notification(Notification {
+                severity,
+                content,
+                line: None,
+            }),
+        });
+    }
+
     pub async fn finish<T>(&self, result: OperationResult<T>, alias: &str)
     where
         T: Serialize + std::fmt::Debug,
@@ -279,6 +289,16 @@ impl OperationAPI {
         self.cancellation_token.clone()
     }
 
+    /// Creates an API for a part of the operation which is cancelled on its own (e.g. on
+    /// its failure) without cancelling the operation; cancelling the operation cancels it
+    /// as well.
+    pub fn child(&self) -> Self {
+        Self {
+            cancellation_token: self.cancellation_token.child_token(),
+            ..self.clone()
+        }
+    }
+
     pub async fn execute(
         &self,
         operation: Operation,
@@ -407,13 +427,12 @@ impl OperationAPI {
                         }
                     }
                 }
-                OperationKind::Merge {
-                    files: _,
-                    out_path: _,
-                    append: _,
-                    source_id: _,
-                } => {
-                    unimplemented!("merging not yet supported");
+                OperationKind::Merge { files, source_id } => {
+                    api.finish(
+                        handlers::merge::merge_files(api.clone(), state, &files, &source_id).await,
+                        operation_str,
+                    )
+                    .await;
                 }
                 OperationKind::Sleep(ms, ignore_cancellation) => {
                     api.finish(
@@ -3,15 +3,20 @@ use crate::{
     operations,
     operations::Operation,
     state,
-    state::{AttachmentInfo, GrabbedElement, IndexesMode, SessionStateAPI, SourceDefinition},
+    state::{
+        AttachmentInfo, DeltasRequest, GrabbedElement, IndexesMode, SessionStateAPI,
+        SourceDefinition,
+    },
     tracker,
     tracker::OperationTrackerAPI,
 };
 use futures::Future;
 use log::{debug, error, warn};
+use merging::merger::FileMergeOptions;
+use parsers::timestamp::TimeCorrection;
 use processor::{grabber::LineRange, search::filter::SearchFilter};
 use sources::{factory::ObserveOptions, sde};
-use std::{ops::RangeInclusive, path::PathBuf};
+use std::{collections::HashMap, ops::RangeInclusive, path::PathBuf};
 use tokio::{
     join,
     sync::{
@@ -107,7 +112,7 @@ impl Session {
                             &tx_operations,
                             &destroying,
                             "state",
-                            state::run(rx_state_api, tx_callback_events_state)
+                            state::run(rx_state_api, state_api.clone(), tx_callback_events_state)
                         ),
                         Self::run(
                             &tx_operations,
@@ -152,9 +157,15 @@ impl Session {
         self.state.clone()
     }
 
-    pub async fn grab(&self, range: LineRange) -> Result<Vec<GrabbedElement>, ComputationError> {
+    /// Grabs rows of the session. If `deltas` is defined, each row gets its time deltas to
+    /// the previous row, the previous row of the same source and the reference row.
+    pub async fn grab(
+        &self,
+        range: LineRange,
+        deltas: Option<DeltasRequest>,
+    ) -> Result<Vec<GrabbedElement>, ComputationError> {
         self.state
-            .grab(range)
+            .grab(range, deltas)
             .await
             .map_err(ComputationError::NativeError)
     }
@@ -162,9 +173,10 @@ impl Session {
     pub async fn grab_indexed(
         &self,
         range: RangeInclusive<u64>,
+        deltas: Option<DeltasRequest>,
     ) -> Result<Vec<GrabbedElement>, ComputationError> {
         self.state
-            .grab_indexed(range)
+            .grab_indexed(range, deltas)
             .await
             .map_err(ComputationError::NativeError)
     }
@@ -240,6 +252,69 @@ impl Session {
             .map_err(ComputationError::NativeError)
     }
 
+    pub async fn get_row_at_time(&self, timestamp: i64) -> Result<Option<u64>, ComputationError> {
+        self.state
+            .get_row_at_time(timestamp)
+            .await
+            .map_err(ComputationError::NativeError)
+    }
+
+    pub async fn get_time_span(
+        &self,
+        range: RangeInclusive<u64>,
+    ) -> Result<Option<(i64, i64)>, ComputationError> {
+        self.state
+            .get_time_span(range)
+            .await
+            .map_err(ComputationError::NativeError)
+    }
+
+    pub async fn grab_by_time(
+        &self,
+        window: RangeInclusive<i64>,
+    ) -> Result<Vec<GrabbedElement>, ComputationError> {
+        self.state
+            .grab_by_time(window)
+            .await
+            .map_err(ComputationError::NativeError)
+    }
+
+    /// Sets the time correction of the source; `None` removes the correction
+    pub async fn set_time_correction(
+        &self,
+        source_id: u16,
+        correction: Option<TimeCorrection>,
+    ) -> Result<(), ComputationError> {
+        self.state
+            .set_time_correction(source_id, correction)
+            .await
+            .map_err(ComputationError::NativeError)
+    }
+
+    /// Fits the time correction of the source to anchors `(row, reference row)` of rows
+    /// logged at the same moment and sets it
+    pub async fn fit_time_correction(
+        &self,
+        source_id: u16,
+        anchors: Vec<(u64, u64)>,
+    ) -> Result<TimeCorrection, ComputationError> {
+        self.state
+            .fit_time_correction(source_id, anchors)
+            .await
+            .map_err(ComputationError::NativeError)
+    }
+
+    /// Returns the time corrections by alias of the source, e.g. to store them with the
+    /// observe options
+    pub async fn get_time_corrections(
+        &self,
+    ) -> Result<HashMap<String, TimeCorrection>, ComputationError> {
+        self.state
+            .get_time_corrections()
+            .await
+            .map_err(ComputationError::NativeError)
+    }
+
     pub async fn grab_ranges(
         &self,
         ranges: Vec<RangeInclusive<u64>>,
@@ -345,6 +420,22 @@ impl Session {
             .map_err(|e| ComputationError::Communication(e.to_string()))
     }
 
+    /// Merges `files` ordered by timestamp into the session; each file gets its own source
+    /// with the alias `<source_id>:<tag>`
+    pub fn merge(
+        &self,
+        operation_id: Uuid,
+        files: Vec<FileMergeOptions>,
+        source_id: String,
+    ) -> Result<(), ComputationError> {
+        self.tx_operations
+            .send(Operation::new(
+                operation_id,
+                operations::OperationKind::Merge { files, source_id },
+            ))
+            .map_err(|e| ComputationError::Communication(e.to_string()))
+    }
+
     pub async fn get_sources(&self) -> Result<Vec<SourceDefinition>, ComputationError> {
         self.state
             .get_sources_definitions()
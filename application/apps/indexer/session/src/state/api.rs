@@ -3,7 +3,7 @@ use crate::{
     state::{
         indexes::controller::Mode as IndexesMode,
         observed::Observed,
-        session_file::{GrabbedElement, SessionFileOrigin},
+        session_file::{DeltasRequest, GrabbedElement, SessionFileOrigin},
         source_ids::SourceDefinition,
         values::ValuesError,
         AttachmentInfo,
@@ -11,14 +11,14 @@ use crate::{
     tracker::OperationTrackerAPI,
 };
 use log::error;
-use parsers;
+use parsers::{self, timestamp::TimeCorrection};
 use processor::{
     grabber::LineRange,
     map::{FilterMatch, FiltersStats, NearestPosition, ScaledDistribution},
     search::searchers::{regular::RegularSearchHolder, values::ValueSearchHolder},
 };
 use sources::factory::ObserveOptions;
-use std::{collections::HashMap, fmt::Display, ops::RangeInclusive, path::PathBuf};
+use std::{collections::HashMap, fmt::Display, fs::File, ops::RangeInclusive, path::PathBuf};
 use tokio::sync::{
     mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
     oneshot,
@@ -30,8 +30,16 @@ use super::values::graph::CandlePoint;
 
 pub enum Api {
     SetSessionFile((Option<PathBuf>, oneshot::Sender<Result<(), NativeError>>)),
+    DetachSessionFile((File, oneshot::Sender<Result<u64, NativeError>>)),
     GetSessionFile(oneshot::Sender<Result<PathBuf, NativeError>>),
-    WriteSessionFile((u16, String, oneshot::Sender<Result<(), NativeError>>)),
+    WriteSessionFile(
+        (
+            u16,
+            String,
+            Option<i64>,
+            oneshot::Sender<Result<(), NativeError>>,
+        ),
+    ),
     FlushSessionFile(oneshot::Sender<Result<(), NativeError>>),
     GetSessionFileOrigin(oneshot::Sender<Result<Option<SessionFileOrigin>, NativeError>>),
     UpdateSession((u16, oneshot::Sender<Result<bool, NativeError>>)),
@@ -78,12 +86,14 @@ pub enum Api {
     Grab(
         (
             LineRange,
+            Option<DeltasRequest>,
             oneshot::Sender<Result<Vec<GrabbedElement>, NativeError>>,
         ),
     ),
     GrabIndexed(
         (
             RangeInclusive<u64>,
+            Option<DeltasRequest>,
             oneshot::Sender<Result<Vec<GrabbedElement>, NativeError>>,
         ),
     ),
@@ -135,6 +145,30 @@ pub enum Api {
     ),
     DropSearch(oneshot::Sender<bool>),
     GetNearestPosition((u64, oneshot::Sender<Option<NearestPosition>>)),
+    GetRowAtTime((i64, oneshot::Sender<Option<u64>>)),
+    GetTimeSpan((RangeInclusive<u64>, oneshot::Sender<Option<(i64, i64)>>)),
+    #[allow(clippy::type_complexity)]
+    GrabByTime(
+        (
+            RangeInclusive<i64>,
+            oneshot::Sender<Result<Vec<GrabbedElement>, NativeError>>,
+        ),
+    ),
+    SetTimeCorrection(
+        (
+            u16,
+            Option<TimeCorrection>,
+            oneshot::Sender<Result<(), NativeError>>,
+        ),
+    ),
+    FitTimeCorrection(
+        (
+            u16,
+            Vec<(u64, u64)>,
+            oneshot::Sender<Result<TimeCorrection, NativeError>>,
+        ),
+    ),
+    GetTimeCorrections(oneshot::Sender<HashMap<String, TimeCorrection>>),
     GetScaledMap((u16, Option<(u64, u64)>, oneshot::Sender<ScaledDistribution>)),
     SetMatches(
         (
@@ -172,6 +206,8 @@ pub enum Api {
     NotifyCancelingOperation(Uuid),
     NotifyCanceledOperation(Uuid),
     AddAttachment(parsers::Attachment),
+    /// Rows read to index their timestamps, starting at the given row
+    AddTimestamps((u64, Result<Vec<String>, NativeError>)),
     GetAttachments(oneshot::Sender<Vec<AttachmentInfo>>),
     // Used for tests of error handeling
     ShutdownWithError,
@@ -185,6 +221,7 @@ impl Display for Api {
             "{}",
             match self {
                 Self::SetSessionFile(_) => "SetSessionFile",
+                Self::DetachSessionFile(_) => "DetachSessionFile",
                 Self::GetSessionFile(_) => "GetSessionFile",
                 Self::WriteSessionFile(_) => "WriteSessionFile",
                 Self::FlushSessionFile(_) => "FlushSessionFile",
@@ -215,6 +252,12 @@ impl Display for Api {
                 Self::ExpandBreadcrumbs { .. } => "ExpandBreadcrumbs",
                 Self::GrabRanges(_) => "GrabRanges",
                 Self::GetNearestPosition(_) => "GetNearestPosition",
+                Self::GetRowAtTime(_) => "GetRowAtTime",
+                Self::GetTimeSpan(_) => "GetTimeSpan",
+                Self::GrabByTime(_) => "GrabByTime",
+                Self::SetTimeCorrection(_) => "SetTimeCorrection",
+                Self::FitTimeCorrection(_) => "FitTimeCorrection",
+                Self::GetTimeCorrections(_) => "GetTimeCorrections",
                 Self::GetScaledMap(_) => "GetScaledMap",
                 Self::SetMatches(_) => "SetMatches",
                 Self::GetSearchValuesHolder(_) => "GetSearchValuesHolder",
@@ -228,6 +271,7 @@ impl Display for Api {
                 Self::NotifyCancelingOperation(_) => "NotifyCancelingOperation",
                 Self::NotifyCanceledOperation(_) => "NotifyCanceledOperation",
                 Self::AddAttachment(_) => "AddAttachment",
+                Self::AddTimestamps(_) => "AddTimestamps",
                 Self::GetAttachments(_) => "GetAttachments",
                 Self::Shutdown => "Shutdown",
                 Self::ShutdownWithError => "ShutdownWithError",
@@ -271,18 +315,25 @@ impl SessionStateAPI {
         })
     }
 
-    pub async fn grab(&self, range: LineRange) -> Result<Vec<GrabbedElement>, NativeError> {
+    /// Grabs rows of the session; rows get time deltas if `deltas` is defined
+    pub async fn grab(
+        &self,
+        range: LineRange,
+        deltas: Option<DeltasRequest>,
+    ) -> Result<Vec<GrabbedElement>, NativeError> {
         let (tx, rx) = oneshot::channel();
-        self.exec_operation(Api::Grab((range.clone(), tx)), rx)
+        self.exec_operation(Api::Grab((range.clone(), deltas, tx)), rx)
             .await?
     }
 
+    /// Grabs rows of the indexed view; rows get time deltas if `deltas` is defined
     pub async fn grab_indexed(
         &self,
         range: RangeInclusive<u64>,
+        deltas: Option<DeltasRequest>,
     ) -> Result<Vec<GrabbedElement>, NativeError> {
         let (tx, rx) = oneshot::channel();
-        self.exec_operation(Api::GrabIndexed((range, tx)), rx)
+        self.exec_operation(Api::GrabIndexed((range, deltas, tx)), rx)
             .await?
     }
 
@@ -376,6 +427,62 @@ impl SessionStateAPI {
             .await
     }
 
+    /// Returns the first row with a timestamp at or after `timestamp` (ms since the Unix epoch)
+    pub async fn get_row_at_time(&self, timestamp: i64) -> Result<Option<u64>, NativeError> {
+        let (tx, rx) = oneshot::channel();
+        self.exec_operation(Api::GetRowAtTime((timestamp, tx)), rx)
+            .await
+    }
+
+    /// Returns the lowest and the highest timestamp of the rows in `range`
+    pub async fn get_time_span(
+        &self,
+        range: RangeInclusive<u64>,
+    ) -> Result<Option<(i64, i64)>, NativeError> {
+        let (tx, rx) = oneshot::channel();
+        self.exec_operation(Api::GetTimeSpan((range, tx)), rx).await
+    }
+
+    /// Grabs the rows with timestamps in `window`
+    pub async fn grab_by_time(
+        &self,
+        window: RangeInclusive<i64>,
+    ) -> Result<Vec<GrabbedElement>, NativeError> {
+        let (tx, rx) = oneshot::channel();
+        self.exec_operation(Api::GrabByTime((window, tx)), rx)
+            .await?
+    }
+
+    /// Sets the time correction of the source; `None` removes the correction
+    pub async fn set_time_correction(
+        &self,
+        source_id: u16,
+        correction: Option<TimeCorrection>,
+    ) -> Result<(), NativeError> {
+        let (tx, rx) = oneshot::channel();
+        self.exec_operation(Api::SetTimeCorrection((source_id, correction, tx)), rx)
+            .await?
+    }
+
+    /// Fits the time correction of the source to anchors `(row, reference row)` and sets it
+    pub async fn fit_time_correction(
+        &self,
+        source_id: u16,
+        anchors: Vec<(u64, u64)>,
+    ) -> Result<TimeCorrection, NativeError> {
+        let (tx, rx) = oneshot::channel();
+        self.exec_operation(Api::FitTimeCorrection((source_id, anchors, tx)), rx)
+            .await?
+    }
+
+    /// Returns the time corrections by alias of the source
+    pub async fn get_time_corrections(
+        &self,
+    ) -> Result<HashMap<String, TimeCorrection>, NativeError> {
+        let (tx, rx) = oneshot::channel();
+        self.exec_operation(Api::GetTimeCorrections(tx), rx).await
+    }
+
     pub async fn get_scaled_map(
         &self,
         dataset_len: u16,
@@ -392,14 +499,33 @@ impl SessionStateAPI {
             .await?
     }
 
+    /// Turns the session linked to a text file into a generated one; see
+    /// [`super::session_file::SessionFile::detach`]
+    pub async fn detach_session_file(&self, content: File) -> Result<u64, NativeError> {
+        let (tx, rx) = oneshot::channel();
+        self.exec_operation(Api::DetachSessionFile((content, tx)), rx)
+            .await?
+    }
+
     pub async fn get_session_file(&self) -> Result<PathBuf, NativeError> {
         let (tx, rx) = oneshot::channel();
         self.exec_operation(Api::GetSessionFile(tx), rx).await?
     }
 
     pub async fn write_session_file(&self, source_id: u16, msg: String) -> Result<(), NativeError> {
+        self.write_timed_session_file(source_id, msg, None).await
+    }
+
+    /// Writes `msg` into the session file; `timestamp` (ms since the Unix epoch) is added to
+    /// the timestamp index for the rows of `msg`
+    pub async fn write_timed_session_file(
+        &self,
+        source_id: u16,
+        msg: String,
+        timestamp: Option<i64>,
+    ) -> Result<(), NativeError> {
         let (tx, rx) = oneshot::channel();
-        self.exec_operation(Api::WriteSessionFile((source_id, msg, tx)), rx)
+        self.exec_operation(Api::WriteSessionFile((source_id, msg, timestamp, tx)), rx)
             .await?
     }
 
@@ -638,6 +764,18 @@ impl SessionStateAPI {
         })
     }
 
+    pub fn add_timestamps(
+        &self,
+        from: u64,
+        rows: Result<Vec<String>, NativeError>,
+    ) -> Result<(), NativeError> {
+        self.tx_api
+            .send(Api::AddTimestamps((from, rows)))
+            .map_err(|e| {
+                NativeError::channel(&format!("fail to send to Api::AddTimestamps; error: {e}",))
+            })
+    }
+
     pub async fn get_attachments(&self) -> Result<Vec<AttachmentInfo>, NativeError> {
         let (tx, rx) = oneshot::channel();
         self.exec_operation(Api::GetAttachments(tx), rx).await
@@ -3,7 +3,7 @@ use crate::{
     progress::Severity,
 };
 use log::{debug, error};
-use parsers;
+use parsers::{self, timestamp::TimeCorrection};
 use processor::{
     grabber::LineRange,
     map::SearchMap,
@@ -16,7 +16,10 @@ use std::{
     ops::RangeInclusive,
     path::PathBuf,
 };
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    task,
+};
 use tokio_util::sync::CancellationToken;
 use uuid::Uuid;
 
@@ -27,6 +30,7 @@ mod observed;
 mod searchers;
 mod session_file;
 mod source_ids;
+mod timestamps;
 pub(crate) mod values;
 
 pub use api::{Api, SessionStateAPI};
@@ -39,7 +43,9 @@ pub use indexes::{
 };
 use observed::Observed;
 use searchers::{SearcherState, Searchers};
-pub use session_file::{GrabbedElement, SessionFile, SessionFileOrigin, SessionFileState};
+pub use session_file::{
+    DeltasRequest, GrabbedElement, SessionFile, SessionFileOrigin, SessionFileState, TimeDeltas,
+};
 pub use source_ids::SourceDefinition;
 pub use values::Values;
 
@@ -61,10 +67,11 @@ pub struct SessionState {
     pub cancelling_operations: HashMap<Uuid, bool>,
     pub status: Status,
     pub debug: bool,
+    api: SessionStateAPI,
 }
 
 impl SessionState {
-    fn new(tx_callback_events: UnboundedSender<CallbackEvent>) -> Self {
+    fn new(api: SessionStateAPI, tx_callback_events: UnboundedSender<CallbackEvent>) -> Self {
         Self {
             session_file: SessionFile::new(),
             observed: Observed::new(),
@@ -79,18 +86,95 @@ impl SessionState {
             status: Status::Open,
             cancelling_operations: HashMap::new(),
             debug: false,
+            api,
+        }
+    }
+
+    /// Indexes timestamps of new rows; rows which have to be read for it are read in a
+    /// blocking task and passed back with `Api::AddTimestamps`
+    fn index_timestamps(
+        &mut self,
+        state_cancellation_token: CancellationToken,
+    ) -> Result<(), NativeError> {
+        if let Some(indexing) = self.session_file.index_timestamps()? {
+            let api = self.api.clone();
+            task::spawn_blocking(move || indexing.run(api, state_cancellation_token));
+        }
+        Ok(())
+    }
+
+    fn handle_add_timestamps(
+        &mut self,
+        from: u64,
+        rows: Result<Vec<String>, NativeError>,
+        state_cancellation_token: CancellationToken,
+    ) -> Result<(), NativeError> {
+        if self.session_file.add_timestamps(from, rows)? {
+            // Rows could be added while reading
+            self.index_timestamps(state_cancellation_token)?;
+        }
+        Ok(())
+    }
+
+    /// Adds the source; the source gets the time correction defined by observe options
+    fn handle_add_source(&mut self, alias: String) -> u16 {
+        let correction = self.observed.correction(&alias);
+        let source_id = self.session_file.sources.add_source(alias);
+        if let Some(correction) = correction.filter(|correction| !correction.is_identity()) {
+            self.session_file
+                .sources
+                .corrections
+                .insert(source_id, correction);
         }
+        source_id
+    }
+
+    /// Sets the time correction of the source and keeps it in the observe options
+    fn handle_set_time_correction(
+        &mut self,
+        source_id: u16,
+        correction: Option<TimeCorrection>,
+    ) -> Result<(), NativeError> {
+        self.session_file
+            .set_time_correction(source_id, correction.clone())?;
+        self.persist_time_correction(source_id, correction);
+        Ok(())
     }
 
-    fn handle_grab(&mut self, range: &LineRange) -> Result<Vec<GrabbedElement>, NativeError> {
+    /// Fits the time correction of the source and keeps it in the observe options
+    fn handle_fit_time_correction(
+        &mut self,
+        source_id: u16,
+        anchors: &[(u64, u64)],
+    ) -> Result<TimeCorrection, NativeError> {
+        let correction = self.session_file.fit_time_correction(source_id, anchors)?;
+        self.persist_time_correction(source_id, Some(correction.clone()));
+        Ok(correction)
+    }
+
+    fn persist_time_correction(&mut self, source_id: u16, correction: Option<TimeCorrection>) {
+        if let Some(alias) = self.session_file.sources.sources.get(&source_id) {
+            self.observed.set_correction(alias, correction);
+        }
+    }
+
+    fn handle_grab(
+        &mut self,
+        range: &LineRange,
+        deltas: Option<DeltasRequest>,
+    ) -> Result<Vec<GrabbedElement>, NativeError> {
         let mut elements = self.session_file.grab(range)?;
         self.indexes.naturalize(&mut elements);
+        if let Some(request) = deltas {
+            self.session_file.set_deltas(&mut elements, &request);
+        }
         Ok(elements)
     }
 
     fn handle_grab_indexed(
         &mut self,
         mut range: RangeInclusive<u64>,
+        deltas: Option<DeltasRequest>,
     ) -> Result<Vec<GrabbedElement>, NativeError> {
         let frame = self.indexes.frame(&mut range)?;
         let mut elements: Vec<GrabbedElement> = vec![];
@@ -99,6 +183,9 @@ impl SessionState {
             elements.append(&mut session_elements);
         }
         frame.naturalize(&mut elements)?;
+        if let Some(request) = deltas {
+            self.session_file.set_deltas(&mut elements, &request);
+        }
         Ok(elements)
     }
 
@@ -150,18 +237,28 @@ impl SessionState {
         Ok(elements)
     }
 
+    fn handle_grab_by_time(
+        &mut self,
+        window: RangeInclusive<i64>,
+    ) -> Result<Vec<GrabbedElement>, NativeError> {
+        let ranges = self.session_file.timestamps.rows_in(window);
+        self.handle_grab_ranges(ranges)
+    }
+
     async fn handle_write_session_file(
         &mut self,
         source_id: u16,
         state_cancellation_token: CancellationToken,
         tx_callback_events: UnboundedSender<CallbackEvent>,
         msg: String,
+        timestamp: Option<i64>,
     ) -> Result<(), NativeError> {
         if matches!(
             self.session_file
-                .write(source_id, state_cancellation_token.clone(), msg)?,
+                .write(source_id, state_cancellation_token.clone(), msg, timestamp)?,
             SessionFileState::Changed
         ) {
+            self.index_timestamps(state_cancellation_token.clone())?;
             self.update_searchers(state_cancellation_token, tx_callback_events)
                 .await?;
         }
@@ -179,6 +276,7 @@ impl SessionState {
                 .flush(state_cancellation_token.clone(), true)?,
             SessionFileState::Changed
         ) {
+            self.index_timestamps(state_cancellation_token.clone())?;
             self.update_searchers(state_cancellation_token, tx_callback_events)
                 .await?;
         }
@@ -195,6 +293,7 @@ impl SessionState {
             .session_file
             .update(source_id, state_cancellation_token.clone())?
         {
+            self.index_timestamps(state_cancellation_token.clone())?;
             self.update_searchers(state_cancellation_token, tx_callback_events)
                 .await?;
             Ok(true)
@@ -396,9 +495,10 @@ impl SessionState {
 
 pub async fn run(
     mut rx_api: UnboundedReceiver<Api>,
+    state_api: SessionStateAPI,
     tx_callback_events: UnboundedSender<CallbackEvent>,
 ) -> Result<(), NativeError> {
-    let mut state = SessionState::new(tx_callback_events.clone());
+    let mut state = SessionState::new(state_api, tx_callback_events.clone());
     let state_cancellation_token = CancellationToken::new();
     debug!("task is started");
     while let Some(msg) = rx_api.recv().await {
@@ -414,6 +514,13 @@ pub async fn run(
                     NativeError::channel("Failed to response to Api::SetSessionFile")
                 })?;
             }
+            Api::DetachSessionFile((content, tx_response)) => {
+                tx_response
+                    .send(state.session_file.detach(content))
+                    .map_err(|_| {
+                        NativeError::channel("Failed to respond to Api::DetachSessionFile")
+                    })?;
+            }
             Api::GetSessionFile(tx_response) => {
                 tx_response
                     .send(state.session_file.filename())
@@ -421,7 +528,7 @@ pub async fn run(
                         NativeError::channel("Failed to respond to Api::GetSessionFile")
                     })?;
             }
-            Api::WriteSessionFile((source_id, msg, tx_response)) => {
+            Api::WriteSessionFile((source_id, msg, timestamp, tx_response)) => {
                 tx_response
                     .send(
                         state
@@ -430,6 +537,7 @@ pub async fn run(
                                 state_cancellation_token.clone(),
                                 tx_callback_events.clone(),
                                 msg,
+                                timestamp,
                             )
                             .await,
                     )
@@ -469,7 +577,7 @@ pub async fn run(
             }
             Api::AddSource((uuid, tx_response)) => {
                 tx_response
-                    .send(state.session_file.sources.add_source(uuid))
+                    .send(state.handle_add_source(uuid))
                     .map_err(|_| NativeError::channel("Failed to respond to Api::AddSource"))?;
             }
             Api::GetSource((uuid, tx_response)) => {
@@ -518,14 +626,14 @@ pub async fn run(
                     .send(res)
                     .map_err(|_| NativeError::channel("Failed to respond to Api::ExportSession"))?;
             }
-            Api::Grab((range, tx_response)) => {
+            Api::Grab((range, deltas, tx_response)) => {
                 tx_response
-                    .send(state.handle_grab(&range))
+                    .send(state.handle_grab(&range, deltas))
                     .map_err(|_| NativeError::channel("Failed to respond to Api::Grab"))?;
             }
-            Api::GrabIndexed((range, tx_response)) => {
+            Api::GrabIndexed((range, deltas, tx_response)) => {
                 tx_response
-                    .send(state.handle_grab_indexed(range))
+                    .send(state.handle_grab_indexed(range, deltas))
                     .map_err(|_| NativeError::channel("Failed to respond to Api::GrabIndexed"))?;
             }
             Api::SetIndexingMode((mode, tx_response)) => {
@@ -593,6 +701,42 @@ pub async fn run(
                         NativeError::channel("Failed to respond to Api::GetNearestPosition")
                     })?;
             }
+            Api::GetRowAtTime((timestamp, tx_response)) => {
+                tx_response
+                    .send(state.session_file.timestamps.first_row_at(timestamp))
+                    .map_err(|_| NativeError::channel("Failed to respond to Api::GetRowAtTime"))?;
+            }
+            Api::GetTimeSpan((range, tx_response)) => {
+                tx_response
+                    .send(state.session_file.timestamps.span(&range))
+                    .map_err(|_| NativeError::channel("Failed to respond to Api::GetTimeSpan"))?;
+            }
+            Api::GrabByTime((window, tx_response)) => {
+                tx_response
+                    .send(state.handle_grab_by_time(window))
+                    .map_err(|_| NativeError::channel("Failed to respond to Api::GrabByTime"))?;
+            }
+            Api::SetTimeCorrection((source_id, correction, tx_response)) => {
+                tx_response
+                    .send(state.handle_set_time_correction(source_id, correction))
+                    .map_err(|_| {
+                        NativeError::channel("Failed to respond to Api::SetTimeCorrection")
+                    })?;
+            }
+            Api::FitTimeCorrection((source_id, anchors, tx_response)) => {
+                tx_response
+                    .send(state.handle_fit_time_correction(source_id, &anchors))
+                    .map_err(|_| {
+                        NativeError::channel("Failed to respond to Api::FitTimeCorrection")
+                    })?;
+            }
+            Api::GetTimeCorrections(tx_response) => {
+                tx_response
+                    .send(state.session_file.sources.corrections_by_alias())
+                    .map_err(|_| {
+                        NativeError::channel("Failed to respond to Api::GetTimeCorrections")
+                    })?;
+            }
             Api::GetScaledMap((len, range, tx_response)) => {
                 tx_response
                     .send(state.search_map.scaled(len, range))
@@ -759,6 +903,13 @@ pub async fn run(
                     error!("Fail to process attachment {at_name:?}; error: {err:?}");
                 }
             }
+            Api::AddTimestamps((from, rows)) => {
+                if let Err(err) =
+                    state.handle_add_timestamps(from, rows, state_cancellation_token.clone())
+                {
+                    error!("Fail to index timestamps; error: {err:?}");
+                }
+            }
             Api::GetAttachments(tx_response) => {
                 tx_response.send(state.attachments.get()).map_err(|_| {
                     NativeError::channel("Failed to respond to Api::GetAttachments")
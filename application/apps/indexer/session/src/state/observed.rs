@@ -1,4 +1,11 @@
-use sources::factory::{FileFormat, ObserveOptions, ObserveOrigin, ParserType};
+use parsers::timestamp::TimeCorrection;
+use sources::{
+    factory::{
+        FileFormat, ObserveOptions, ObserveOrigin, ParserType, TCPTransportMode, Transport,
+        UnixSocketMode,
+    },
+    recorder::RecordingFormat,
+};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone)]
@@ -18,35 +25,109 @@ impl Observed {
     pub fn is_file_based_export_possible(&self) -> bool {
         let mut possibility = true;
         self.executed.iter().for_each(|opt| {
-            if matches!(opt.origin, ObserveOrigin::Stream(..)) {
+            let exportable = match opt.origin {
+                // Streams can be exported from their recording or the replayed file only
+                ObserveOrigin::Stream(_, Transport::Replay(_)) => true,
+                ObserveOrigin::Stream(_, ref transport) => {
+                    opt.recording.is_some() && recording_matches_rows(transport)
+                }
+                ObserveOrigin::Archive(..) | ObserveOrigin::Directory(..) => false,
+                ObserveOrigin::File(..) | ObserveOrigin::Concat(..) => true,
+            };
+            if !exportable {
                 possibility = false;
             }
         });
         possibility
     }
 
-    pub fn get_files(&self) -> Vec<(ParserType, FileFormat, PathBuf)> {
-        let mut files: Vec<(ParserType, FileFormat, PathBuf)> = vec![];
+    /// Time correction of the source with `alias` defined by the options of the latest
+    /// observe, which defines a correction for it
+    pub fn correction(&self, alias: &str) -> Option<TimeCorrection> {
+        let uuid = alias.split_once(':').map(|(uuid, _)| uuid);
+        self.executed.iter().rev().find_map(|opt| {
+            opt.corrections
+                .get(alias)
+                .or_else(|| uuid.and_then(|uuid| opt.corrections.get(uuid)))
+                .cloned()
+        })
+    }
+
+    /// Stores the time correction of the source with `alias` in the options of the latest
+    /// observe, so it overrides corrections defined by uuid; `None` removes the correction
+    pub fn set_correction(&mut self, alias: &str, correction: Option<TimeCorrection>) {
+        self.executed.iter_mut().for_each(|opt| {
+            opt.corrections.remove(alias);
+        });
+        if correction.is_none() && self.correction(alias).is_none() {
+            return;
+        }
+        if let Some(opt) = self.executed.last_mut() {
+            opt.corrections
+                .insert(alias.to_owned(), correction.unwrap_or_default());
+        }
+    }
+
+    /// Files of the observed sources with the alias of their source
+    pub fn get_files(&self) -> Vec<(String, ParserType, FileFormat, PathBuf)> {
+        let mut files: Vec<(String, ParserType, FileFormat, PathBuf)> = vec![];
         self.executed.iter().for_each(|opt| match &opt.origin {
-            ObserveOrigin::File(_, file_format, filename) => {
-                files.push((opt.parser.clone(), file_format.clone(), filename.clone()))
-            }
+            ObserveOrigin::File(uuid, file_format, filename) => files.push((
+                uuid.clone(),
+                opt.parser.clone(),
+                file_format.clone(),
+                filename.clone(),
+            )),
             ObserveOrigin::Concat(list) => {
                 files.append(
                     &mut list
                         .iter()
-                        .map(|(_, file_format, filename)| {
-                            (opt.parser.clone(), file_format.clone(), filename.clone())
+                        .map(|file| {
+                            (
+                                file.uuid.clone(),
+                                file.parser(&opt.parser).clone(),
+                                file.format.clone(),
+                                file.path.clone(),
+                            )
                         })
-                        .collect::<Vec<(ParserType, FileFormat, PathBuf)>>(),
+                        .collect::<Vec<(String, ParserType, FileFormat, PathBuf)>>(),
                 );
             }
+            ObserveOrigin::Stream(uuid, Transport::Replay(settings)) => files.push((
+                uuid.clone(),
+                opt.parser.clone(),
+                settings.format.clone(),
+                settings.path.clone(),
+            )),
+            ObserveOrigin::Stream(uuid, transport) => {
+                if let Some(recording) = opt.recording.as_ref() {
+                    let (parser, file_format) =
+                        RecordingFormat::of_stream(transport, &opt.parser).reopen(&opt.parser);
+                    files.push((uuid.clone(), parser, file_format, recording.clone()));
+                }
+            }
             _ => {}
         });
         files
     }
 }
 
+/// Rows of a stream match the messages of its recording, as long as the session has no
+/// rows beside the received messages (markers of reconnects) and all messages come from a
+/// single peer.
+fn recording_matches_rows(transport: &Transport) -> bool {
+    match transport {
+        Transport::TCP(settings) => {
+            settings.reconnect.is_none() && settings.mode == TCPTransportMode::Connect
+        }
+        Transport::Unix(settings) => {
+            settings.reconnect.is_none() && settings.mode == UnixSocketMode::Connect
+        }
+        Transport::Serial(settings) => settings.reconnect.is_none(),
+        _ => true,
+    }
+}
+
 impl Default for Observed {
     fn default() -> Self {
         Self::new()
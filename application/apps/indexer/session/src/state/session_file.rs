@@ -1,18 +1,22 @@
-use super::source_ids::SourceIDs;
+use super::{source_ids::SourceIDs, timestamps::Timestamps, SessionStateAPI};
 use crate::{
     events::{NativeError, NativeErrorKind},
     paths,
     progress::Severity,
 };
-use log::debug;
+use log::{debug, error, warn};
+use parsers::{dlt::fmt::shift_datetime_column, timestamp::TimeCorrection};
 use processor::{
-    grabber::{Grabber, LineRange},
+    grabber::{GrabMetadata, Grabber, LineRange},
     text_source::TextFileSource,
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    cell::Cell,
+    collections::VecDeque,
     fs::File,
-    io::{BufWriter, Write},
+    io::{self, BufWriter, Read, Write},
+    ops::{Range, RangeInclusive},
     path::PathBuf,
     time::Instant,
 };
@@ -21,6 +25,8 @@ use uuid::Uuid;
 
 pub const FLUSH_DATA_IN_MS: u128 = 500;
 pub const SESSION_FILE_EXTENSION: &str = "session";
+/// Max count of rows read at once to index their timestamps
+const TIMESTAMPS_INDEXING_ROWS: u64 = 10_000;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GrabbedElement {
@@ -32,6 +38,30 @@ pub struct GrabbedElement {
     pub pos: usize,
     #[serde(rename = "n")]
     pub nature: u8,
+    #[serde(rename = "d", default, skip_serializing_if = "Option::is_none")]
+    pub deltas: Option<TimeDeltas>,
+}
+
+/// Time deltas of a grabbed row in milliseconds. A delta is `None` if the row or the row it's
+/// compared with has no timestamp.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeDeltas {
+    /// Delta to the previous row of the session
+    #[serde(rename = "p")]
+    pub previous: Option<i64>,
+    /// Delta to the previous row of the same source
+    #[serde(rename = "s")]
+    pub source: Option<i64>,
+    /// Delta to the reference row
+    #[serde(rename = "r")]
+    pub reference: Option<i64>,
+}
+
+/// Requests time deltas of grabbed rows
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeltasRequest {
+    /// Row to compute the deltas to (e.g. a bookmark)
+    pub reference: Option<u64>,
 }
 
 impl GrabbedElement {
@@ -40,6 +70,52 @@ impl GrabbedElement {
     }
 }
 
+/// Rows of the session file, which have to be read to index their timestamps. Reading is done
+/// outside of the state, so the state isn't blocked by big (linked) files.
+#[derive(Debug)]
+pub struct TimestampsIndexing {
+    filename: PathBuf,
+    metadata: GrabMetadata,
+    rows: Range<u64>,
+}
+
+impl TimestampsIndexing {
+    /// Reads the rows in chunks and passes them to the state
+    pub fn run(self, state: SessionStateAPI, cancel: CancellationToken) {
+        let mut grabber = match Grabber::lazy(TextFileSource::new(&self.filename)) {
+            Ok(grabber) => grabber,
+            Err(err) => {
+                error!("Fail to read rows to index timestamps: {err}");
+                return;
+            }
+        };
+        if let Err(err) = grabber.inject_metadata(self.metadata) {
+            error!("Fail to read rows to index timestamps: {err}");
+            return;
+        }
+        let mut from = self.rows.start;
+        while from < self.rows.end && !cancel.is_cancelled() {
+            let to = (from + TIMESTAMPS_INDEXING_ROWS).min(self.rows.end);
+            let rows = grabber
+                .grab_content(&LineRange::from(from..=to - 1))
+                .map_err(|e| NativeError {
+                    severity: Severity::ERROR,
+                    kind: NativeErrorKind::Grabber,
+                    message: Some(format!("{e}")),
+                });
+            let failed = rows.is_err();
+            if let Err(err) = state.add_timestamps(from, rows) {
+                warn!("Fail to pass rows to index timestamps: {err:?}");
+                return;
+            }
+            if failed {
+                return;
+            }
+            from = to;
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SessionFileState {
     Changed,
@@ -74,6 +150,11 @@ pub struct SessionFile {
     pub writer: Option<BufWriter<File>>,
     pub last_message_timestamp: Instant,
     pub sources: SourceIDs,
+    pub timestamps: Timestamps,
+    /// Count of rows written by sources since the last update, in order of writing
+    written: VecDeque<(u16, u64)>,
+    /// End of the rows, which are read outside of the state to index their timestamps
+    indexing: Option<u64>,
 }
 
 impl SessionFile {
@@ -84,6 +165,9 @@ impl SessionFile {
             writer: None,
             last_message_timestamp: Instant::now(),
             sources: SourceIDs::new(),
+            timestamps: Timestamps::new(),
+            written: VecDeque::new(),
+            indexing: None,
         }
     }
 
@@ -93,20 +177,8 @@ impl SessionFile {
                 self.filename = Some(SessionFileOrigin::Linked(filename.clone()));
                 filename
             } else {
-                let streams = paths::get_streams_dir()?;
-                let filename = streams.join(format!("{}.{SESSION_FILE_EXTENSION}", Uuid::new_v4()));
-                debug!("Session file setup: {}", filename.to_string_lossy());
-                self.writer = Some(BufWriter::new(File::create(&filename).map_err(|e| {
-                    NativeError {
-                        severity: Severity::ERROR,
-                        kind: NativeErrorKind::Io,
-                        message: Some(format!(
-                            "Fail to create session writer for {}: {}",
-                            filename.to_string_lossy(),
-                            e
-                        )),
-                    }
-                })?));
+                let (filename, writer) = Self::create()?;
+                self.writer = Some(writer);
                 self.filename = Some(SessionFileOrigin::Generated(filename.clone()));
                 filename
             };
@@ -117,6 +189,66 @@ impl SessionFile {
         }
     }
 
+    /// Creates a new generated session file
+    fn create() -> Result<(PathBuf, BufWriter<File>), NativeError> {
+        let streams = paths::get_streams_dir()?;
+        let filename = streams.join(format!("{}.{SESSION_FILE_EXTENSION}", Uuid::new_v4()));
+        debug!("Session file setup: {}", filename.to_string_lossy());
+        let writer = BufWriter::new(File::create(&filename).map_err(|e| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Io,
+            message: Some(format!(
+                "Fail to create session writer for {}: {}",
+                filename.to_string_lossy(),
+                e
+            )),
+        })?);
+        Ok((filename, writer))
+    }
+
+    /// Turns a session linked to a text file into a generated one, so new content can be
+    /// written after the linked file was rotated. The already indexed content of the linked
+    /// file is copied from `content`; reading of `content` stops right after it. Returns the
+    /// count of copied bytes.
+    pub fn detach(&mut self, content: File) -> Result<u64, NativeError> {
+        let Some(SessionFileOrigin::Linked(linked)) = self.filename.clone() else {
+            return Err(NativeError {
+                severity: Severity::ERROR,
+                kind: NativeErrorKind::Grabber,
+                message: Some(String::from("Session isn't linked to a file")),
+            });
+        };
+        let indexed = self.read_bytes();
+        let (filename, mut writer) = Self::create()?;
+        let copied = io::copy(&mut (&content).take(indexed), &mut writer)?;
+        if copied < indexed {
+            drop(writer);
+            if let Err(err) = std::fs::remove_file(&filename) {
+                warn!("Fail to remove {}: {err}", filename.display());
+            }
+            return Err(NativeError {
+                severity: Severity::WARNING,
+                kind: NativeErrorKind::Io,
+                message: Some(format!(
+                    "Content of {} isn't available anymore after rotation; tailing is stopped",
+                    linked.display()
+                )),
+            });
+        }
+        writer.flush()?;
+        let mut grabber = Grabber::lazy(TextFileSource::new(&filename))?;
+        grabber.update_from_file(None)?;
+        debug!(
+            "Session file {} is detached into {}",
+            linked.display(),
+            filename.display()
+        );
+        self.writer = Some(writer);
+        self.filename = Some(SessionFileOrigin::Generated(filename));
+        self.grabber = Some(Box::new(grabber));
+        Ok(copied)
+    }
+
     #[allow(clippy::len_without_is_empty)]
     pub fn len(&mut self) -> u64 {
         if let Some(ref grabber) = self.grabber {
@@ -147,17 +279,26 @@ impl SessionFile {
         }
     }
 
+    /// Writes `msg` into the session file; `timestamp` is the timestamp of the rows of
+    /// `msg`, if it's known
     pub fn write(
         &mut self,
         source_id: u16,
         state_cancellation_token: CancellationToken,
         msg: String,
+        timestamp: Option<i64>,
     ) -> Result<SessionFileState, NativeError> {
-        if !self.sources.is_source_same(source_id) {
-            self.flush(state_cancellation_token.clone(), false)?;
-        }
         if let Some(writer) = &mut self.writer {
             writer.write_all(msg.as_bytes())?;
+            let rows = msg.bytes().filter(|b| *b == b'\n').count();
+            self.timestamps.add_written(&msg, timestamp);
+            // Rows are mapped to sources on the next update, so switching of sources
+            // doesn't require a flush
+            match self.written.back_mut() {
+                Some((id, written)) if *id == source_id => *written += rows as u64,
+                _ if rows > 0 => self.written.push_back((source_id, rows as u64)),
+                _ => {}
+            }
             self.sources.source_update(source_id);
             if self.last_message_timestamp.elapsed().as_millis() > FLUSH_DATA_IN_MS {
                 self.flush(state_cancellation_token, true)
@@ -212,7 +353,13 @@ impl SessionFile {
         })?);
         let prev = grabber.log_entry_count().unwrap_or(0) as u64;
         if let Some(range) = grabber.update_from_file(Some(state_cancellation_token))? {
-            self.sources.add_range(range, source_id);
+            for (range, source_id) in split_by_sources(range, &mut self.written, source_id) {
+                if let Some(correction) = self.sources.corrections.get(&source_id) {
+                    self.timestamps
+                        .add_correction(range.clone(), correction.clone());
+                }
+                self.sources.add_range(range, source_id);
+            }
         }
         let current = grabber.log_entry_count().unwrap_or(0) as u64;
         Ok(if prev != current {
@@ -222,6 +369,67 @@ impl SessionFile {
         })
     }
 
+    /// Adds timestamps of new rows to the index. Returns the rows, which have to be read
+    /// from the session file to index them; their content is passed back with
+    /// `add_timestamps`. New rows aren't indexed until reading of such rows is done.
+    pub fn index_timestamps(&mut self) -> Result<Option<TimestampsIndexing>, NativeError> {
+        if self.indexing.is_some() {
+            return Ok(None);
+        }
+        let rows = self.len();
+        while self.timestamps.len() < rows {
+            let from = self.timestamps.len();
+            let count = (rows - from).min(TIMESTAMPS_INDEXING_ROWS);
+            if !self.timestamps.needs_content(count) {
+                self.timestamps.append(count, None);
+                continue;
+            }
+            let grabber = self.grabber.as_ref().ok_or(NativeError {
+                severity: Severity::ERROR,
+                kind: NativeErrorKind::Grabber,
+                message: Some(String::from("Grabber isn't inited")),
+            })?;
+            let Some(metadata) = grabber.get_metadata().cloned() else {
+                return Ok(None);
+            };
+            self.indexing = Some(rows);
+            return Ok(Some(TimestampsIndexing {
+                filename: grabber.associated_file(),
+                metadata,
+                rows: from..rows,
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Adds timestamps of rows read by `TimestampsIndexing`; `from` is the first of the
+    /// rows. Returns `true` if reading of rows is done.
+    pub fn add_timestamps(
+        &mut self,
+        from: u64,
+        rows: Result<Vec<String>, NativeError>,
+    ) -> Result<bool, NativeError> {
+        let Some(end) = self.indexing else {
+            return Ok(false);
+        };
+        let rows = match rows {
+            Ok(rows) if from == self.timestamps.len() => rows,
+            Ok(_) => {
+                warn!("Rows from {from} don't follow the indexed timestamps; ignored");
+                return Ok(false);
+            }
+            Err(err) => {
+                self.indexing = None;
+                return Err(err);
+            }
+        };
+        self.timestamps.append(rows.len() as u64, Some(rows));
+        if self.timestamps.len() >= end {
+            self.indexing = None;
+        }
+        Ok(self.indexing.is_none())
+    }
+
     pub fn grab(&self, range: &LineRange) -> Result<Vec<GrabbedElement>, NativeError> {
         let grabber = &mut (self.grabber.as_ref().ok_or(NativeError {
             severity: Severity::ERROR,
@@ -245,11 +453,106 @@ impl SessionFile {
                     content,
                     pos,
                     nature: 0,
+                    deltas: None,
                 }
             })
             .collect())
     }
 
+    /// Sets time deltas of `elements` as defined by `request`
+    pub fn set_deltas(&self, elements: &mut [GrabbedElement], request: &DeltasRequest) {
+        let reference = request.reference.and_then(|row| self.timestamps.get(row));
+        for element in elements.iter_mut() {
+            let row = element.pos as u64;
+            let timestamp = self.timestamps.get(row);
+            let delta_to = |other: Option<i64>| timestamp.zip(other).map(|(ts, other)| ts - other);
+            element.deltas = Some(TimeDeltas {
+                previous: delta_to(row.checked_sub(1).and_then(|row| self.timestamps.get(row))),
+                source: delta_to(
+                    self.sources
+                        .previous_row(row, element.source_id)
+                        .and_then(|row| self.timestamps.get(row)),
+                ),
+                reference: delta_to(reference),
+            });
+        }
+    }
+
+    /// Sets the time correction of the source; `None` removes the correction
+    pub fn set_time_correction(
+        &mut self,
+        source_id: u16,
+        correction: Option<TimeCorrection>,
+    ) -> Result<(), NativeError> {
+        if !self.sources.sources.contains_key(&source_id) {
+            return Err(NativeError {
+                severity: Severity::ERROR,
+                kind: NativeErrorKind::Configuration,
+                message: Some(format!("Source {source_id} doesn't exist")),
+            });
+        }
+        match correction.filter(|correction| !correction.is_identity()) {
+            Some(correction) => self.sources.corrections.insert(source_id, correction),
+            None => self.sources.corrections.remove(&source_id),
+        };
+        self.timestamps
+            .set_corrections(self.sources.corrected_ranges());
+        Ok(())
+    }
+
+    /// Fits the time correction of the source to `anchors` and sets it. An anchor is a pair
+    /// `(row, reference)` of a row of the source and a row of another source, which were
+    /// logged at the same moment.
+    pub fn fit_time_correction(
+        &mut self,
+        source_id: u16,
+        anchors: &[(u64, u64)],
+    ) -> Result<TimeCorrection, NativeError> {
+        let invalid = |message: String| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Configuration,
+            message: Some(message),
+        };
+        let mut pairs = Vec::with_capacity(anchors.len());
+        for (row, reference) in anchors.iter() {
+            if self.sources.get_mapped_ranges(&(*row..=*row)).source(*row) != Some(source_id) {
+                return Err(invalid(format!(
+                    "Row {row} doesn't belong to source {source_id}"
+                )));
+            }
+            let timestamp = |ts: Option<i64>, row: &u64| {
+                ts.ok_or_else(|| invalid(format!("Row {row} has no timestamp")))
+            };
+            pairs.push((
+                timestamp(self.timestamps.original(*row), row)?,
+                timestamp(self.timestamps.get(*reference), reference)?,
+            ));
+        }
+        let correction = TimeCorrection::from_anchors(&pairs)
+            .ok_or_else(|| invalid(String::from("No anchors are defined")))?;
+        self.set_time_correction(source_id, Some(correction.clone()))?;
+        Ok(correction)
+    }
+
+    /// Content of the row with its timestamp moved to the corrected time. Only timestamps
+    /// of the detected text format are rewritten.
+    fn corrected_content(&self, row: u64, content: String) -> String {
+        let (Some(original), Some(corrected)) =
+            (self.timestamps.original(row), self.timestamps.get(row))
+        else {
+            return content;
+        };
+        if original == corrected {
+            return content;
+        }
+        match self.timestamps.format() {
+            Some(format) => format.shift(&content, None, corrected - original),
+            // Timestamps were given with the rows (e.g. DLT storage headers)
+            None => shift_datetime_column(&content, corrected - original),
+        }
+        .unwrap_or(content)
+    }
+
     pub fn filename(&self) -> Result<PathBuf, NativeError> {
         if let Some(origin) = self.filename.as_ref() {
             Ok(origin.filename())
@@ -267,6 +570,9 @@ impl SessionFile {
     /// `modifier` can be used to modify content before writing; for example it can be used to
     /// exclude some content during exporting (write selected columns only)
     ///
+    /// Timestamps of rows of sources with time correction are written corrected: the
+    /// timestamp of the detected format for text rows, the date-time column for DLT rows.
+    ///
     /// # Arguments
     ///
     /// * `writer` - This is where the copied content will be written.
@@ -293,13 +599,34 @@ impl SessionFile {
             kind: NativeErrorKind::Grabber,
             message: Some(String::from("Grabber isn't inited")),
         })?);
-        grabber
-            .copy_content(writer, line_range, modifier)
-            .map_err(|e| NativeError {
-                severity: Severity::ERROR,
-                kind: NativeErrorKind::Grabber,
-                message: Some(format!("{e}")),
-            })
+        let range = &line_range.range;
+        let corrected = self
+            .sources
+            .corrected_ranges()
+            .iter()
+            .any(|(rows, _)| rows.start() <= range.end() && rows.end() >= range.start());
+        let copied = if !corrected {
+            grabber.copy_content(writer, line_range, modifier)
+        } else {
+            let row = Cell::new(*line_range.range.start());
+            grabber.copy_content(
+                writer,
+                line_range,
+                Some(|content: String| {
+                    let content = self.corrected_content(row.get(), content);
+                    row.set(row.get() + 1);
+                    match modifier.as_ref() {
+                        Some(modifier) => modifier(content),
+                        None => content,
+                    }
+                }),
+            )
+        };
+        copied.map_err(|e| NativeError {
+            severity: Severity::ERROR,
+            kind: NativeErrorKind::Grabber,
+            message: Some(format!("{e}")),
+        })
     }
 
     /// Cleans up the temporary generated files and for the session on its attachments if exist
@@ -355,8 +682,136 @@ impl SessionFile {
     }
 }
 
+/// Splits new rows of the session file by the sources which wrote them. Rows which weren't
+/// written by sources (e.g. rows of a linked file) belong to `source_id`.
+fn split_by_sources(
+    range: RangeInclusive<u64>,
+    written: &mut VecDeque<(u16, u64)>,
+    source_id: u16,
+) -> Vec<(RangeInclusive<u64>, u16)> {
+    let mut ranges = Vec::new();
+    let mut start = *range.start();
+    while start <= *range.end() {
+        let Some((id, rows)) = written.front_mut() else {
+            ranges.push((start..=*range.end(), source_id));
+            break;
+        };
+        let end = (start + *rows - 1).min(*range.end());
+        ranges.push((start..=end, *id));
+        *rows -= end - start + 1;
+        if *rows == 0 {
+            written.pop_front();
+        }
+        start = end + 1;
+    }
+    ranges
+}
+
 impl Default for SessionFile {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn element(pos: usize, source_id: u16) -> GrabbedElement {
+        GrabbedElement {
+            source_id,
+            content: String::new(),
+            pos,
+            nature: 0,
+            deltas: None,
+        }
+    }
+
+    #[test]
+    fn test_split_by_sources() {
+        let mut written = VecDeque::from([(0, 2), (1, 1), (0, 3)]);
+        assert_eq!(
+            split_by_sources(10..=13, &mut written, 0),
+            vec![(10..=11, 0), (12..=12, 1), (13..=13, 0)]
+        );
+        // Rest of the written rows is mapped on the next update
+        assert_eq!(written, VecDeque::from([(0, 2)]));
+        assert_eq!(
+            split_by_sources(14..=17, &mut written, 2),
+            vec![(14..=15, 0), (16..=17, 2)]
+        );
+        assert!(written.is_empty());
+    }
+
+    #[test]
+    fn test_deltas() {
+        let mut session_file = SessionFile::new();
+        session_file.sources.add_range(0..=1, 0);
+        session_file.sources.add_range(2..=2, 1);
+        session_file.sources.add_range(3..=3, 0);
+        for ts in [Some(1_000), Some(1_500), Some(1_600), None] {
+            session_file.timestamps.add_written("row\n", ts);
+        }
+        session_file.timestamps.append(4, None);
+        let mut elements = vec![element(0, 0), element(2, 1), element(3, 0)];
+        session_file.set_deltas(&mut elements, &DeltasRequest { reference: Some(1) });
+        let deltas: Vec<TimeDeltas> = elements.into_iter().filter_map(|el| el.deltas).collect();
+        assert_eq!(
+            deltas,
+            vec![
+                TimeDeltas {
+                    previous: None,
+                    source: None,
+                    reference: Some(-500),
+                },
+                TimeDeltas {
+                    previous: Some(100),
+                    source: None,
+                    reference: Some(100),
+                },
+                // Row without timestamp gets the timestamp of the previous row
+                TimeDeltas {
+                    previous: Some(0),
+                    source: Some(100),
+                    reference: Some(100),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_timestamps_of_linked_file() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("linked.log");
+        std::fs::write(&path, "row 1\nrow 2\nrow 3\n").unwrap();
+        let mut session_file = SessionFile::new();
+        session_file.init(Some(path.clone())).unwrap();
+        session_file.update(0, CancellationToken::new()).unwrap();
+        let indexing = session_file
+            .index_timestamps()
+            .unwrap()
+            .expect("Rows of linked file have to be read");
+        assert_eq!(indexing.rows, 0..3);
+        // Rows are read once at a time
+        assert!(session_file.index_timestamps().unwrap().is_none());
+        let rows = |range: RangeInclusive<u64>| {
+            session_file
+                .grab(&LineRange::from(range))
+                .unwrap()
+                .into_iter()
+                .map(|element| element.content)
+                .collect::<Vec<_>>()
+        };
+        let (first, last) = (rows(0..=1), rows(2..=2));
+        assert!(!session_file.add_timestamps(0, Ok(first)).unwrap());
+        assert_eq!(session_file.timestamps.len(), 2);
+        assert!(session_file.add_timestamps(2, Ok(last)).unwrap());
+        assert_eq!(session_file.timestamps.len(), 3);
+        assert!(session_file.index_timestamps().unwrap().is_none());
+        // Appended rows are read from the end of indexed rows
+        std::fs::write(&path, "row 1\nrow 2\nrow 3\nrow 4\n").unwrap();
+        session_file.update(0, CancellationToken::new()).unwrap();
+        let indexing = session_file.index_timestamps().unwrap().unwrap();
+        assert_eq!(indexing.rows, 3..4);
+    }
+}
@@ -1,3 +1,4 @@
+use parsers::timestamp::TimeCorrection;
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, ops::RangeInclusive};
 pub struct MappedRanges<'a> {
@@ -30,7 +31,11 @@ pub struct SourceDefinition {
 pub struct SourceIDs {
     pub sources: HashMap<u16, String>,
     pub map: Vec<(RangeInclusive<u64>, u16)>,
+    /// Ranges of rows of each source, ordered by the first row
+    by_source: HashMap<u16, Vec<RangeInclusive<u64>>>,
     pub recent: Option<u16>,
+    /// Time corrections of sources
+    pub corrections: HashMap<u16, TimeCorrection>,
 }
 
 impl SourceIDs {
@@ -38,7 +43,9 @@ impl SourceIDs {
         Self {
             sources: HashMap::new(),
             map: vec![],
+            by_source: HashMap::new(),
             recent: None,
+            corrections: HashMap::new(),
         }
     }
 
@@ -54,14 +61,6 @@ impl SourceIDs {
             .find_map(|(key, val)| if val == &alias { Some(*key) } else { None })
     }
 
-    pub fn is_source_same(&self, source_id: u16) -> bool {
-        if let Some(id) = self.recent {
-            id == source_id
-        } else {
-            true
-        }
-    }
-
     pub fn source_update(&mut self, source_id: u16) {
         let changed = if let Some(id) = self.recent {
             id != source_id
@@ -91,10 +90,45 @@ impl SourceIDs {
             .collect::<Vec<SourceDefinition>>()
     }
 
+    /// Ranges of rows of sources with time correction
+    pub fn corrected_ranges(&self) -> Vec<(RangeInclusive<u64>, TimeCorrection)> {
+        self.map
+            .iter()
+            .filter_map(|(range, source_id)| {
+                self.corrections
+                    .get(source_id)
+                    .map(|correction| (range.clone(), correction.clone()))
+            })
+            .collect()
+    }
+
+    /// Time corrections by alias of the source
+    pub fn corrections_by_alias(&self) -> HashMap<String, TimeCorrection> {
+        self.corrections
+            .iter()
+            .filter_map(|(source_id, correction)| {
+                self.sources
+                    .get(source_id)
+                    .map(|alias| (alias.clone(), correction.clone()))
+            })
+            .collect()
+    }
+
     pub fn add_range(&mut self, range: RangeInclusive<u64>, source_id: u16) {
+        self.by_source
+            .entry(source_id)
+            .or_default()
+            .push(range.clone());
         self.map.push((range, source_id));
     }
 
+    /// Returns the last row before `row`, which belongs to `source_id`
+    pub fn previous_row(&self, row: u64, source_id: u16) -> Option<u64> {
+        let ranges = self.by_source.get(&source_id)?;
+        let next = ranges.partition_point(|range| *range.start() < row);
+        next.checked_sub(1).map(|n| (*ranges[n].end()).min(row - 1))
+    }
+
     pub fn get_mapped_ranges(&self, requested: &RangeInclusive<u64>) -> MappedRanges {
         MappedRanges::new(
             self.map
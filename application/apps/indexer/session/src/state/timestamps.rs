@@ -0,0 +1,463 @@
+use parsers::timestamp::{detect_format, TimeCorrection, TimestampFormat, DETECTION_SAMPLE_LINES};
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+    ops::RangeInclusive,
+};
+
+/// Count of rows in a block of the index
+const BLOCK_SIZE: u64 = 1024;
+/// Offset of a row without timestamp
+const NO_TIMESTAMP: i32 = i32::MIN;
+/// Offset of a row, which timestamp is too far from the base of the block; such timestamps
+/// are stored separately
+const WIDE_TIMESTAMP: i32 = i32::MIN + 1;
+
+/// Rows of the index are grouped into blocks. Timestamps of rows are stored as offsets to
+/// the first timestamp of the block, so a row takes 4 bytes.
+#[derive(Debug, Default)]
+struct Block {
+    base: Option<i64>,
+    offsets: Vec<i32>,
+    /// Lowest and highest corrected timestamp of the block
+    bounds: Option<(i64, i64)>,
+}
+
+impl Block {
+    fn extend_bounds(&mut self, timestamp: i64) {
+        self.bounds = Some(match self.bounds {
+            Some((min, max)) => (min.min(timestamp), max.max(timestamp)),
+            None => (timestamp, timestamp),
+        });
+    }
+}
+
+#[derive(Debug)]
+enum Detection {
+    /// Not enough rows to detect the format of text rows yet. Keeps the given timestamps and
+    /// the content of rows indexed so far; these rows are indexed again as soon as the
+    /// format is detected.
+    Pending(Vec<(Option<i64>, Option<String>)>),
+    Done(Option<TimestampFormat>),
+}
+
+/// Row written into the session file, which isn't indexed yet
+#[derive(Debug)]
+enum WrittenRow {
+    /// Timestamp given with the row or extracted from its content
+    Timestamp(Option<i64>),
+    /// Content of a row without given timestamp, which was written before the format was
+    /// detected
+    Content(String),
+}
+
+/// Timestamp index of the rows of the session file. Timestamps are in milliseconds since
+/// the Unix epoch.
+///
+/// Timestamps of rows are given with the written messages (e.g. DLT storage header or
+/// pcap timestamps). Rows without given timestamp (text) are parsed with the detected
+/// timestamp format; rows without timestamp at all get the timestamp of the previous row.
+///
+/// The index keeps the original timestamps; the time corrections of ranges of rows are
+/// applied on reading.
+#[derive(Debug)]
+pub struct Timestamps {
+    blocks: Vec<Block>,
+    wide: HashMap<u64, i64>,
+    len: u64,
+    /// Corrections of ranges of rows, ordered by the first row
+    corrections: Vec<(RangeInclusive<u64>, TimeCorrection)>,
+    /// Rows written into the session file, which aren't indexed yet
+    written: VecDeque<WrittenRow>,
+    detection: Detection,
+    last: Option<i64>,
+}
+
+impl Timestamps {
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            wide: HashMap::new(),
+            len: 0,
+            corrections: Vec::new(),
+            written: VecDeque::new(),
+            detection: Detection::Pending(Vec::new()),
+            last: None,
+        }
+    }
+
+    /// Count of indexed rows
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Registers rows of `content` written into the session file. `timestamp` is given for
+    /// all rows; otherwise timestamps are extracted from the rows with the detected format.
+    pub fn add_written(&mut self, content: &str, timestamp: Option<i64>) {
+        let rows = content.bytes().filter(|b| *b == b'\n').count();
+        for row in content.split('\n').take(rows) {
+            let written = match (timestamp, &self.detection) {
+                (Some(_), _) | (None, Detection::Done(None)) => WrittenRow::Timestamp(timestamp),
+                (None, Detection::Done(Some(format))) => {
+                    WrittenRow::Timestamp(format.extract(row, None))
+                }
+                (None, Detection::Pending(_)) => WrittenRow::Content(row.to_owned()),
+            };
+            self.written.push_back(written);
+        }
+    }
+
+    /// Returns `true` if the content of the next `rows` rows is needed to index them, because
+    /// they weren't written into the session file (e.g. rows of a linked file)
+    pub fn needs_content(&self, rows: u64) -> bool {
+        !matches!(self.detection, Detection::Done(None)) && (self.written.len() as u64) < rows
+    }
+
+    /// Adds the next `rows` rows to the index. `content` of the rows is used for rows, which
+    /// weren't written into the session file.
+    pub fn append(&mut self, rows: u64, content: Option<Vec<String>>) {
+        let mut content = content.unwrap_or_default();
+        let rows: Vec<(Option<i64>, Option<String>)> = (0..rows as usize)
+            .map(|i| match self.written.pop_front() {
+                Some(WrittenRow::Timestamp(timestamp)) => (timestamp, None),
+                Some(WrittenRow::Content(row)) => (None, Some(row)),
+                None => (None, content.get_mut(i).map(mem::take)),
+            })
+            .collect();
+        let rows = match &mut self.detection {
+            Detection::Done(_) => rows,
+            Detection::Pending(indexed) => {
+                let from = indexed.len();
+                indexed.extend(rows);
+                let untimed = indexed
+                    .iter()
+                    .filter(|(timestamp, _)| timestamp.is_none())
+                    .filter_map(|(_, row)| row.as_deref())
+                    .take(DETECTION_SAMPLE_LINES);
+                let detected = detect_format(untimed)
+                    .and_then(|detected| TimestampFormat::new(&detected.format).ok());
+                if detected.is_none() && indexed.len() < DETECTION_SAMPLE_LINES {
+                    indexed[from..]
+                        .iter()
+                        .map(|(timestamp, _)| (*timestamp, None))
+                        .collect()
+                } else {
+                    // Rows indexed before the detection are indexed again with the format
+                    let indexed = mem::take(indexed);
+                    self.detection = Detection::Done(detected);
+                    self.clear();
+                    indexed
+                }
+            }
+        };
+        for (timestamp, row) in rows {
+            let timestamp = timestamp
+                .or_else(|| {
+                    self.format()
+                        .zip(row.as_deref())
+                        .and_then(|(format, row)| format.extract(row, None))
+                })
+                .or(self.last);
+            self.push(timestamp);
+        }
+    }
+
+    /// Drops all indexed rows; corrections are kept
+    fn clear(&mut self) {
+        self.blocks.clear();
+        self.wide.clear();
+        self.len = 0;
+        self.last = None;
+    }
+
+    fn push(&mut self, timestamp: Option<i64>) {
+        if self.len.is_multiple_of(BLOCK_SIZE) {
+            self.blocks.push(Block::default());
+        }
+        let row = self.len;
+        self.len += 1;
+        let corrected = timestamp.map(|ts| self.correct(row, ts));
+        let Some(block) = self.blocks.last_mut() else {
+            return;
+        };
+        let (Some(timestamp), Some(corrected)) = (timestamp, corrected) else {
+            block.offsets.push(NO_TIMESTAMP);
+            return;
+        };
+        self.last = Some(timestamp);
+        let base = *block.base.get_or_insert(timestamp);
+        match i32::try_from(timestamp - base) {
+            Ok(offset) if offset != NO_TIMESTAMP && offset != WIDE_TIMESTAMP => {
+                block.offsets.push(offset);
+            }
+            _ => {
+                block.offsets.push(WIDE_TIMESTAMP);
+                self.wide.insert(row, timestamp);
+            }
+        }
+        block.extend_bounds(corrected);
+    }
+
+    /// Sets corrections of ranges of rows; ranges have to be ordered and must not overlap
+    pub fn set_corrections(&mut self, corrections: Vec<(RangeInclusive<u64>, TimeCorrection)>) {
+        self.corrections = corrections;
+        for n in 0..self.blocks.len() {
+            let first = n as u64 * BLOCK_SIZE;
+            let rows = first..first + self.blocks[n].offsets.len() as u64;
+            let corrected: Vec<i64> = rows.filter_map(|row| self.get(row)).collect();
+            let block = &mut self.blocks[n];
+            block.bounds = None;
+            corrected.into_iter().for_each(|ts| block.extend_bounds(ts));
+        }
+    }
+
+    /// Adds the correction of a range of rows, which follows all ranges with corrections and
+    /// isn't indexed yet
+    pub fn add_correction(&mut self, range: RangeInclusive<u64>, correction: TimeCorrection) {
+        self.corrections.push((range, correction));
+    }
+
+    /// Correction of the row
+    pub fn correction(&self, row: u64) -> Option<&TimeCorrection> {
+        let next = self
+            .corrections
+            .partition_point(|(range, _)| *range.start() <= row);
+        next.checked_sub(1)
+            .map(|n| &self.corrections[n])
+            .filter(|(range, _)| range.contains(&row))
+            .map(|(_, correction)| correction)
+    }
+
+    fn correct(&self, row: u64, timestamp: i64) -> i64 {
+        self.correction(row)
+            .map(|correction| correction.apply(timestamp))
+            .unwrap_or(timestamp)
+    }
+
+    /// Format of timestamps detected in the content of rows
+    pub fn format(&self) -> Option<&TimestampFormat> {
+        match &self.detection {
+            Detection::Done(format) => format.as_ref(),
+            Detection::Pending(_) => None,
+        }
+    }
+
+    /// Original timestamp of the row without correction
+    pub fn original(&self, row: u64) -> Option<i64> {
+        let block = self.blocks.get((row / BLOCK_SIZE) as usize)?;
+        match *block.offsets.get((row % BLOCK_SIZE) as usize)? {
+            NO_TIMESTAMP => None,
+            WIDE_TIMESTAMP => self.wide.get(&row).copied(),
+            offset => block.base.map(|base| base + i64::from(offset)),
+        }
+    }
+
+    /// Corrected timestamp of the row
+    pub fn get(&self, row: u64) -> Option<i64> {
+        self.original(row).map(|ts| self.correct(row, ts))
+    }
+
+    /// Rows of blocks, which may contain timestamps of `range`
+    fn candidates(&self, range: RangeInclusive<i64>) -> impl Iterator<Item = u64> + '_ {
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(move |(_, block)| {
+                block
+                    .bounds
+                    .is_some_and(|(min, max)| max >= *range.start() && min <= *range.end())
+            })
+            .flat_map(|(n, block)| {
+                let first = n as u64 * BLOCK_SIZE;
+                first..first + block.offsets.len() as u64
+            })
+    }
+
+    /// First row with a timestamp at or after `timestamp`
+    pub fn first_row_at(&self, timestamp: i64) -> Option<u64> {
+        self.candidates(timestamp..=i64::MAX)
+            .find(|row| self.get(*row).is_some_and(|ts| ts >= timestamp))
+    }
+
+    /// Lowest and highest timestamp of the rows in `range`
+    pub fn span(&self, range: &RangeInclusive<u64>) -> Option<(i64, i64)> {
+        let end = (*range.end()).min(self.len.checked_sub(1)?);
+        let mut bounds: Option<(i64, i64)> = None;
+        let mut extend = |(min, max): (i64, i64)| {
+            bounds = Some(match bounds {
+                Some((lowest, highest)) => (lowest.min(min), highest.max(max)),
+                None => (min, max),
+            });
+        };
+        let mut row = *range.start();
+        while row <= end {
+            let block = &self.blocks[(row / BLOCK_SIZE) as usize];
+            let block_end = (row / BLOCK_SIZE + 1) * BLOCK_SIZE - 1;
+            if row.is_multiple_of(BLOCK_SIZE) && block_end <= end {
+                // Whole block is in the range
+                if let Some(block_bounds) = block.bounds {
+                    extend(block_bounds);
+                }
+                row = block_end + 1;
+                continue;
+            }
+            if let Some(ts) = self.get(row) {
+                extend((ts, ts));
+            }
+            row += 1;
+        }
+        bounds
+    }
+
+    /// Ranges of rows with timestamps in `window`
+    pub fn rows_in(&self, window: RangeInclusive<i64>) -> Vec<RangeInclusive<u64>> {
+        let mut ranges: Vec<RangeInclusive<u64>> = Vec::new();
+        for row in self.candidates(window.clone()) {
+            if !self.get(row).is_some_and(|ts| window.contains(&ts)) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if *last.end() + 1 == row => *last = *last.start()..=row,
+                _ => ranges.push(row..=row),
+            }
+        }
+        ranges
+    }
+}
+
+impl Default for Timestamps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(content: &[&str]) -> Vec<String> {
+        content.iter().map(|row| row.to_string()).collect()
+    }
+
+    #[test]
+    fn test_given_timestamps() {
+        let mut timestamps = Timestamps::new();
+        timestamps.add_written("a\n", Some(1_000));
+        timestamps.add_written("b\nc\n", Some(3_000));
+        timestamps.add_written("d\n", Some(i64::from(i32::MAX) * 10));
+        timestamps.add_written("e\n", Some(2_000));
+        assert!(!timestamps.needs_content(5));
+        timestamps.append(5, None);
+        assert_eq!(timestamps.len(), 5);
+        assert_eq!(timestamps.get(0), Some(1_000));
+        assert_eq!(timestamps.get(2), Some(3_000));
+        assert_eq!(timestamps.get(3), Some(i64::from(i32::MAX) * 10));
+        assert_eq!(timestamps.first_row_at(1_500), Some(1));
+        assert_eq!(timestamps.first_row_at(i64::MAX), None);
+        assert_eq!(timestamps.span(&(0..=1)), Some((1_000, 3_000)));
+        assert_eq!(timestamps.span(&(4..=10)), Some((2_000, 2_000)));
+        assert_eq!(timestamps.rows_in(1_000..=2_000), vec![0..=0, 4..=4]);
+    }
+
+    #[test]
+    fn test_detected_timestamps() {
+        let mut timestamps = Timestamps::new();
+        let content = rows(&[
+            "header",
+            "2024-03-05 10:11:12.000 first",
+            "  continued",
+            "2024-03-05 10:11:13.000 second",
+        ]);
+        assert!(timestamps.needs_content(4));
+        timestamps.append(4, Some(content));
+        assert_eq!(timestamps.get(0), None);
+        assert_eq!(timestamps.get(1), Some(1_709_633_472_000));
+        assert_eq!(timestamps.get(2), Some(1_709_633_472_000));
+        assert_eq!(timestamps.get(3), Some(1_709_633_473_000));
+        assert_eq!(
+            timestamps.rows_in(1_709_633_472_000..=1_709_633_472_000),
+            vec![1..=2]
+        );
+    }
+
+    #[test]
+    fn test_written_timestamps() {
+        let mut timestamps = Timestamps::new();
+        timestamps.add_written("header\n2024-03-05 10:11:12.000 first\n", None);
+        assert!(!timestamps.needs_content(2));
+        // Not enough rows to detect the format
+        timestamps.append(1, None);
+        assert_eq!(timestamps.get(0), None);
+        timestamps.append(1, None);
+        assert_eq!(timestamps.get(1), Some(1_709_633_472_000));
+        timestamps.add_written("  continued\n2024-03-05 10:11:13.000 second\n", None);
+        timestamps.append(2, None);
+        assert_eq!(timestamps.get(2), Some(1_709_633_472_000));
+        assert_eq!(timestamps.get(3), Some(1_709_633_473_000));
+    }
+
+    #[test]
+    fn test_detection_over_chunks() {
+        let mut timestamps = Timestamps::new();
+        timestamps.add_written("header\n", None);
+        timestamps.add_written("dlt\n", Some(1_000));
+        timestamps.append(2, None);
+        assert!(timestamps.format().is_none());
+        assert_eq!(timestamps.get(0), None);
+        assert_eq!(timestamps.get(1), Some(1_000));
+        // Rows indexed while the detection is pending are indexed again
+        timestamps.append(2, Some(rows(&["  continued", "2024-03-05 10:11:14.000 c"])));
+        assert!(timestamps.format().is_some());
+        assert_eq!(timestamps.len(), 4);
+        assert_eq!(timestamps.get(0), None);
+        assert_eq!(timestamps.get(1), Some(1_000));
+        assert_eq!(timestamps.get(2), Some(1_000));
+        assert_eq!(timestamps.get(3), Some(1_709_633_474_000));
+        // Without detected format after enough rows, the detection is given up
+        let mut timestamps = Timestamps::new();
+        let content = rows(&["no timestamp"; DETECTION_SAMPLE_LINES]);
+        timestamps.append(content.len() as u64, Some(content));
+        assert!(!timestamps.needs_content(1));
+    }
+
+    #[test]
+    fn test_many_blocks() {
+        let mut timestamps = Timestamps::new();
+        let count = BLOCK_SIZE * 3 + 10;
+        for row in 0..count {
+            timestamps.add_written("row\n", Some(row as i64 * 10));
+        }
+        timestamps.append(count, None);
+        assert_eq!(
+            timestamps.first_row_at(BLOCK_SIZE as i64 * 20 + 5),
+            Some(BLOCK_SIZE * 2 + 1)
+        );
+        assert_eq!(
+            timestamps.span(&(5..=count + 100)),
+            Some((50, (count as i64 - 1) * 10))
+        );
+    }
+
+    #[test]
+    fn test_corrections() {
+        let mut timestamps = Timestamps::new();
+        for row in 0..4 {
+            timestamps.add_written("row\n", Some(row * 1_000));
+        }
+        timestamps.append(2, None);
+        timestamps.set_corrections(vec![(1..=1, TimeCorrection::offset(5_000))]);
+        timestamps.add_correction(3..=3, TimeCorrection::offset(-3_000));
+        timestamps.append(2, None);
+        assert_eq!(timestamps.get(0), Some(0));
+        assert_eq!(timestamps.get(1), Some(6_000));
+        assert_eq!(timestamps.original(1), Some(1_000));
+        assert_eq!(timestamps.get(2), Some(2_000));
+        assert_eq!(timestamps.get(3), Some(0));
+        assert_eq!(timestamps.rows_in(0..=0), vec![0..=0, 3..=3]);
+        assert_eq!(timestamps.first_row_at(5_000), Some(1));
+        assert_eq!(timestamps.span(&(0..=3)), Some((0, 6_000)));
+        timestamps.set_corrections(Vec::new());
+        assert_eq!(timestamps.span(&(0..=3)), Some((0, 3_000)));
+    }
+}
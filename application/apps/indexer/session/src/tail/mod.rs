@@ -1,12 +1,20 @@
-use log::error;
-use std::path::Path;
+use log::{debug, error, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::{fs::Metadata, io, path::Path};
 use thiserror::Error as ThisError;
 use tokio::{
-    sync::mpsc::Sender,
-    time::{timeout, Duration},
+    select,
+    sync::mpsc::{unbounded_channel, Sender, UnboundedReceiver},
+    time::{sleep, Duration},
 };
 use tokio_util::sync::CancellationToken;
 
+mod rotation;
+
+pub use rotation::{rotated_path, RotatingFile};
+
+/// Interval of checking the file if there are no notifications from the file system (or
+/// file system notifications are not available)
 const TRACKING_INTERVAL_MS: u64 = 1000;
 
 #[derive(ThisError, Debug)]
@@ -17,37 +25,196 @@ pub enum Error {
     Channel(String),
 }
 
+/// Change of a tracked file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Content was appended to the file
+    Appended,
+    /// The file was truncated in place, e.g. by logrotate with `copytruncate`
+    Truncated,
+    /// The file was renamed or removed and a new file was created with the same path
+    Replaced,
+}
+
+/// Identity and size of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileState {
+    len: u64,
+    /// Device and inode of the file; not available on all platforms
+    id: Option<(u64, u64)>,
+}
+
+impl FileState {
+    /// Returns `None` if the file doesn't exist, e.g. between renaming it and creating
+    /// the new one
+    async fn read(path: &Path) -> Result<Option<Self>, Error> {
+        match tokio::fs::metadata(path).await {
+            Ok(md) => Ok(Some(Self::from(&md))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::Io(err.to_string())),
+        }
+    }
+
+    fn change(&self, updated: &FileState) -> Option<Change> {
+        if self.id != updated.id {
+            Some(Change::Replaced)
+        } else if updated.len < self.len {
+            Some(Change::Truncated)
+        } else if updated.len > self.len {
+            Some(Change::Appended)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<&Metadata> for FileState {
+    fn from(md: &Metadata) -> Self {
+        Self {
+            len: md.len(),
+            id: file_id(md),
+        }
+    }
+}
+
+/// Device and inode of the file, which identify the file independently of its path. Not
+/// available on all platforms.
+pub(crate) fn file_id(md: &Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((md.dev(), md.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = md;
+        None
+    }
+}
+
+/// Tracks the file and sends a [`Change`] each time the file grows, is truncated or is
+/// replaced. File system notifications are used to react on changes right away; the file
+/// is checked with [`TRACKING_INTERVAL_MS`] in addition, because notifications can be
+/// missed (e.g. on network file systems).
 pub async fn track(
     path: &Path,
-    tx_update: Sender<Result<(), Error>>,
+    tx_update: Sender<Result<Change, Error>>,
     shutdown: CancellationToken,
 ) -> Result<(), Error> {
-    use tokio::fs;
-    let mut size = fs::metadata(path)
-        .await
-        .map(|md| md.len())
-        .map_err(|e| Error::Io(e.to_string()))?;
+    let mut state = FileState::read(path)
+        .await?
+        .ok_or_else(|| Error::Io(format!("File {} doesn't exist", path.display())))?;
+    let mut notifications = watch(path);
     loop {
-        match timeout(
-            Duration::from_millis(TRACKING_INTERVAL_MS),
-            shutdown.cancelled(),
-        )
-        .await
-        {
-            Ok(_) => break,
-            Err(_) => {
-                let updated = fs::metadata(path)
-                    .await
-                    .map(|md| md.len())
-                    .map_err(|e| Error::Io(e.to_string()))?;
-                if updated != size {
-                    size = updated;
-                    if let Err(err) = tx_update.send(Ok(())).await {
-                        return Err(Error::Channel(format!("Fail to send update signal: {err}")));
-                    }
+        select! {
+            _ = shutdown.cancelled() => break,
+            _ = async {
+                match notifications.as_mut() {
+                    Some((_, rx_notification)) => rx_notification.recv().await,
+                    None => std::future::pending().await,
                 }
-            }
+            } => {},
+            _ = sleep(Duration::from_millis(TRACKING_INTERVAL_MS)) => {},
         };
+        let Some(updated) = FileState::read(path).await? else {
+            continue;
+        };
+        if let Some(change) = state.change(&updated) {
+            debug!("File {} is changed: {change:?}", path.display());
+            state = updated;
+            if let Err(err) = tx_update.send(Ok(change)).await {
+                return Err(Error::Channel(format!("Fail to send update signal: {err}")));
+            }
+        }
     }
     Ok(())
 }
+
+/// Watches the folder of the file, because a rotated file is replaced by a new one, which
+/// wouldn't be visible watching the file itself.
+fn watch(path: &Path) -> Option<(RecommendedWatcher, UnboundedReceiver<()>)> {
+    let filename = path.file_name()?.to_owned();
+    let folder = match path.parent() {
+        Some(folder) if !folder.as_os_str().is_empty() => folder,
+        _ => Path::new("."),
+    };
+    watch_folder(folder, move |changed| {
+        changed.file_name() == Some(filename.as_os_str())
+    })
+}
+
+/// Watches the content of the folder (not recursive) and sends a notification each time an
+/// entry accepted by `filter` is changed. Returns `None` if file system notifications are not
+/// available; the watcher has to be kept alive to receive notifications.
+pub(crate) fn watch_folder<F>(
+    folder: &Path,
+    filter: F,
+) -> Option<(RecommendedWatcher, UnboundedReceiver<()>)>
+where
+    F: Fn(&Path) -> bool + Send + 'static,
+{
+    let (tx_notification, rx_notification) = unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) => {
+                if event.paths.iter().any(|changed| filter(changed)) {
+                    // Receiver is dropped only when watching is finished
+                    let _ = tx_notification.send(());
+                }
+            }
+            Err(err) => error!("File system notification error: {err}"),
+        }
+    })
+    .map_err(|err| warn!("Fail to create file system watcher: {err}"))
+    .ok()?;
+    watcher
+        .watch(folder, RecursiveMode::NonRecursive)
+        .map_err(|err| warn!("Fail to watch {}: {err}", folder.display()))
+        .ok()?;
+    Some((watcher, rx_notification))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, io::Write};
+    use tokio::{sync::mpsc::channel, time::timeout};
+
+    async fn next_change(rx: &mut tokio::sync::mpsc::Receiver<Result<Change, Error>>) -> Change {
+        timeout(Duration::from_millis(TRACKING_INTERVAL_MS * 5), rx.recv())
+            .await
+            .expect("change is detected in time")
+            .expect("tracker is running")
+            .expect("tracker has no errors")
+    }
+
+    #[tokio::test]
+    async fn test_track_rotation() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("tracked.log");
+        fs::write(&path, b"first\n").unwrap();
+        let (tx, mut rx) = channel(1);
+        let shutdown = CancellationToken::new();
+        let tracker = tokio::spawn({
+            let path = path.clone();
+            let shutdown = shutdown.clone();
+            async move { track(&path, tx, shutdown).await }
+        });
+        // Let the tracker take the initial state of the file
+        sleep(Duration::from_millis(100)).await;
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"second\n")
+            .unwrap();
+        assert_eq!(next_change(&mut rx).await, Change::Appended);
+        fs::File::create(&path).unwrap();
+        assert_eq!(next_change(&mut rx).await, Change::Truncated);
+        fs::rename(&path, folder.path().join("tracked.log.1")).unwrap();
+        fs::write(&path, b"third\n").unwrap();
+        assert_eq!(next_change(&mut rx).await, Change::Replaced);
+        shutdown.cancel();
+        tracker.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+use super::FileState;
+use log::debug;
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Suffix which logrotate (and most other tools) adds to the most recent rotated file
+const ROTATED_SUFFIX: &str = ".1";
+
+/// Path of the most recent rotated file of `path`
+pub fn rotated_path(path: &Path) -> PathBuf {
+    let mut rotated = OsString::from(path.as_os_str());
+    rotated.push(ROTATED_SUFFIX);
+    PathBuf::from(rotated)
+}
+
+/// Reader of a tailed file, which follows the file through log rotation. Reaching the end
+/// of the file, the reader checks whether the file at the original path was:
+///
+/// - replaced by a new file (rotation by renaming): the rest of the old file is read already
+///   via the opened handle, so reading continues with the new file from its beginning;
+/// - truncated in place (`copytruncate`): reading continues from the beginning of the
+///   file. Data written between the last read and the truncation were copied into the
+///   rotated file (`<path>.1`); if picking up of the rotated file is enabled, this tail
+///   of the rotated file is read first.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    state: FileState,
+    /// Position in `file`
+    pos: u64,
+    /// Tail of the rotated file, which has to be read before continuing with `file`
+    rotated: Option<File>,
+    pick_up_rotated: bool,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let state = FileState::from(&file.metadata()?);
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            state,
+            pos: 0,
+            rotated: None,
+            pick_up_rotated: true,
+        })
+    }
+
+    /// Defines whether the tail of the rotated file is read after the file was truncated in
+    /// place. Enabled by default.
+    pub fn pick_up_rotated(mut self, pick_up: bool) -> Self {
+        self.pick_up_rotated = pick_up;
+        self
+    }
+
+    /// Checks whether the file at the original path was rotated and switches to the new
+    /// content. Returns `false` if there is nothing new to read.
+    fn follow_rotation(&mut self) -> io::Result<bool> {
+        let updated = match fs::metadata(&self.path) {
+            Ok(md) => FileState::from(&md),
+            // Rotated file is renamed, but new file isn't created yet
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        if updated.id != self.state.id {
+            debug!("File {} was replaced", self.path.display());
+            let file = File::open(&self.path)?;
+            self.state = FileState::from(&file.metadata()?);
+            self.file = file;
+            self.pos = 0;
+            Ok(true)
+        } else if updated.len < self.pos {
+            debug!(
+                "File {} was truncated from {} to {} bytes",
+                self.path.display(),
+                self.pos,
+                updated.len
+            );
+            if self.pick_up_rotated {
+                self.rotated = self.rotated_tail()?;
+            }
+            self.file.seek(SeekFrom::Start(0))?;
+            self.state = updated;
+            self.pos = 0;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Opens the rotated file and seeks it to the position, which was reached in the
+    /// truncated file. Returns `None` if there is no rotated file or it cannot be a copy
+    /// of the truncated file.
+    fn rotated_tail(&self) -> io::Result<Option<File>> {
+        let mut file = match File::open(rotated_path(&self.path)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if file.metadata()?.len() < self.pos {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(self.pos))?;
+        Ok(Some(file))
+    }
+}
+
+impl Read for RotatingFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(rotated) = self.rotated.as_mut() {
+                let len = rotated.read(buf)?;
+                if len > 0 {
+                    return Ok(len);
+                }
+                self.rotated = None;
+            }
+            let len = self.file.read(buf)?;
+            if len > 0 {
+                self.pos += len as u64;
+                return Ok(len);
+            }
+            if buf.is_empty() || !self.follow_rotation()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &Path, content: &str) {
+        fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+    }
+
+    fn read_available(file: &mut RotatingFile) -> String {
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        content
+    }
+
+    #[test]
+    fn test_append() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("app.log");
+        append(&path, "a\n");
+        let mut file = RotatingFile::open(&path).unwrap();
+        assert_eq!(read_available(&mut file), "a\n");
+        assert_eq!(read_available(&mut file), "");
+        append(&path, "b\n");
+        assert_eq!(read_available(&mut file), "b\n");
+    }
+
+    #[test]
+    fn test_rename_and_recreate() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("app.log");
+        append(&path, "a\n");
+        let mut file = RotatingFile::open(&path).unwrap();
+        assert_eq!(read_available(&mut file), "a\n");
+        append(&path, "b\n");
+        fs::rename(&path, folder.path().join("app.log.1")).unwrap();
+        assert_eq!(read_available(&mut file), "b\n");
+        append(&path, "c\n");
+        assert_eq!(read_available(&mut file), "c\n");
+    }
+
+    #[test]
+    fn test_copytruncate() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("app.log");
+        append(&path, "a\n");
+        let mut file = RotatingFile::open(&path).unwrap();
+        assert_eq!(read_available(&mut file), "a\n");
+        append(&path, "b\n");
+        fs::copy(&path, folder.path().join("app.log.1")).unwrap();
+        File::create(&path).unwrap();
+        append(&path, "c\n");
+        assert_eq!(read_available(&mut file), "b\nc\n");
+    }
+
+    #[test]
+    fn test_copytruncate_without_rotated() {
+        let folder = tempfile::tempdir().unwrap();
+        let path = folder.path().join("app.log");
+        append(&path, "a\nb\n");
+        let mut file = RotatingFile::open(&path).unwrap().pick_up_rotated(false);
+        assert_eq!(read_available(&mut file), "a\nb\n");
+        fs::copy(&path, folder.path().join("app.log.1")).unwrap();
+        File::create(&path).unwrap();
+        append(&path, "c\n");
+        assert_eq!(read_available(&mut file), "c\n");
+    }
+}
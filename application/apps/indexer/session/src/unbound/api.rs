@@ -114,6 +114,20 @@ impl UnboundSessionAPI {
             .await
     }
 
+    pub async fn detect_timestamp_format(
+        &self,
+        id: u64,
+        file_path: String,
+    ) -> Result<CommandOutcome<String>, ComputationError> {
+        let (tx_results, rx_results) = oneshot::channel();
+        self.process_command(
+            id,
+            rx_results,
+            Command::DetectTimestampFormat(file_path, tx_results),
+        )
+        .await
+    }
+
     pub async fn spawn_process(
         &self,
         id: u64,
@@ -149,6 +163,20 @@ impl UnboundSessionAPI {
             .await
     }
 
+    pub async fn get_archive_members(
+        &self,
+        id: u64,
+        archive: String,
+    ) -> Result<CommandOutcome<String>, ComputationError> {
+        let (tx_results, rx_results) = oneshot::channel();
+        self.process_command(
+            id,
+            rx_results,
+            Command::GetArchiveMembers(archive, tx_results),
+        )
+        .await
+    }
+
     pub async fn get_someip_statistic(
         &self,
         id: u64,
@@ -0,0 +1,13 @@
+use super::CommandOutcome;
+use crate::events::ComputationError;
+use sources::binary::archive::list_members;
+use std::path::Path;
+
+pub fn members(archive: String) -> Result<CommandOutcome<String>, ComputationError> {
+    let members = list_members(Path::new(&archive))
+        .map_err(|e| ComputationError::IoOperation(e.to_string()))?;
+    Ok(CommandOutcome::Finished(
+        serde_json::to_string(&members)
+            .map_err(|e| ComputationError::IoOperation(e.to_string()))?,
+    ))
+}
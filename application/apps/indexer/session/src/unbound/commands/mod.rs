@@ -1,3 +1,4 @@
+mod archive;
 mod cancel_test;
 mod checksum;
 mod dlt;
@@ -9,6 +10,7 @@ mod serial;
 mod shells;
 mod sleep;
 mod someip;
+mod timestamp;
 
 use crate::{events::ComputationError, unbound::commands::someip::get_someip_statistic};
 
@@ -77,6 +79,10 @@ pub enum Command {
         Vec<String>,
         oneshot::Sender<Result<CommandOutcome<String>, ComputationError>>,
     ),
+    GetArchiveMembers(
+        String,
+        oneshot::Sender<Result<CommandOutcome<String>, ComputationError>>,
+    ),
     GetShellProfiles(oneshot::Sender<Result<CommandOutcome<String>, ComputationError>>),
     GetContextEnvvars(oneshot::Sender<Result<CommandOutcome<String>, ComputationError>>),
     SerialPortsList(oneshot::Sender<Result<CommandOutcome<Vec<String>>, ComputationError>>),
@@ -84,6 +90,10 @@ pub enum Command {
         String,
         oneshot::Sender<Result<CommandOutcome<bool>, ComputationError>>,
     ),
+    DetectTimestampFormat(
+        String,
+        oneshot::Sender<Result<CommandOutcome<String>, ComputationError>>,
+    ),
     CancelTest(
         i64,
         i64,
@@ -107,8 +117,10 @@ impl std::fmt::Display for Command {
                 Command::Checksum(_, _) => "Calculating file's checksum",
                 Command::GetDltStats(_, _) => "Getting dlt stats",
                 Command::GetSomeipStatistic(_, _) => "Getting someip statistic",
+                Command::GetArchiveMembers(_, _) => "Getting archive members",
                 Command::GetRegexError(_, _) => "Checking regex",
                 Command::IsFileBinary(_, _) => "Checking if file is binary",
+                Command::DetectTimestampFormat(_, _) => "Detecting timestamp format",
             }
         )
     }
@@ -140,10 +152,14 @@ pub async fn process(command: Command, signal: Signal) {
         Command::GetSomeipStatistic(files, tx) => {
             tx.send(get_someip_statistic(files, signal)).is_err()
         }
+        Command::GetArchiveMembers(archive, tx) => tx.send(archive::members(archive)).is_err(),
         Command::GetShellProfiles(tx) => tx.send(shells::get_valid_profiles(signal)).is_err(),
         Command::GetContextEnvvars(tx) => tx.send(shells::get_context_envvars(signal)).is_err(),
         Command::SerialPortsList(tx) => tx.send(serial::available_ports(signal)).is_err(),
         Command::IsFileBinary(file_path, tx) => tx.send(file::is_file_binary(file_path)).is_err(),
+        Command::DetectTimestampFormat(file_path, tx) => {
+            tx.send(timestamp::detect_format(file_path)).is_err()
+        }
         Command::CancelTest(a, b, tx) => tx
             .send(cancel_test::cancel_test(a, b, signal).await)
             .is_err(),
@@ -162,10 +178,12 @@ pub fn err(command: Command, err: ComputationError) {
         Command::Checksum(_file, tx) => tx.send(Err(err)).is_err(),
         Command::GetDltStats(_files, tx) => tx.send(Err(err)).is_err(),
         Command::GetSomeipStatistic(_files, tx) => tx.send(Err(err)).is_err(),
+        Command::GetArchiveMembers(_archive, tx) => tx.send(Err(err)).is_err(),
         Command::GetShellProfiles(tx) => tx.send(Err(err)).is_err(),
         Command::GetContextEnvvars(tx) => tx.send(Err(err)).is_err(),
         Command::SerialPortsList(tx) => tx.send(Err(err)).is_err(),
         Command::IsFileBinary(_filepath, tx) => tx.send(Err(err)).is_err(),
+        Command::DetectTimestampFormat(_filepath, tx) => tx.send(Err(err)).is_err(),
         Command::CancelTest(_a, _b, tx) => tx.send(Err(err)).is_err(),
     } {
         error!("Fail to send error response for command: {cmd}");
@@ -0,0 +1,15 @@
+use super::CommandOutcome;
+use crate::events::ComputationError;
+use parsers::timestamp::detect_file_format;
+use std::path::Path;
+
+/// Detects the timestamp format of a text file. Returns the detected format serialized as
+/// JSON or `null` if no known format is found.
+pub fn detect_format(file_path: String) -> Result<CommandOutcome<String>, ComputationError> {
+    let detected = detect_file_format(Path::new(&file_path))
+        .map_err(|e| ComputationError::IoOperation(e.to_string()))?;
+    Ok(CommandOutcome::Finished(
+        serde_json::to_string(&detected)
+            .map_err(|e| ComputationError::IoOperation(e.to_string()))?,
+    ))
+}
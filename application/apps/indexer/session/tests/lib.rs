@@ -1 +1,2 @@
 mod snapshot_tests;
+mod tailing;
@@ -0,0 +1,63 @@
+use processor::grabber::LineRange;
+use session::{events::CallbackEvent, session::Session};
+use sources::factory::{FileFormat, ObserveOptions, ParserType};
+use std::{fs, io::Write};
+use tokio::time::{sleep, timeout, Duration};
+use uuid::Uuid;
+
+/// Waits until the session has `rows` rows and returns their content
+async fn wait_rows(session: &Session, rows: usize) -> Vec<String> {
+    timeout(Duration::from_secs(10), async {
+        while session.get_stream_len().await.unwrap() < rows {
+            sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("rows are added in time");
+    session
+        .grab(LineRange::from(0..=(rows as u64 - 1)), None)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.content)
+        .collect()
+}
+
+#[tokio::test]
+async fn tail_renamed_text_file() {
+    let folder = tempfile::tempdir().unwrap();
+    let path = folder.path().join("app.log");
+    fs::write(&path, "first\nsecond\n").unwrap();
+    let uuid = Uuid::new_v4();
+    let (session, mut receiver) = Session::new(uuid).await.unwrap();
+    let operation = Uuid::new_v4();
+    session
+        .observe(
+            operation,
+            ObserveOptions::file(path.clone(), FileFormat::Text, ParserType::Text),
+        )
+        .unwrap();
+    while let Some(event) = receiver.recv().await {
+        match event {
+            CallbackEvent::FileRead => break,
+            CallbackEvent::OperationError { error, .. } => panic!("{error:?}"),
+            _ => {}
+        }
+    }
+    assert_eq!(wait_rows(&session, 2).await, ["first", "second"]);
+    // Rotation by renaming: the new file is read from its beginning
+    fs::rename(&path, folder.path().join("app.log.1")).unwrap();
+    fs::write(&path, "third\n").unwrap();
+    assert_eq!(wait_rows(&session, 3).await, ["first", "second", "third"]);
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(b"fourth\n")
+        .unwrap();
+    assert_eq!(
+        wait_rows(&session, 4).await,
+        ["first", "second", "third", "fourth"]
+    );
+    session.stop(operation).await.unwrap();
+}
@@ -70,6 +70,7 @@ impl ByteSource for MockByteSource {
                     newly_loaded_bytes: 5,
                     skipped_bytes: 0,
                     last_known_ts: None,
+                    networks: Vec::new(),
                 }));
 
             black_box(AA)
@@ -0,0 +1,423 @@
+use crate::{
+    binary::compressed::{Compression, DecompressedReader},
+    factory::FileFormat,
+};
+use parsers::dlt::DLT_STORAGE_PATTERN;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    sync::mpsc::{sync_channel, Receiver, SyncSender},
+    thread,
+};
+use zip::ZipArchive;
+
+/// Size of chunks passed from the archive reading thread
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Count of chunks which can be buffered before the archive reading thread is blocked
+const CHUNKS_IN_FLIGHT: usize = 16;
+/// Count of bytes of a member used to detect its format
+const HEADER_LEN: usize = 16;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const ZIP_EMPTY_MAGIC: &[u8] = b"PK\x05\x06";
+const TAR_HEADER_LEN: usize = 512;
+const TAR_CHECKSUM: std::ops::Range<usize> = 148..156;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    /// Tar archive, possibly compressed (e.g. `.tar.gz`)
+    Tar,
+}
+
+impl ArchiveKind {
+    /// Detects the kind of archive by its magic bytes or, for tar archives, by the checksum
+    /// of the first header. Returns `None` if the file isn't a supported archive.
+    pub fn detect(path: &Path) -> io::Result<Option<Self>> {
+        let mut header = Vec::with_capacity(TAR_HEADER_LEN);
+        File::open(path)?
+            .take(ZIP_MAGIC.len() as u64)
+            .read_to_end(&mut header)?;
+        if header.starts_with(ZIP_MAGIC) || header.starts_with(ZIP_EMPTY_MAGIC) {
+            return Ok(Some(Self::Zip));
+        }
+        header.clear();
+        DecompressedReader::open(path)?
+            .take(TAR_HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        Ok(is_tar_header(&header).then_some(Self::Tar))
+    }
+}
+
+/// Checks the checksum of a tar header. Unlike the "ustar" magic, the checksum is present
+/// in all tar formats, including pre-POSIX (v7) archives.
+fn is_tar_header(header: &[u8]) -> bool {
+    if header.len() < TAR_HEADER_LEN {
+        return false;
+    }
+    let Some(expected) = std::str::from_utf8(&header[TAR_CHECKSUM])
+        .ok()
+        .map(|field| field.trim_matches(|c| c == ' ' || c == '\0'))
+        .filter(|field| !field.is_empty())
+        .and_then(|field| u32::from_str_radix(field, 8).ok())
+    else {
+        return false;
+    };
+    // The checksum field itself is summed as spaces. Some old implementations used signed
+    // bytes, so both variants are accepted.
+    let (unsigned, signed) = header[..TAR_HEADER_LEN].iter().enumerate().fold(
+        (0u32, 0i32),
+        |(unsigned, signed), (i, byte)| {
+            let byte = if TAR_CHECKSUM.contains(&i) {
+                b' '
+            } else {
+                *byte
+            };
+            (unsigned + byte as u32, signed + byte as i8 as i32)
+        },
+    );
+    expected == unsigned || expected as i32 == signed
+}
+
+/// Describes a file inside of an archive
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchiveMember {
+    /// Path of the member inside of the archive
+    pub path: String,
+    /// Size of the member's content (uncompressed)
+    pub size: u64,
+    /// Detected format of the member; `None` if it cannot be detected
+    pub format: Option<FileFormat>,
+    /// `true` if the member itself is compressed (e.g. `trace.dlt.gz`)
+    pub compressed: bool,
+}
+
+impl ArchiveMember {
+    fn new(path: String, size: u64, header: &[u8]) -> Self {
+        let compressed = Compression::detect(header).is_some();
+        let format = detect_format(&path, if compressed { &[] } else { header });
+        Self {
+            path,
+            size,
+            format,
+            compressed,
+        }
+    }
+}
+
+/// Detects the format of a file by its first bytes and falls back to the extension of the
+/// file name (a compression extension like `.gz` is ignored).
+pub fn detect_format(name: &str, header: &[u8]) -> Option<FileFormat> {
+    const PCAPNG_MAGIC: &[u8] = &[0x0a, 0x0d, 0x0d, 0x0a];
+    const PCAP_MAGICS: [&[u8]; 4] = [
+        &[0xd4, 0xc3, 0xb2, 0xa1],
+        &[0xa1, 0xb2, 0xc3, 0xd4],
+        &[0x4d, 0x3c, 0xb2, 0xa1],
+        &[0xa1, 0xb2, 0x3c, 0x4d],
+    ];
+    if header.starts_with(PCAPNG_MAGIC) {
+        return Some(FileFormat::PcapNG);
+    }
+    if PCAP_MAGICS.iter().any(|magic| header.starts_with(magic)) {
+        return Some(FileFormat::PcapLegacy);
+    }
+    if header.starts_with(DLT_STORAGE_PATTERN) {
+        return Some(FileFormat::Binary);
+    }
+    let name = name.to_lowercase();
+    let name = ["gz", "zst", "xz", "bz2"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(&format!(".{ext}")))
+        .unwrap_or(&name);
+    match Path::new(name).extension()?.to_str()? {
+        "pcapng" => Some(FileFormat::PcapNG),
+        "pcap" => Some(FileFormat::PcapLegacy),
+        "dlt" => Some(FileFormat::Binary),
+        "txt" | "log" | "json" | "csv" => Some(FileFormat::Text),
+        _ => None,
+    }
+}
+
+/// Lists all files of the archive. Directories and other special entries are skipped.
+pub fn list_members(archive: &Path) -> io::Result<Vec<ArchiveMember>> {
+    match ArchiveKind::detect(archive)? {
+        Some(ArchiveKind::Zip) => {
+            let mut zip = ZipArchive::new(File::open(archive)?).map_err(io::Error::other)?;
+            let mut members = Vec::new();
+            for i in 0..zip.len() {
+                let file = zip.by_index(i).map_err(io::Error::other)?;
+                if !file.is_file() {
+                    continue;
+                }
+                let (path, size) = (file.name().to_owned(), file.size());
+                let mut header = Vec::with_capacity(HEADER_LEN);
+                file.take(HEADER_LEN as u64).read_to_end(&mut header)?;
+                members.push(ArchiveMember::new(path, size, &header));
+            }
+            Ok(members)
+        }
+        Some(ArchiveKind::Tar) => {
+            let mut tar = tar::Archive::new(DecompressedReader::open(archive)?);
+            let mut members = Vec::new();
+            for entry in tar.entries()? {
+                let mut entry = entry?;
+                if !entry.header().entry_type().is_file() {
+                    continue;
+                }
+                let (path, size) = (entry.path()?.to_string_lossy().into_owned(), entry.size());
+                let mut header = Vec::with_capacity(HEADER_LEN);
+                (&mut entry)
+                    .take(HEADER_LEN as u64)
+                    .read_to_end(&mut header)?;
+                members.push(ArchiveMember::new(path, size, &header));
+            }
+            Ok(members)
+        }
+        None => Err(unsupported(archive)),
+    }
+}
+
+/// Reader of a single member of an archive.
+///
+/// Members of archives borrow the archive they belong to, therefore the member is read in
+/// a dedicated thread and passed in chunks to the reader. Nothing is extracted to disk.
+pub struct ArchiveMemberReader {
+    rx: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ArchiveMemberReader {
+    /// Opens the member `member` of the archive `archive`. Fails if the archive isn't
+    /// supported or the member cannot be found.
+    pub fn open(archive: &Path, member: &str) -> io::Result<Self> {
+        let kind = ArchiveKind::detect(archive)?.ok_or_else(|| unsupported(archive))?;
+        let (tx_located, rx_located) = sync_channel(1);
+        let (tx_data, rx_data) = sync_channel(CHUNKS_IN_FLIGHT);
+        let mut pipe = Pipe {
+            located: Some(tx_located),
+            data: tx_data,
+        };
+        let (archive, member) = (archive.to_path_buf(), member.to_owned());
+        thread::spawn(move || {
+            if let Err(err) = pipe.read_member(kind, &archive, &member) {
+                pipe.fail(err);
+            }
+        });
+        rx_located
+            .recv()
+            .map_err(|_| io::Error::other("archive reader is stopped unexpectedly"))??;
+        Ok(Self {
+            rx: rx_data,
+            chunk: Vec::new(),
+            pos: 0,
+        })
+    }
+}
+
+impl Read for ArchiveMemberReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                // Sender is dropped: member is read completely
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.pos);
+        buf[..len].copy_from_slice(&self.chunk[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Sending side of [`ArchiveMemberReader`]
+struct Pipe {
+    located: Option<SyncSender<io::Result<()>>>,
+    data: SyncSender<io::Result<Vec<u8>>>,
+}
+
+impl Pipe {
+    fn read_member(&mut self, kind: ArchiveKind, archive: &Path, member: &str) -> io::Result<()> {
+        match kind {
+            ArchiveKind::Zip => {
+                let mut zip = ZipArchive::new(File::open(archive)?).map_err(io::Error::other)?;
+                let file = zip
+                    .by_name(member)
+                    .map_err(|_| not_found(archive, member))?;
+                self.pump(file)
+            }
+            ArchiveKind::Tar => {
+                let mut tar = tar::Archive::new(DecompressedReader::open(archive)?);
+                for entry in tar.entries()? {
+                    let entry = entry?;
+                    if entry.path()?.to_string_lossy() == member {
+                        return self.pump(entry);
+                    }
+                }
+                Err(not_found(archive, member))
+            }
+        }
+    }
+
+    fn pump<R: Read>(&mut self, mut reader: R) -> io::Result<()> {
+        if let Some(located) = self.located.take() {
+            // Reader could be dropped already; nothing to do in this case
+            let _ = located.send(Ok(()));
+        }
+        loop {
+            let mut chunk = vec![0u8; CHUNK_SIZE];
+            let len = reader.read(&mut chunk)?;
+            if len == 0 {
+                return Ok(());
+            }
+            chunk.truncate(len);
+            if self.data.send(Ok(chunk)).is_err() {
+                // Reader is dropped; no reason to continue
+                return Ok(());
+            }
+        }
+    }
+
+    fn fail(self, err: io::Error) {
+        // Reader could be dropped already; nothing to report in this case
+        if let Some(located) = self.located {
+            let _ = located.send(Err(err));
+        } else {
+            let _ = self.data.send(Err(err));
+        }
+    }
+}
+
+fn unsupported(archive: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{} isn't a zip or tar archive", archive.display()),
+    )
+}
+
+fn not_found(archive: &Path, member: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{member} isn't found in {}", archive.display()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{Cursor, Write},
+        path::PathBuf,
+    };
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    const DLT: &[u8] = b"DLT\x01 storage header and some payload";
+    const TEXT: &[u8] = b"first line\nsecond line\n";
+
+    fn tmp_file(name: &str, content: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}_{name}", uuid::Uuid::new_v4()));
+        File::create(&path).unwrap().write_all(content).unwrap();
+        path
+    }
+
+    fn zip_archive() -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.add_directory("logs/", options).unwrap();
+        zip.start_file("logs/trace.dlt", options).unwrap();
+        zip.write_all(DLT).unwrap();
+        zip.start_file("logs/syslog.log", options).unwrap();
+        zip.write_all(TEXT).unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn tar_archive(new_header: fn() -> tar::Header) -> Vec<u8> {
+        let mut tar = tar::Builder::new(Vec::new());
+        for (path, content) in [("logs/trace.dlt", DLT), ("logs/syslog.log", TEXT)] {
+            let mut header = new_header();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, path, content).unwrap();
+        }
+        tar.into_inner().unwrap()
+    }
+
+    fn check_archive(archive: &Path) {
+        let members = list_members(archive).unwrap();
+        assert_eq!(members.len(), 2);
+        assert_eq!(members[0].path, "logs/trace.dlt");
+        assert_eq!(members[0].size, DLT.len() as u64);
+        assert!(matches!(members[0].format, Some(FileFormat::Binary)));
+        assert_eq!(members[1].path, "logs/syslog.log");
+        assert!(matches!(members[1].format, Some(FileFormat::Text)));
+        let mut content = Vec::new();
+        ArchiveMemberReader::open(archive, "logs/syslog.log")
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, TEXT);
+        assert!(ArchiveMemberReader::open(archive, "logs/missing.log").is_err());
+    }
+
+    #[test]
+    fn test_zip_members() {
+        let archive = tmp_file("bundle.zip", &zip_archive());
+        assert_eq!(
+            ArchiveKind::detect(&archive).unwrap(),
+            Some(ArchiveKind::Zip)
+        );
+        check_archive(&archive);
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn test_tar_gz_members() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(&tar_archive(tar::Header::new_gnu))
+            .unwrap();
+        let archive = tmp_file("bundle.tar.gz", &encoder.finish().unwrap());
+        assert_eq!(
+            ArchiveKind::detect(&archive).unwrap(),
+            Some(ArchiveKind::Tar)
+        );
+        check_archive(&archive);
+        std::fs::remove_file(archive).unwrap();
+    }
+
+    #[test]
+    fn test_v7_tar_members() {
+        let archive = tmp_file("bundle.tar", &tar_archive(tar::Header::new_old));
+        assert_eq!(
+            ArchiveKind::detect(&archive).unwrap(),
+            Some(ArchiveKind::Tar)
+        );
+        check_archive(&archive);
+        std::fs::remove_file(archive).unwrap();
+        // Random content of the same size isn't a tar header
+        let mut header = [b'x'; TAR_HEADER_LEN];
+        assert!(!is_tar_header(&header));
+        header[TAR_CHECKSUM].copy_from_slice(b"0000000\0");
+        assert!(!is_tar_header(&header));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert!(matches!(
+            detect_format("capture", &[0x0a, 0x0d, 0x0d, 0x0a]),
+            Some(FileFormat::PcapNG)
+        ));
+        assert!(matches!(
+            detect_format("capture.pcap.gz", &[]),
+            Some(FileFormat::PcapLegacy)
+        ));
+        assert!(detect_format("image.png", &[]).is_none());
+    }
+}
@@ -0,0 +1,190 @@
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use xz2::bufread::XzDecoder;
+
+/// Compression formats which can be read transparently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Compression {
+    const MAGIC: [(Compression, &'static [u8]); 4] = [
+        (Compression::Gzip, &[0x1f, 0x8b]),
+        (Compression::Zstd, &[0x28, 0xb5, 0x2f, 0xfd]),
+        (Compression::Xz, &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]),
+        (Compression::Bzip2, b"BZh"),
+    ];
+
+    /// Detects the compression by the magic bytes at the beginning of the content
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        Self::MAGIC
+            .iter()
+            .find(|(_, magic)| header.starts_with(magic))
+            .map(|(compression, _)| *compression)
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Gzip => "gzip",
+                Self::Zstd => "zstd",
+                Self::Xz => "xz",
+                Self::Bzip2 => "bzip2",
+            }
+        )
+    }
+}
+
+/// Counts bytes which were read from the underlying input
+struct CountingReader<R: Read> {
+    inner: R,
+    consumed: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.consumed.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+}
+
+/// Reader which decompresses its input if it's compressed with one of the supported
+/// [`Compression`] formats; not compressed input is passed through as it is.
+///
+/// It's meant to be used as input of file based sources like `BinaryByteSource` or
+/// `PcapngByteSource`.
+pub struct DecompressedReader {
+    inner: Box<dyn Read + Send>,
+    compression: Option<Compression>,
+    consumed: Arc<AtomicU64>,
+}
+
+impl DecompressedReader {
+    pub fn new<R: Read + Send + 'static>(input: R) -> io::Result<Self> {
+        let consumed = Arc::new(AtomicU64::new(0));
+        let mut reader = BufReader::new(CountingReader {
+            inner: input,
+            consumed: consumed.clone(),
+        });
+        let compression = Compression::detect(reader.fill_buf()?);
+        let inner: Box<dyn Read + Send> = match compression {
+            Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(reader)),
+            Some(Compression::Zstd) => Box::new(zstd::stream::read::Decoder::with_buffer(reader)?),
+            Some(Compression::Xz) => Box::new(XzDecoder::new_multi_decoder(reader)),
+            Some(Compression::Bzip2) => Box::new(MultiBzDecoder::new(reader)),
+            None => Box::new(reader),
+        };
+        Ok(Self {
+            inner,
+            compression,
+            consumed,
+        })
+    }
+
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::new(File::open(path)?)
+    }
+
+    /// Detected compression of the input; `None` if the input isn't compressed
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Shared counter of bytes which were read from the original (compressed) input. Can be
+    /// used to report the progress against the size of the compressed file.
+    pub fn consumed(&self) -> Arc<AtomicU64> {
+        self.consumed.clone()
+    }
+}
+
+impl Read for DecompressedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{binary::raw::BinaryByteSource, tests::general_source_reload_test};
+    use std::io::{Cursor, Write};
+
+    const CONTENT: &[u8] = b"first line\nsecond line\nthird line\n";
+
+    fn compress(compression: Compression) -> Vec<u8> {
+        match compression {
+            Compression::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(CONTENT).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Zstd => zstd::stream::encode_all(CONTENT, 0).unwrap(),
+            Compression::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+                encoder.write_all(CONTENT).unwrap();
+                encoder.finish().unwrap()
+            }
+            Compression::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+                encoder.write_all(CONTENT).unwrap();
+                encoder.finish().unwrap()
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompress() {
+        for compression in [
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Xz,
+            Compression::Bzip2,
+        ] {
+            let compressed = compress(compression);
+            let compressed_len = compressed.len() as u64;
+            let mut reader = DecompressedReader::new(Cursor::new(compressed)).unwrap();
+            assert_eq!(reader.compression(), Some(compression));
+            let mut content = Vec::new();
+            reader.read_to_end(&mut content).unwrap();
+            assert_eq!(content, CONTENT, "{compression}");
+            assert_eq!(reader.consumed().load(Ordering::Relaxed), compressed_len);
+        }
+    }
+
+    #[test]
+    fn test_pass_through() {
+        let mut reader = DecompressedReader::new(Cursor::new(CONTENT)).unwrap();
+        assert_eq!(reader.compression(), None);
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        assert_eq!(content, CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_general_source_reload() {
+        let reader = DecompressedReader::new(Cursor::new(compress(Compression::Gzip))).unwrap();
+        let mut source = BinaryByteSource::custom(reader, 16, 8);
+        general_source_reload_test(&mut source).await;
+    }
+}
@@ -1,2 +1,4 @@
+pub mod archive;
+pub mod compressed;
 pub mod pcap;
 pub mod raw;
@@ -0,0 +1,376 @@
+use etherparse::{IpNumber, NetSlice};
+use log::warn;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+};
+
+/// How long (in capture time, microseconds) fragments of an IP packet are kept waiting for
+/// the missing ones. Same as the default of the Linux kernel.
+const FRAGMENTS_TIMEOUT_US: u64 = 30_000_000;
+
+/// Maximum amount of bytes of all incomplete IP packets. If more bytes are waiting, the
+/// oldest incomplete packets are dropped.
+const MAX_FRAGMENTS_BYTES: usize = 16 * 1024 * 1024;
+
+/// Maximum size of a reassembled IP payload
+const MAX_PAYLOAD_LEN: usize = 65_535;
+
+/// Counters of IP packets which could not be reassembled from their fragments
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefragFailures {
+    /// Missing fragments didn't arrive in time
+    pub timed_out: usize,
+    /// Dropped to keep the memory limit for pending fragments
+    pub evicted: usize,
+    /// Fragments didn't fit together or exceeded the maximum packet size
+    pub invalid: usize,
+    /// Fragments were still missing at the end of the capture
+    pub incomplete: usize,
+}
+
+impl DefragFailures {
+    pub fn total(&self) -> usize {
+        self.timed_out + self.evicted + self.invalid + self.incomplete
+    }
+}
+
+/// Fragment of an IP packet as found in a captured frame
+#[derive(Debug)]
+pub(crate) struct Fragment<'a> {
+    key: FragmentsKey,
+    /// Offset of the fragment in the original payload (in bytes)
+    offset: usize,
+    /// More fragments are following
+    more: bool,
+    payload: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+    /// Returns the fragment if the payload of the IP packet `net` is fragmented
+    pub fn from_net(net: &NetSlice<'a>) -> Option<Self> {
+        match net {
+            NetSlice::Ipv4(ip) => {
+                let header = ip.header();
+                if !header.more_fragments() && header.fragments_offset().value() == 0 {
+                    return None;
+                }
+                Some(Self {
+                    key: FragmentsKey {
+                        src: IpAddr::V4(header.source_addr()),
+                        dst: IpAddr::V4(header.destination_addr()),
+                        protocol: ip.payload().ip_number,
+                        id: header.identification() as u32,
+                    },
+                    offset: header.fragments_offset().value() as usize * 8,
+                    more: header.more_fragments(),
+                    payload: ip.payload().payload,
+                })
+            }
+            NetSlice::Ipv6(ip) => {
+                let (id, offset, more) =
+                    ipv6_fragment_header(ip.header().next_header(), ip.extensions().slice())?;
+                if !more && offset == 0 {
+                    // atomic fragment, the payload is complete
+                    return None;
+                }
+                Some(Self {
+                    key: FragmentsKey {
+                        src: IpAddr::V6(ip.header().source_addr()),
+                        dst: IpAddr::V6(ip.header().destination_addr()),
+                        protocol: ip.payload().ip_number,
+                        id,
+                    },
+                    offset,
+                    more,
+                    payload: ip.payload().payload,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn addrs(&self) -> (IpAddr, IpAddr) {
+        (self.key.src, self.key.dst)
+    }
+
+    pub fn len(&self) -> usize {
+        self.payload.len()
+    }
+}
+
+/// Looks up the fragment header in the IPv6 extension headers. Returns the identification,
+/// the offset in bytes and the "more fragments" flag.
+fn ipv6_fragment_header(first: IpNumber, mut extensions: &[u8]) -> Option<(u32, usize, bool)> {
+    let mut next = first;
+    loop {
+        let len = match next {
+            IpNumber::IPV6_FRAGMENTATION_HEADER => {
+                let header = extensions.get(..8)?;
+                let offset = u16::from_be_bytes([header[2], header[3]]) >> 3;
+                let more = header[3] & 1 == 1;
+                let id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+                return Some((id, offset as usize * 8, more));
+            }
+            IpNumber::AUTHENTICATION_HEADER => (*extensions.get(1)? as usize + 2) * 4,
+            IpNumber::IPV6_HEADER_HOP_BY_HOP
+            | IpNumber::IPV6_ROUTE_HEADER
+            | IpNumber::IPV6_DESTINATION_OPTIONS => (*extensions.get(1)? as usize + 1) * 8,
+            _ => return None,
+        };
+        next = IpNumber(*extensions.first()?);
+        extensions = extensions.get(len..)?;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FragmentsKey {
+    src: IpAddr,
+    dst: IpAddr,
+    protocol: IpNumber,
+    id: u32,
+}
+
+/// Received fragments of one IP packet
+#[derive(Debug)]
+struct Fragments {
+    /// Capture time of the first received fragment
+    first_seen_us: u64,
+    /// Order of appearance; the oldest packets are dropped first
+    seq: u64,
+    /// Fragments by their offset
+    parts: BTreeMap<usize, Vec<u8>>,
+    /// Length of the whole payload; known as soon as the last fragment is received
+    total: Option<usize>,
+    bytes: usize,
+}
+
+impl Fragments {
+    /// Returns the reassembled payload if there are no holes anymore
+    fn assemble(&self) -> Option<Vec<u8>> {
+        let total = self.total?;
+        let mut payload = Vec::with_capacity(total);
+        for (offset, part) in self.parts.iter() {
+            if *offset > payload.len() {
+                return None;
+            }
+            // overlapping bytes are taken from the earlier fragment
+            let overlap = payload.len() - offset;
+            if overlap < part.len() {
+                payload.extend_from_slice(&part[overlap..]);
+            }
+        }
+        if payload.len() < total {
+            return None;
+        }
+        payload.truncate(total);
+        Some(payload)
+    }
+}
+
+/// Reassembles IPv4 and IPv6 packets from their fragments. Incomplete packets are dropped
+/// after [`FRAGMENTS_TIMEOUT_US`] of capture time or if the pending fragments would exceed
+/// [`MAX_FRAGMENTS_BYTES`].
+#[derive(Debug, Default)]
+pub(crate) struct IpDefragmenter {
+    pending: HashMap<FragmentsKey, Fragments>,
+    bytes: usize,
+    next_seq: u64,
+    /// Latest known capture time
+    now_us: u64,
+    failures: DefragFailures,
+}
+
+impl IpDefragmenter {
+    /// Adds a fragment captured at `ts_us` (if known). Returns the protocol and the whole
+    /// payload of the IP packet as soon as all its fragments are received.
+    pub fn push(&mut self, fragment: Fragment, ts_us: Option<u64>) -> Option<(IpNumber, Vec<u8>)> {
+        if let Some(ts_us) = ts_us {
+            self.now_us = self.now_us.max(ts_us);
+            self.expire();
+        }
+        let Fragment {
+            key,
+            offset,
+            more,
+            payload,
+        } = fragment;
+        let end = offset + payload.len();
+        let next_seq = &mut self.next_seq;
+        let now_us = self.now_us;
+        let fragments = self.pending.entry(key).or_insert_with(|| {
+            *next_seq += 1;
+            Fragments {
+                first_seen_us: now_us,
+                seq: *next_seq,
+                parts: BTreeMap::new(),
+                total: None,
+                bytes: 0,
+            }
+        });
+        let invalid = end > MAX_PAYLOAD_LEN
+            || (!more && fragments.total.is_some_and(|total| total != end))
+            || fragments.total.is_some_and(|total| end > total)
+            || (!more
+                && fragments
+                    .parts
+                    .keys()
+                    .last()
+                    .is_some_and(|last| *last >= end));
+        if invalid {
+            warn!(
+                "{key:?}: invalid IP fragment at offset {offset} ({} bytes)",
+                payload.len()
+            );
+            self.drop_packet(&key);
+            self.failures.invalid += 1;
+            return None;
+        }
+        if !more {
+            fragments.total = Some(end);
+        }
+        if fragments
+            .parts
+            .get(&offset)
+            .map_or(true, |part| part.len() < payload.len())
+        {
+            if let Some(replaced) = fragments.parts.insert(offset, payload.to_vec()) {
+                fragments.bytes -= replaced.len();
+                self.bytes -= replaced.len();
+            }
+            fragments.bytes += payload.len();
+            self.bytes += payload.len();
+        }
+        if let Some(assembled) = fragments.assemble() {
+            self.drop_packet(&key);
+            return Some((key.protocol, assembled));
+        }
+        while self.bytes > MAX_FRAGMENTS_BYTES {
+            let Some(oldest) = self
+                .pending
+                .iter()
+                .min_by_key(|(_, fragments)| fragments.seq)
+                .map(|(key, _)| *key)
+            else {
+                break;
+            };
+            warn!("{oldest:?}: IP fragments dropped, memory limit for fragments is exceeded");
+            self.drop_packet(&oldest);
+            self.failures.evicted += 1;
+        }
+        None
+    }
+
+    /// Drops all incomplete packets; called at the end of the capture
+    pub fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            warn!(
+                "{} IP packets are incomplete at the end of the capture",
+                self.pending.len()
+            );
+            self.failures.incomplete += self.pending.len();
+        }
+        self.pending.clear();
+        self.bytes = 0;
+    }
+
+    pub fn failures(&self) -> &DefragFailures {
+        &self.failures
+    }
+
+    fn expire(&mut self) {
+        let now_us = self.now_us;
+        let bytes = &mut self.bytes;
+        let failures = &mut self.failures;
+        self.pending.retain(|key, fragments| {
+            if now_us.saturating_sub(fragments.first_seen_us) <= FRAGMENTS_TIMEOUT_US {
+                return true;
+            }
+            warn!("{key:?}: IP fragments dropped, missing fragments didn't arrive in time");
+            *bytes -= fragments.bytes;
+            failures.timed_out += 1;
+            false
+        });
+    }
+
+    fn drop_packet(&mut self, key: &FragmentsKey) {
+        if let Some(fragments) = self.pending.remove(key) {
+            self.bytes -= fragments.bytes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn fragment(id: u32, offset: usize, more: bool, payload: &[u8]) -> Fragment {
+        Fragment {
+            key: FragmentsKey {
+                src: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+                dst: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)),
+                protocol: IpNumber::UDP,
+                id,
+            },
+            offset,
+            more,
+            payload,
+        }
+    }
+
+    #[test]
+    fn test_defrag_out_of_order() {
+        let mut defrag = IpDefragmenter::default();
+        assert_eq!(defrag.push(fragment(1, 16, false, b"cc"), None), None);
+        assert_eq!(defrag.push(fragment(1, 0, true, b"aaaaaaaa"), None), None);
+        assert_eq!(
+            defrag.push(fragment(1, 8, true, b"bbbbbbbb"), None),
+            Some((IpNumber::UDP, b"aaaaaaaabbbbbbbbcc".to_vec()))
+        );
+        assert_eq!(defrag.bytes, 0);
+        assert_eq!(defrag.failures().total(), 0);
+    }
+
+    #[test]
+    fn test_defrag_timeout() {
+        let mut defrag = IpDefragmenter::default();
+        assert_eq!(
+            defrag.push(fragment(1, 0, true, b"aaaaaaaa"), Some(0)),
+            None
+        );
+        assert_eq!(
+            defrag.push(
+                fragment(2, 0, true, b"aaaaaaaa"),
+                Some(FRAGMENTS_TIMEOUT_US + 1)
+            ),
+            None
+        );
+        assert_eq!(defrag.failures().timed_out, 1);
+        // the first fragment is gone, so the packet cannot be completed
+        assert_eq!(defrag.push(fragment(1, 8, false, b"b"), None), None);
+        defrag.flush();
+        assert_eq!(defrag.failures().incomplete, 2);
+    }
+
+    #[test]
+    fn test_defrag_memory_limit() {
+        let mut defrag = IpDefragmenter::default();
+        let part = vec![0u8; 60_000];
+        let count = MAX_FRAGMENTS_BYTES / part.len() + 1;
+        for id in 0..count {
+            defrag.push(fragment(id as u32, 0, true, &part), None);
+        }
+        assert_eq!(defrag.failures().evicted, 1);
+        assert!(defrag.bytes <= MAX_FRAGMENTS_BYTES);
+    }
+
+    #[test]
+    fn test_defrag_invalid() {
+        let mut defrag = IpDefragmenter::default();
+        defrag.push(fragment(1, 8, false, b"bbbbbbbb"), None);
+        defrag.push(fragment(1, 16, true, b"cccccccc"), None);
+        assert_eq!(defrag.failures().invalid, 1);
+        assert!(defrag.pending.is_empty());
+    }
+}
@@ -1,16 +1,18 @@
 use crate::{
-    binary::pcap::debug_block, ByteSource, Error as SourceError, ReloadInfo, SourceFilter,
-    TransportProtocol,
+    binary::pcap::{debug_block, reassembly::Flows, ReassemblyStats},
+    ByteSource, Error as SourceError, ReloadInfo, SourceFilter,
 };
-use buf_redux::Buffer;
-use etherparse::{SlicedPacket, TransportSlice};
 use log::{debug, error, trace};
-use pcap_parser::{traits::PcapReaderIterator, LegacyPcapReader, PcapBlockOwned, PcapError};
+use pcap_parser::{
+    traits::PcapReaderIterator, LegacyPcapReader, Linktype, PcapBlockOwned, PcapError,
+};
 use std::io::Read;
 
 pub struct PcapLegacyByteSource<R: Read> {
     pcap_reader: LegacyPcapReader<R>,
-    buffer: Buffer,
+    flows: Flows,
+    /// Link type of all packets as given in the global header
+    linktype: Linktype,
     last_know_timestamp: Option<u64>,
     total: usize,
 }
@@ -20,22 +22,37 @@ impl<R: Read> PcapLegacyByteSource<R> {
         Ok(Self {
             pcap_reader: LegacyPcapReader::new(65536, reader)
                 .map_err(|e| SourceError::Setup(format!("{e}")))?,
-            buffer: Buffer::new(),
+            flows: Flows::new(),
+            linktype: Linktype::ETHERNET,
             last_know_timestamp: None,
             total: 0,
         })
     }
+
+    /// Problems found so far while reassembling the captured traffic
+    pub fn reassembly_stats(&self) -> ReassemblyStats {
+        self.flows.stats()
+    }
 }
 
-impl<R: Read + Send + Sync> ByteSource for PcapLegacyByteSource<R> {
+impl<R: Read + Send> ByteSource for PcapLegacyByteSource<R> {
     async fn load(
         &mut self,
         filter: Option<&SourceFilter>,
     ) -> Result<Option<ReloadInfo>, SourceError> {
-        let raw_data;
-        let mut consumed;
         let mut skipped = 0usize;
         loop {
+            if let Some((newly_loaded, dropped)) = self.flows.load() {
+                return Ok(Some(
+                    ReloadInfo::new(
+                        newly_loaded,
+                        self.flows.len(),
+                        skipped + dropped,
+                        self.last_know_timestamp,
+                    )
+                    .with_networks(self.flows.networks().to_vec()),
+                ));
+            }
             match self.pcap_reader.next() {
                 Ok((bytes_read, block)) => {
                     self.total += bytes_read;
@@ -44,28 +61,44 @@ impl<R: Read + Send + Sync> ByteSource for PcapLegacyByteSource<R> {
                         bytes_read,
                         self.total
                     );
-                    consumed = bytes_read;
-                    match block {
-                        PcapBlockOwned::LegacyHeader(ref _hdr) => {
-                            self.pcap_reader.consume(consumed);
+                    let (raw_data, ts_us) = match block {
+                        PcapBlockOwned::LegacyHeader(ref hdr) => {
+                            trace!("LegacyHeader: link type {:?}", hdr.network);
+                            self.linktype = hdr.network;
+                            self.pcap_reader.consume(bytes_read);
                             continue;
                         }
-                        PcapBlockOwned::Legacy(ref b) => {
-                            raw_data = &b.data[..b.origlen as usize];
-                            break;
-                        }
+                        PcapBlockOwned::Legacy(ref b) => (
+                            &b.data[..b.origlen as usize],
+                            Some(b.ts_sec as u64 * 1_000_000 + b.ts_usec as u64),
+                        ),
                         other_type => {
                             debug_block(other_type);
-                            skipped += consumed;
+                            skipped += bytes_read;
                             debug!("skipped in total {} bytes", skipped);
-                            self.pcap_reader.consume(consumed);
+                            self.pcap_reader.consume(bytes_read);
                             continue;
                         }
-                    }
+                    };
+                    let taken = self
+                        .flows
+                        .feed(self.linktype, None, raw_data, ts_us, filter);
+                    // payload is copied into flows and can be dropped by pcap reader
+                    trace!("consume {} processed bytes", bytes_read);
+                    self.pcap_reader.consume(bytes_read);
+                    skipped += bytes_read - taken?;
                 }
                 Err(PcapError::Eof) => {
                     debug!("reloading from pcap file, EOF");
-                    return Ok(None);
+                    return Ok(self.flows.flush().map(|(newly_loaded, dropped)| {
+                        ReloadInfo::new(
+                            newly_loaded,
+                            self.flows.len(),
+                            skipped + dropped,
+                            self.last_know_timestamp,
+                        )
+                        .with_networks(self.flows.networks().to_vec())
+                    }));
                 }
                 Err(PcapError::Incomplete(size)) => {
                     trace!("reloading from pcap file, Incomplete ({})", size);
@@ -81,79 +114,23 @@ impl<R: Read + Send + Sync> ByteSource for PcapLegacyByteSource<R> {
                 }
             }
         }
-        let res = match SlicedPacket::from_ethernet(raw_data) {
-            Ok(value) => {
-                let payload = match &value.transport {
-                    Some(TransportSlice::Icmpv4(slice)) => slice.payload(),
-                    Some(TransportSlice::Icmpv6(slice)) => slice.payload(),
-                    Some(TransportSlice::Udp(slice)) => slice.payload(),
-                    Some(TransportSlice::Tcp(slice)) => slice.payload(),
-                    None => {
-                        return Err(SourceError::Unrecoverable(format!(
-                            "ethernet frame with unknown payload: {:02X?}",
-                            raw_data
-                        )));
-                    }
-                };
-                skipped += consumed - payload.len();
-                match (value.transport, filter) {
-                    (
-                        Some(actual),
-                        Some(SourceFilter {
-                            transport: Some(wanted),
-                        }),
-                    ) => {
-                        let actual_tp: TransportProtocol = actual.into();
-                        let received_bytes = self.buffer.copy_from_slice(payload);
-                        let available_bytes = self.buffer.len();
-                        if actual_tp == *wanted {
-                            Ok(Some(ReloadInfo::new(
-                                received_bytes,
-                                available_bytes,
-                                skipped,
-                                self.last_know_timestamp,
-                            )))
-                        } else {
-                            Ok(Some(ReloadInfo::new(
-                                0,
-                                0,
-                                payload.len() + skipped,
-                                self.last_know_timestamp,
-                            )))
-                        }
-                    }
-                    _ => {
-                        let copied = self.buffer.copy_from_slice(payload);
-                        let available_bytes = self.buffer.len();
-                        Ok(Some(ReloadInfo::new(
-                            copied,
-                            available_bytes,
-                            skipped,
-                            self.last_know_timestamp,
-                        )))
-                    }
-                }
-            }
-            Err(e) => Err(SourceError::Unrecoverable(format!(
-                "error trying to extract data from ethernet frame: {e}"
-            ))),
-        };
-        // bytes are copied into buffer and can be dropped by pcap reader
-        trace!("consume {} processed bytes", consumed);
-        self.pcap_reader.consume(consumed);
-        res
     }
 
     fn current_slice(&self) -> &[u8] {
-        self.buffer.buf()
+        self.flows.buf()
     }
 
     fn consume(&mut self, offset: usize) {
-        self.buffer.consume(offset);
+        self.flows.consume(offset);
     }
 
     fn len(&self) -> usize {
-        self.buffer.len()
+        self.flows.len()
+    }
+
+    fn problems(&self) -> Option<String> {
+        let stats = self.reassembly_stats();
+        (!stats.is_empty()).then(|| stats.to_string())
     }
 }
 
@@ -239,4 +216,60 @@ mod tests {
 
         general_source_reload_test(&mut source).await;
     }
+
+    #[tokio::test]
+    async fn test_report_incomplete_fragments() {
+        use etherparse::{EtherType, Ethernet2Header, IpNumber, Ipv4Header};
+
+        // first fragment of an IPv4 packet; the rest of the packet is never captured
+        let payload = [0u8; 16];
+        let mut frame = Vec::new();
+        Ethernet2Header {
+            source: [1, 2, 3, 4, 5, 6],
+            destination: [7, 8, 9, 10, 11, 12],
+            ether_type: EtherType::IPV4,
+        }
+        .write(&mut frame)
+        .unwrap();
+        let mut ip = Ipv4Header::new(
+            payload.len() as u16,
+            20,
+            IpNumber::UDP,
+            [192, 168, 1, 1],
+            [192, 168, 1, 2],
+        )
+        .unwrap();
+        ip.identification = 7;
+        ip.more_fragments = true;
+        ip.write(&mut frame).unwrap();
+        frame.extend_from_slice(&payload);
+
+        let mut pcap = vec![
+            0xd4, 0xc3, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00,
+        ];
+        for field in [1, 0, frame.len() as u32, frame.len() as u32] {
+            pcap.extend_from_slice(&field.to_le_bytes());
+        }
+        pcap.extend_from_slice(&frame);
+
+        let mut source =
+            PcapLegacyByteSource::new(std::io::Cursor::new(pcap)).expect("cannot create source");
+        assert!(source.problems().is_none());
+        while source
+            .load(None)
+            .await
+            .expect("reload should work")
+            .is_some()
+        {
+            source.consume(source.len());
+        }
+        let stats = source.reassembly_stats();
+        assert_eq!(stats.tcp_lost_bytes, 0);
+        assert_eq!(stats.ip_fragments.incomplete, 1);
+        assert_eq!(stats.ip_fragments.total(), 1);
+        assert!(source
+            .problems()
+            .is_some_and(|problems| problems.contains("incomplete: 1")));
+    }
 }
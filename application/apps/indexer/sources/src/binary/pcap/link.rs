@@ -0,0 +1,156 @@
+use crate::Error as SourceError;
+use etherparse::{EtherType, SlicedPacket};
+use pcap_parser::Linktype;
+
+/// Length of the Linux cooked capture header (`LINKTYPE_LINUX_SLL`)
+const SLL_HEADER_LEN: usize = 16;
+/// Length of the Linux cooked capture v2 header (`LINKTYPE_LINUX_SLL2`)
+const SLL2_HEADER_LEN: usize = 20;
+/// Length of the Ethernet II header
+const ETHERNET_HEADER_LEN: usize = 14;
+/// Length of a VLAN tag (802.1Q, 802.1ad)
+const VLAN_TAG_LEN: usize = 4;
+
+/// Ether types of VLAN tags which can be stacked in front of the network layer
+const VLAN_ETHER_TYPES: [EtherType; 3] = [
+    EtherType::VLAN_TAGGED_FRAME,
+    EtherType::PROVIDER_BRIDGING,
+    EtherType::VLAN_DOUBLE_TAGGED_FRAME,
+];
+
+/// Slices a captured frame according to the link type of the interface it was captured on.
+/// Any number of stacked VLAN tags is skipped; the id of the outermost one is returned along
+/// with the packet.
+pub(crate) fn slice_frame(
+    linktype: Linktype,
+    data: &[u8],
+) -> Result<(SlicedPacket, Option<u16>), SourceError> {
+    let (ether_type, payload) = match linktype {
+        Linktype::RAW | Linktype::IPV4 | Linktype::IPV6 => {
+            return SlicedPacket::from_ip(data)
+                .map(|packet| (packet, None))
+                .map_err(|e| {
+                    SourceError::Unrecoverable(format!(
+                        "error trying to extract data from IP packet: {e}"
+                    ))
+                });
+        }
+        Linktype::ETHERNET => link_header(data, ETHERNET_HEADER_LEN, 12)?,
+        Linktype::LINUX_SLL => link_header(data, SLL_HEADER_LEN, 14)?,
+        Linktype::LINUX_SLL2 => link_header(data, SLL2_HEADER_LEN, 0)?,
+        other => {
+            return Err(SourceError::Unrecoverable(format!(
+                "unsupported link type of captured frames: {other:?}"
+            )));
+        }
+    };
+    let (ether_type, payload, vlan) = skip_vlan_tags(ether_type, payload)?;
+    SlicedPacket::from_ether_type(ether_type, payload)
+        .map(|packet| (packet, vlan))
+        .map_err(|e| {
+            SourceError::Unrecoverable(format!(
+                "error trying to extract data from {linktype:?} frame: {e}"
+            ))
+        })
+}
+
+/// Returns the ether type found at `type_offset` and the payload after the link layer header
+fn link_header(
+    data: &[u8],
+    header_len: usize,
+    type_offset: usize,
+) -> Result<(EtherType, &[u8]), SourceError> {
+    if data.len() < header_len {
+        return Err(SourceError::Unrecoverable(format!(
+            "frame is shorter than its link layer header: {:02X?}",
+            data
+        )));
+    }
+    let ether_type = u16::from_be_bytes([data[type_offset], data[type_offset + 1]]);
+    Ok((EtherType(ether_type), &data[header_len..]))
+}
+
+/// Returns the ether type and the payload after all VLAN tags and the id of the outermost tag
+fn skip_vlan_tags(
+    mut ether_type: EtherType,
+    mut payload: &[u8],
+) -> Result<(EtherType, &[u8], Option<u16>), SourceError> {
+    let mut vlan = None;
+    while VLAN_ETHER_TYPES.contains(&ether_type) {
+        if payload.len() < VLAN_TAG_LEN {
+            return Err(SourceError::Unrecoverable(format!(
+                "truncated VLAN tag: {:02X?}",
+                payload
+            )));
+        }
+        vlan.get_or_insert(u16::from_be_bytes([payload[0], payload[1]]) & 0x0fff);
+        ether_type = EtherType(u16::from_be_bytes([payload[2], payload[3]]));
+        payload = &payload[VLAN_TAG_LEN..];
+    }
+    Ok((ether_type, payload, vlan))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use etherparse::{PacketBuilder, TransportSlice};
+
+    const PAYLOAD: &[u8] = b"payload";
+
+    fn ip_packet() -> Vec<u8> {
+        let builder = PacketBuilder::ipv4([192, 168, 1, 1], [192, 168, 1, 2], 20).udp(1000, 2000);
+        let mut packet = Vec::with_capacity(builder.size(PAYLOAD.len()));
+        builder.write(&mut packet, PAYLOAD).unwrap();
+        packet
+    }
+
+    fn udp_payload<'a>(packet: &'a SlicedPacket) -> &'a [u8] {
+        match &packet.transport {
+            Some(TransportSlice::Udp(udp)) => udp.payload(),
+            other => panic!("unexpected transport: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_raw_ip() {
+        let data = ip_packet();
+        let (packet, vlan) = slice_frame(Linktype::RAW, &data).unwrap();
+        assert_eq!(vlan, None);
+        assert_eq!(udp_payload(&packet), PAYLOAD);
+    }
+
+    #[test]
+    fn test_linux_sll() {
+        let mut data = vec![0, 0, 0, 1, 0, 6, 1, 2, 3, 4, 5, 6, 0, 0, 0x08, 0x00];
+        data.extend(ip_packet());
+        let (packet, _) = slice_frame(Linktype::LINUX_SLL, &data).unwrap();
+        assert_eq!(udp_payload(&packet), PAYLOAD);
+    }
+
+    #[test]
+    fn test_linux_sll2() {
+        let mut data = vec![
+            0x08, 0x00, 0, 0, 0, 0, 0, 2, 0, 1, 0, 6, 1, 2, 3, 4, 5, 6, 0, 0,
+        ];
+        data.extend(ip_packet());
+        let (packet, _) = slice_frame(Linktype::LINUX_SLL2, &data).unwrap();
+        assert_eq!(udp_payload(&packet), PAYLOAD);
+    }
+
+    #[test]
+    fn test_stacked_vlan_tags() {
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 0x88, 0xa8];
+        data.extend([0x00, 0x01, 0x81, 0x00]);
+        data.extend([0x00, 0x02, 0x81, 0x00]);
+        data.extend([0x00, 0x03, 0x08, 0x00]);
+        data.extend(ip_packet());
+        let (packet, vlan) = slice_frame(Linktype::ETHERNET, &data).unwrap();
+        assert_eq!(vlan, Some(1));
+        assert_eq!(udp_payload(&packet), PAYLOAD);
+    }
+
+    #[test]
+    fn test_unsupported_link_type() {
+        assert!(slice_frame(Linktype::IEEE802_11, &ip_packet()).is_err());
+    }
+}
@@ -1,7 +1,46 @@
 use pcap_parser::PcapBlockOwned;
+use std::fmt;
 
+mod defrag;
 pub mod legacy;
+mod link;
 pub mod ng;
+mod reassembly;
+
+pub use defrag::DefragFailures;
+
+/// Problems found while reassembling the captured traffic. Affected payload never reaches
+/// the parser.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReassemblyStats {
+    /// Count of bytes which are missing in TCP streams and were skipped
+    pub tcp_lost_bytes: u64,
+    /// IP packets which were dropped, because they couldn't be reassembled from fragments
+    pub ip_fragments: DefragFailures,
+}
+
+impl ReassemblyStats {
+    pub fn is_empty(&self) -> bool {
+        self.tcp_lost_bytes == 0 && self.ip_fragments.total() == 0
+    }
+}
+
+impl fmt::Display for ReassemblyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fragments = &self.ip_fragments;
+        write!(
+            f,
+            "Captured traffic is incomplete: {} byte(s) lost in TCP streams; {} IP packet(s) \
+            dropped (timed out: {}, evicted: {}, invalid: {}, incomplete: {})",
+            self.tcp_lost_bytes,
+            fragments.total(),
+            fragments.timed_out,
+            fragments.evicted,
+            fragments.invalid,
+            fragments.incomplete
+        )
+    }
+}
 
 fn debug_block(b: PcapBlockOwned) {
     match b {
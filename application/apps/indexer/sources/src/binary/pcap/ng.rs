@@ -1,16 +1,45 @@
 use crate::{
-    binary::pcap::debug_block, ByteSource, Error as SourceError, ReloadInfo, SourceFilter,
-    TransportProtocol,
+    binary::pcap::{debug_block, reassembly::Flows, ReassemblyStats},
+    ByteSource, Error as SourceError, ReloadInfo, SourceFilter,
 };
-use buf_redux::Buffer;
-use etherparse::{SlicedPacket, TransportSlice};
 use log::{debug, error, trace};
-use pcap_parser::{traits::PcapReaderIterator, PcapBlockOwned, PcapError, PcapNGReader};
+use pcap_parser::{traits::PcapReaderIterator, Linktype, PcapBlockOwned, PcapError, PcapNGReader};
 use std::io::Read;
 
+/// Interface as described by an Interface Description Block
+#[derive(Debug, Clone, Copy)]
+struct Interface {
+    linktype: Linktype,
+    /// Resolution of timestamps (`if_tsresol`): if the most significant bit is zero, the
+    /// remaining bits are a negative power of 10, otherwise a negative power of 2 of seconds
+    ts_resolution: u8,
+    /// Offset of timestamps in seconds (`if_tsoffset`)
+    ts_offset: i64,
+}
+
+impl Interface {
+    /// Converts a raw timestamp of a packet into microseconds since the epoch
+    fn ts_us(&self, ts_high: u32, ts_low: u32) -> u64 {
+        let raw = ((ts_high as u64) << 32 | ts_low as u64) as u128;
+        let exponent = (self.ts_resolution & 0x7f) as u32;
+        let ts_us = if self.ts_resolution & 0x80 == 0 {
+            match exponent.checked_sub(6) {
+                Some(diff) => raw / 10u128.pow(diff.min(38)),
+                None => raw * 10u128.pow(6 - exponent),
+            }
+        } else {
+            (raw * 1_000_000) >> exponent
+        };
+        let ts_us = ts_us as i128 + self.ts_offset as i128 * 1_000_000;
+        ts_us.clamp(0, u64::MAX as i128) as u64
+    }
+}
+
 pub struct PcapngByteSource<R: Read> {
     pcapng_reader: PcapNGReader<R>,
-    buffer: Buffer,
+    flows: Flows,
+    /// Interfaces of the current section by interface id
+    interfaces: Vec<Interface>,
     last_know_timestamp: Option<u64>,
     total: usize,
 }
@@ -20,22 +49,37 @@ impl<R: Read> PcapngByteSource<R> {
         Ok(Self {
             pcapng_reader: PcapNGReader::new(65536, reader)
                 .map_err(|e| SourceError::Setup(format!("{e}")))?,
-            buffer: Buffer::new(),
+            flows: Flows::new(),
+            interfaces: Vec::new(),
             last_know_timestamp: None,
             total: 0,
         })
     }
+
+    /// Problems found so far while reassembling the captured traffic
+    pub fn reassembly_stats(&self) -> ReassemblyStats {
+        self.flows.stats()
+    }
 }
 
-impl<R: Read + Send + Sync> ByteSource for PcapngByteSource<R> {
+impl<R: Read + Send> ByteSource for PcapngByteSource<R> {
     async fn load(
         &mut self,
         filter: Option<&SourceFilter>,
     ) -> Result<Option<ReloadInfo>, SourceError> {
-        let raw_data;
-        let mut consumed;
         let mut skipped = 0usize;
         loop {
+            if let Some((newly_loaded, dropped)) = self.flows.load() {
+                return Ok(Some(
+                    ReloadInfo::new(
+                        newly_loaded,
+                        self.flows.len(),
+                        skipped + dropped,
+                        self.last_know_timestamp,
+                    )
+                    .with_networks(self.flows.networks().to_vec()),
+                ));
+            }
             match self.pcapng_reader.next() {
                 Ok((bytes_read, block)) => {
                     self.total += bytes_read;
@@ -44,32 +88,76 @@ impl<R: Read + Send + Sync> ByteSource for PcapngByteSource<R> {
                         bytes_read,
                         self.total
                     );
-                    consumed = bytes_read;
-                    match block {
+                    let (linktype, if_id, raw_data, ts_us) = match block {
                         PcapBlockOwned::NG(pcap_parser::Block::EnhancedPacket(ref epb)) => {
                             trace!("Enhanced package");
-                            let ts_us: u64 = (epb.ts_high as u64) << 32 | epb.ts_low as u64;
+                            let interface = interface(&self.interfaces, epb.if_id)?;
+                            let ts_us = interface.ts_us(epb.ts_high, epb.ts_low);
                             self.last_know_timestamp = Some(ts_us / 1000);
-                            raw_data = &epb.data[..epb.caplen as usize];
-                            break;
+                            (
+                                interface.linktype,
+                                epb.if_id,
+                                &epb.data[..epb.caplen as usize],
+                                Some(ts_us),
+                            )
                         }
                         PcapBlockOwned::NG(pcap_parser::Block::SimplePacket(ref spb)) => {
                             trace!("SimplePacket");
-                            raw_data = &spb.data[..spb.origlen as usize];
-                            break;
+                            // simple packets are always captured on the first interface
+                            (
+                                interface(&self.interfaces, 0)?.linktype,
+                                0,
+                                &spb.data[..spb.origlen as usize],
+                                None,
+                            )
+                        }
+                        PcapBlockOwned::NG(pcap_parser::Block::SectionHeader(_)) => {
+                            trace!("NG SectionHeader");
+                            // interface ids are local to a section
+                            self.interfaces.clear();
+                            skipped += bytes_read;
+                            self.pcapng_reader.consume(bytes_read);
+                            continue;
+                        }
+                        PcapBlockOwned::NG(pcap_parser::Block::InterfaceDescription(ref idb)) => {
+                            let interface = Interface {
+                                linktype: idb.linktype,
+                                ts_resolution: idb.if_tsresol,
+                                ts_offset: idb.if_tsoffset,
+                            };
+                            trace!("NG InterfaceDescription: {:?}", interface);
+                            self.interfaces.push(interface);
+                            skipped += bytes_read;
+                            self.pcapng_reader.consume(bytes_read);
+                            continue;
                         }
                         other_type => {
                             debug_block(other_type);
-                            skipped += consumed;
+                            skipped += bytes_read;
                             debug!("skipped in total {} bytes", skipped);
-                            self.pcapng_reader.consume(consumed);
+                            self.pcapng_reader.consume(bytes_read);
                             continue;
                         }
-                    }
+                    };
+                    let taken = self
+                        .flows
+                        .feed(linktype, Some(if_id), raw_data, ts_us, filter);
+                    // payload is copied into flows and can be dropped by pcap reader
+                    trace!("consume {} processed bytes", bytes_read);
+                    self.pcapng_reader.consume(bytes_read);
+                    skipped += bytes_read - taken?;
                 }
                 Err(PcapError::Eof) => {
                     debug!("reloading from pcap file, EOF");
-                    return Ok(None);
+                    return Ok(self.flows.flush().map(|(newly_loaded, dropped)| {
+                        ReloadInfo::new(
+                            newly_loaded,
+                            self.flows.len(),
+                            skipped + dropped,
+                            self.last_know_timestamp,
+                        )
+                        .with_networks(self.flows.networks().to_vec())
+                    }));
                 }
                 Err(PcapError::Incomplete(size)) => {
                     trace!("reloading from pcap file, Incomplete ({})", size);
@@ -85,90 +173,42 @@ impl<R: Read + Send + Sync> ByteSource for PcapngByteSource<R> {
                 }
             }
         }
-        let res = match SlicedPacket::from_ethernet(raw_data) {
-            Ok(value) => {
-                let payload = match &value.transport {
-                    Some(TransportSlice::Icmpv4(slice)) => slice.payload(),
-                    Some(TransportSlice::Icmpv6(slice)) => slice.payload(),
-                    Some(TransportSlice::Udp(slice)) => slice.payload(),
-                    Some(TransportSlice::Tcp(slice)) => slice.payload(),
-                    None => {
-                        return Err(SourceError::Unrecoverable(format!(
-                            "ethernet frame with unknown payload: {:02X?}",
-                            raw_data
-                        )));
-                    }
-                };
-                skipped += consumed - payload.len();
-                match (value.transport, filter) {
-                    (
-                        Some(actual),
-                        Some(SourceFilter {
-                            transport: Some(wanted),
-                        }),
-                    ) => {
-                        let actual_tp: TransportProtocol = actual.into();
-                        let received_bytes = self.buffer.copy_from_slice(payload);
-                        let available_bytes = self.buffer.len();
-                        if actual_tp == *wanted {
-                            Ok(Some(ReloadInfo::new(
-                                received_bytes,
-                                available_bytes,
-                                skipped,
-                                self.last_know_timestamp,
-                            )))
-                        } else {
-                            Ok(Some(ReloadInfo::new(
-                                0,
-                                0,
-                                payload.len() + skipped,
-                                self.last_know_timestamp,
-                            )))
-                        }
-                    }
-                    _ => {
-                        let copied = self.buffer.copy_from_slice(payload);
-                        let available_bytes = self.buffer.len();
-                        Ok(Some(ReloadInfo::new(
-                            copied,
-                            available_bytes,
-                            skipped,
-                            self.last_know_timestamp,
-                        )))
-                    }
-                }
-            }
-            Err(e) => Err(SourceError::Unrecoverable(format!(
-                "error trying to extract data from ethernet frame: {e}"
-            ))),
-        };
-        // bytes are copied into buffer and can be dropped by pcap reader
-        trace!("consume {} processed bytes", consumed);
-        self.pcapng_reader.consume(consumed);
-        res
     }
 
     fn current_slice(&self) -> &[u8] {
-        self.buffer.buf()
+        self.flows.buf()
     }
 
     fn consume(&mut self, offset: usize) {
-        self.buffer.consume(offset);
+        self.flows.consume(offset);
     }
 
     fn len(&self) -> usize {
-        self.buffer.len()
+        self.flows.len()
+    }
+
+    fn problems(&self) -> Option<String> {
+        let stats = self.reassembly_stats();
+        (!stats.is_empty()).then(|| stats.to_string())
     }
 }
 
+fn interface(interfaces: &[Interface], if_id: u32) -> Result<Interface, SourceError> {
+    interfaces
+        .get(if_id as usize)
+        .copied()
+        .ok_or_else(|| SourceError::Unrecoverable(format!("packet of unknown interface {if_id}")))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        binary::pcap::ng::PcapngByteSource,
+        binary::pcap::ng::{Interface, PcapngByteSource},
         tests::{general_source_reload_test, mock_read::MockRepeatRead},
         ByteSource,
     };
     use env_logger;
+    use pcap_parser::Linktype;
 
     const SAMPLE_PCAPNG_DATA: &[u8] = &[
         /*0*/
@@ -233,4 +273,45 @@ mod tests {
 
         general_source_reload_test(&mut source).await;
     }
+
+    #[test]
+    fn test_timestamp_resolution() {
+        let interface = |ts_resolution, ts_offset| Interface {
+            linktype: Linktype::ETHERNET,
+            ts_resolution,
+            ts_offset,
+        };
+        let raw: u64 = 1_700_000_000_123_456_789;
+        let (high, low) = ((raw >> 32) as u32, raw as u32);
+        // nanoseconds
+        assert_eq!(interface(9, 0).ts_us(high, low), 1_700_000_000_123_456);
+        // microseconds (default)
+        let (high, low) = (0, 1_500_000);
+        assert_eq!(interface(6, 0).ts_us(high, low), 1_500_000);
+        // milliseconds with offset of one hour
+        assert_eq!(interface(3, 3600).ts_us(high, low), 5_100_000_000);
+        // 2^-10 seconds
+        assert_eq!(interface(0x80 | 10, 0).ts_us(0, 2048), 2_000_000);
+        // negative offset is clamped
+        assert_eq!(interface(6, -10).ts_us(0, 1), 0);
+    }
+
+    #[tokio::test]
+    async fn test_nanosecond_timestamps() {
+        let mut data = SAMPLE_PCAPNG_DATA.to_vec();
+        // if_tsresol option with nanoseconds in the interface description block
+        let options: &[u8] = &[
+            0x09, 0x00, 0x01, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        data.splice(44..44, options.iter().copied());
+        data[32] += options.len() as u8;
+        data[44 + options.len()] += options.len() as u8;
+        let raw: u64 = 1_700_000_000_123_456_789;
+        let epb = 48 + options.len();
+        data[epb + 12..epb + 16].copy_from_slice(&((raw >> 32) as u32).to_le_bytes());
+        data[epb + 16..epb + 20].copy_from_slice(&(raw as u32).to_le_bytes());
+        let mut source = PcapngByteSource::new(std::io::Cursor::new(data)).unwrap();
+        let reload_info = source.load(None).await.unwrap().unwrap();
+        assert_eq!(reload_info.last_known_ts, Some(1_700_000_000_123));
+    }
 }
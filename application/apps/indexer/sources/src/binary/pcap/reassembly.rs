@@ -0,0 +1,878 @@
+use super::{
+    defrag::{Fragment, IpDefragmenter},
+    link::slice_frame,
+    ReassemblyStats,
+};
+use crate::{Error as SourceError, SourceFilter, TransportProtocol};
+use buf_redux::Buffer;
+use etherparse::{IpNumber, NetSlice, TcpSlice, TransportSlice, UdpSlice};
+use log::{trace, warn};
+use parsers::NetworkMeta;
+use pcap_parser::Linktype;
+use std::{
+    collections::{BTreeMap, HashMap},
+    mem,
+    net::IpAddr,
+};
+
+/// Maximum amount of out-of-order bytes of one TCP connection. If more bytes are waiting for
+/// a missing segment, the segment is considered lost and the gap is skipped.
+const MAX_PENDING_BYTES: usize = 4 * 1024 * 1024;
+
+/// Identifies a flow of payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum FlowKey {
+    /// Datagrams (UDP, ICMP) sent from one endpoint to another. Their payload is complete on
+    /// its own and is passed in the order of capturing. ICMP has no ports (`0`).
+    Datagrams {
+        src: Option<(IpAddr, u16)>,
+        dst: Option<(IpAddr, u16)>,
+    },
+    /// One direction of a TCP connection
+    Tcp {
+        src: (IpAddr, u16),
+        dst: (IpAddr, u16),
+    },
+}
+
+impl FlowKey {
+    /// Flow of datagrams between the endpoints of `network`
+    fn datagrams(network: Option<&NetworkMeta>) -> Self {
+        Self::Datagrams {
+            src: network.map(|network| (network.source, network.source_port.unwrap_or(0))),
+            dst: network
+                .map(|network| (network.destination, network.destination_port.unwrap_or(0))),
+        }
+    }
+}
+
+/// Reassembles the payload of a TCP connection (one direction) into an ordered byte stream.
+/// Retransmitted and overlapping bytes are dropped.
+#[derive(Debug, Default)]
+struct TcpReassembler {
+    /// Sequence number of the next expected byte
+    next_seq: Option<u32>,
+    /// Offset of the next expected byte in the stream
+    offset: u64,
+    /// Out-of-order segments by their offset in the stream
+    pending: BTreeMap<u64, Vec<u8>>,
+    pending_bytes: usize,
+}
+
+/// Result of pushing a segment into [`TcpReassembler`]
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Pushed {
+    /// Count of bytes which were already received before
+    duplicated: usize,
+    /// Count of bytes which were never received and skipped
+    lost: u64,
+}
+
+impl TcpReassembler {
+    /// Pushes a segment; all bytes which are in order now are appended to `ready`.
+    fn push(&mut self, seq: u32, syn: bool, payload: &[u8], ready: &mut Vec<u8>) -> Pushed {
+        let mut pushed = Pushed::default();
+        let seq = if syn {
+            // SYN occupies one sequence number. It also starts a new connection on
+            // reused ports.
+            if self
+                .next_seq
+                .is_some_and(|next| next != seq.wrapping_add(1))
+            {
+                *self = Self::default();
+            }
+            seq.wrapping_add(1)
+        } else {
+            seq
+        };
+        let next_seq = *self.next_seq.get_or_insert(seq);
+        if payload.is_empty() {
+            return pushed;
+        }
+        // Interpret the distance as signed to handle wrapping of sequence numbers
+        let distance = seq.wrapping_sub(next_seq) as i32 as i64;
+        let start = self.offset as i64 + distance;
+        let end = start + payload.len() as i64;
+        if end <= self.offset as i64 {
+            pushed.duplicated += payload.len();
+        } else if start <= self.offset as i64 {
+            let overlap = (self.offset as i64 - start) as usize;
+            pushed.duplicated += overlap;
+            self.advance(&payload[overlap..], ready);
+            pushed.duplicated += self.drain(ready);
+        } else {
+            let start = start as u64;
+            match self.pending.get(&start) {
+                Some(existing) if existing.len() >= payload.len() => {
+                    pushed.duplicated += payload.len();
+                }
+                _ => {
+                    if let Some(replaced) = self.pending.insert(start, payload.to_vec()) {
+                        pushed.duplicated += replaced.len();
+                        self.pending_bytes -= replaced.len();
+                    }
+                    self.pending_bytes += payload.len();
+                }
+            }
+            if self.pending_bytes > MAX_PENDING_BYTES {
+                let (lost, duplicated) = self.skip_gap(ready);
+                pushed.lost += lost;
+                pushed.duplicated += duplicated;
+            }
+        }
+        pushed
+    }
+
+    /// Skips all missing segments and delivers everything which is pending. Returns the count
+    /// of lost and duplicated bytes.
+    fn flush(&mut self, ready: &mut Vec<u8>) -> (u64, usize) {
+        let (mut lost, mut duplicated) = (0, 0);
+        while !self.pending.is_empty() {
+            let (gap, dups) = self.skip_gap(ready);
+            lost += gap;
+            duplicated += dups;
+        }
+        (lost, duplicated)
+    }
+
+    /// Skips the missing bytes in front of the first pending segment and delivers all
+    /// segments which are in order after that.
+    fn skip_gap(&mut self, ready: &mut Vec<u8>) -> (u64, usize) {
+        let Some(first) = self.pending.keys().next().copied() else {
+            return (0, 0);
+        };
+        let gap = first.saturating_sub(self.offset);
+        self.offset += gap;
+        self.next_seq = self.next_seq.map(|seq| seq.wrapping_add(gap as u32));
+        (gap, self.drain(ready))
+    }
+
+    fn advance(&mut self, bytes: &[u8], ready: &mut Vec<u8>) {
+        ready.extend_from_slice(bytes);
+        self.offset += bytes.len() as u64;
+        self.next_seq = self
+            .next_seq
+            .map(|seq| seq.wrapping_add(bytes.len() as u32));
+    }
+
+    /// Delivers pending segments which became in order. Returns the count of duplicated bytes.
+    fn drain(&mut self, ready: &mut Vec<u8>) -> usize {
+        let mut duplicated = 0;
+        while let Some(entry) = self.pending.first_entry() {
+            let start = *entry.key();
+            if start > self.offset {
+                break;
+            }
+            let segment = entry.remove();
+            self.pending_bytes -= segment.len();
+            let overlap = ((self.offset - start) as usize).min(segment.len());
+            duplicated += overlap;
+            self.advance(&segment[overlap..], ready);
+        }
+        duplicated
+    }
+}
+
+/// Header fields of a TCP segment which are needed for reassembly
+#[derive(Debug, Clone, Copy)]
+struct TcpSegment {
+    seq: u32,
+    syn: bool,
+    fin: bool,
+    rst: bool,
+}
+
+impl From<&TcpSlice<'_>> for TcpSegment {
+    fn from(tcp: &TcpSlice<'_>) -> Self {
+        Self {
+            seq: tcp.sequence_number(),
+            syn: tcp.syn(),
+            fin: tcp.fin(),
+            rst: tcp.rst(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Flow {
+    /// Order of appearance; used to pick flows in a stable order
+    id: usize,
+    /// Reassembled bytes which weren't moved to the buffer yet
+    ready: Vec<u8>,
+    /// Bytes which were moved to the buffer, but were not consumed before the buffer was
+    /// switched to another flow
+    leftover: Vec<u8>,
+    tcp: Option<TcpReassembler>,
+    closed: bool,
+    /// Network metadata of the last packet of the flow
+    network: Option<NetworkMeta>,
+    /// Network metadata of the packets of the ready bytes by their offset
+    ready_networks: Vec<(usize, NetworkMeta)>,
+}
+
+impl Flow {
+    fn is_empty(&self) -> bool {
+        self.ready.is_empty() && self.leftover.is_empty()
+    }
+}
+
+/// Demultiplexes the payload of captured frames into flows and feeds one flow at a time
+/// into the buffer. The payload of TCP connections is reassembled, so the parser gets the
+/// ordered byte stream of each connection separately. Fragmented IP packets are reassembled
+/// before.
+pub(crate) struct Flows {
+    flows: HashMap<FlowKey, Flow>,
+    /// Flow which is currently loaded into the buffer
+    active: Option<FlowKey>,
+    /// Flow which was fed last
+    last: Option<FlowKey>,
+    next_id: usize,
+    buffer: Buffer,
+    defrag: IpDefragmenter,
+    /// Count of bytes which are missing in TCP streams
+    tcp_lost_bytes: u64,
+    /// Network metadata of the bytes moved into the buffer by the last load
+    loaded_networks: Vec<(usize, NetworkMeta)>,
+}
+
+impl Flows {
+    pub fn new() -> Self {
+        Self {
+            flows: HashMap::new(),
+            active: None,
+            last: None,
+            next_id: 0,
+            buffer: Buffer::new(),
+            defrag: IpDefragmenter::default(),
+            tcp_lost_bytes: 0,
+            loaded_networks: Vec::new(),
+        }
+    }
+
+    /// Network metadata of the bytes moved into the buffer by the last load. Each entry
+    /// applies from its offset within the newly loaded bytes up to the next entry.
+    pub fn networks(&self) -> &[(usize, NetworkMeta)] {
+        &self.loaded_networks
+    }
+
+    pub fn stats(&self) -> ReassemblyStats {
+        ReassemblyStats {
+            tcp_lost_bytes: self.tcp_lost_bytes,
+            ip_fragments: *self.defrag.failures(),
+        }
+    }
+
+    pub fn buf(&self) -> &[u8] {
+        self.buffer.buf()
+    }
+
+    pub fn consume(&mut self, offset: usize) {
+        self.buffer.consume(offset);
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Feeds the payload of the frame `raw_data` with the link type `linktype`, captured on
+    /// `interface` at `ts_us` (if known), into its flow. Fragments of IP packets are held back
+    /// until the packet is complete. Returns the count of payload bytes which were taken.
+    pub fn feed(
+        &mut self,
+        linktype: Linktype,
+        interface: Option<u32>,
+        raw_data: &[u8],
+        ts_us: Option<u64>,
+        filter: Option<&SourceFilter>,
+    ) -> Result<usize, SourceError> {
+        let (packet, vlan) = slice_frame(linktype, raw_data)?;
+        let network = |(source, destination): (IpAddr, IpAddr)| NetworkMeta {
+            source,
+            source_port: None,
+            destination,
+            destination_port: None,
+            vlan,
+            interface,
+        };
+        if let Some(fragment) = packet.net.as_ref().and_then(Fragment::from_net) {
+            let network = network(fragment.addrs());
+            let len = fragment.len();
+            if let Some((protocol, payload)) = self.defrag.push(fragment, ts_us) {
+                self.feed_reassembled(network, protocol, &payload, filter);
+            }
+            return Ok(len);
+        }
+        let network = packet.net.as_ref().and_then(ip_addrs).map(network);
+        let (key, segment, payload, network) = match (&packet.net, &packet.transport) {
+            (Some(net), Some(TransportSlice::Tcp(tcp))) => {
+                let (src, dst) = ip_addrs(net).ok_or_else(|| {
+                    SourceError::Unrecoverable(format!(
+                        "TCP segment without IP header: {:02X?}",
+                        raw_data
+                    ))
+                })?;
+                (
+                    FlowKey::Tcp {
+                        src: (src, tcp.source_port()),
+                        dst: (dst, tcp.destination_port()),
+                    },
+                    Some(TcpSegment::from(tcp)),
+                    tcp.payload(),
+                    network.map(|network| {
+                        network.with_ports(tcp.source_port(), tcp.destination_port())
+                    }),
+                )
+            }
+            (_, Some(TransportSlice::Udp(udp))) => {
+                let network = network
+                    .map(|network| network.with_ports(udp.source_port(), udp.destination_port()));
+                (
+                    FlowKey::datagrams(network.as_ref()),
+                    None,
+                    udp.payload(),
+                    network,
+                )
+            }
+            (_, Some(TransportSlice::Icmpv4(icmp))) => (
+                FlowKey::datagrams(network.as_ref()),
+                None,
+                icmp.payload(),
+                network,
+            ),
+            (_, Some(TransportSlice::Icmpv6(icmp))) => (
+                FlowKey::datagrams(network.as_ref()),
+                None,
+                icmp.payload(),
+                network,
+            ),
+            (_, None) => {
+                return Err(SourceError::Unrecoverable(format!(
+                    "frame with unknown payload: {:02X?}",
+                    raw_data
+                )));
+            }
+        };
+        if let (
+            Some(SourceFilter {
+                transport: Some(wanted),
+                ..
+            }),
+            Some(actual),
+        ) = (filter, &packet.transport)
+        {
+            if TransportProtocol::from(actual.clone()) != *wanted {
+                return Ok(0);
+            }
+        }
+        Ok(self.feed_payload(key, segment, payload, network))
+    }
+
+    /// Feeds the transport payload of an IP packet which was reassembled from fragments
+    fn feed_reassembled(
+        &mut self,
+        network: NetworkMeta,
+        protocol: IpNumber,
+        payload: &[u8],
+        filter: Option<&SourceFilter>,
+    ) {
+        let transport = match protocol {
+            IpNumber::TCP => TransportProtocol::TCP,
+            IpNumber::UDP => TransportProtocol::UDP,
+            _ => TransportProtocol::Unknown,
+        };
+        if filter.is_some_and(|filter| filter.transport.is_some_and(|wanted| wanted != transport)) {
+            return;
+        }
+        match transport {
+            TransportProtocol::TCP => match TcpSlice::from_slice(payload) {
+                Ok(tcp) => {
+                    let key = FlowKey::Tcp {
+                        src: (network.source, tcp.source_port()),
+                        dst: (network.destination, tcp.destination_port()),
+                    };
+                    let network = network.with_ports(tcp.source_port(), tcp.destination_port());
+                    self.feed_payload(
+                        key,
+                        Some(TcpSegment::from(&tcp)),
+                        tcp.payload(),
+                        Some(network),
+                    );
+                }
+                Err(e) => warn!("reassembled IP packet with invalid TCP segment: {e}"),
+            },
+            TransportProtocol::UDP => match UdpSlice::from_slice(payload) {
+                Ok(udp) => {
+                    let network = network.with_ports(udp.source_port(), udp.destination_port());
+                    self.feed_payload(
+                        FlowKey::datagrams(Some(&network)),
+                        None,
+                        udp.payload(),
+                        Some(network),
+                    );
+                }
+                Err(e) => warn!("reassembled IP packet with invalid UDP datagram: {e}"),
+            },
+            TransportProtocol::Unknown => {
+                trace!("reassembled IP packet with protocol {protocol:?} is ignored");
+            }
+        }
+    }
+
+    /// Appends the payload to its flow. Returns the count of bytes which were taken.
+    fn feed_payload(
+        &mut self,
+        key: FlowKey,
+        segment: Option<TcpSegment>,
+        payload: &[u8],
+        network: Option<NetworkMeta>,
+    ) -> usize {
+        let next_id = &mut self.next_id;
+        let flow = self.flows.entry(key).or_insert_with(|| {
+            *next_id += 1;
+            Flow {
+                id: *next_id,
+                ready: Vec::new(),
+                leftover: Vec::new(),
+                tcp: None,
+                closed: false,
+                network: None,
+                ready_networks: Vec::new(),
+            }
+        });
+        self.last = Some(key);
+        if let Some(network) = network {
+            if flow.ready_networks.last().map(|(_, last)| last) != Some(&network) {
+                flow.ready_networks
+                    .push((flow.ready.len(), network.clone()));
+            }
+            flow.network = Some(network);
+        }
+        let Some(segment) = segment else {
+            flow.ready.extend_from_slice(payload);
+            return payload.len();
+        };
+        let reassembler = flow.tcp.get_or_insert_with(TcpReassembler::default);
+        let pushed = reassembler.push(segment.seq, segment.syn, payload, &mut flow.ready);
+        if pushed.lost > 0 {
+            warn!("{key:?}: {} bytes are missing in TCP stream", pushed.lost);
+        }
+        let mut lost = pushed.lost;
+        if segment.syn {
+            flow.closed = false;
+        }
+        if segment.fin || segment.rst {
+            let (gap, _) = reassembler.flush(&mut flow.ready);
+            if gap > 0 {
+                warn!("{key:?}: {gap} bytes are missing in closed TCP stream");
+            }
+            lost += gap;
+            flow.closed = true;
+        }
+        self.tcp_lost_bytes += lost;
+        payload.len().saturating_sub(pushed.duplicated)
+    }
+
+    /// Moves reassembled bytes into the buffer. Bytes of the active flow are appended; if the
+    /// active flow has nothing new, the buffer is switched to another flow with new bytes.
+    /// Returns the count of newly loaded and skipped bytes, or `None` if nothing is ready.
+    pub fn load(&mut self) -> Option<(usize, usize)> {
+        if let Some(loaded) = self.load_active() {
+            return Some((loaded, 0));
+        }
+        let next = self
+            .last
+            .filter(|key| {
+                self.flows
+                    .get(key)
+                    .is_some_and(|flow| !flow.ready.is_empty())
+            })
+            .or_else(|| self.next_flow())?;
+        // Unconsumed bytes of the active flow are kept until the flow gets new data
+        self.switch_to(next, false)
+    }
+
+    /// Delivers everything which is left after the end of the input. Gaps in TCP streams are
+    /// skipped; incomplete bytes of flows which cannot get new data are dropped.
+    pub fn flush(&mut self) -> Option<(usize, usize)> {
+        for (key, flow) in self.flows.iter_mut() {
+            if let Some(reassembler) = flow.tcp.as_mut() {
+                let (lost, _) = reassembler.flush(&mut flow.ready);
+                if lost > 0 {
+                    warn!("{key:?}: {lost} bytes are missing in TCP stream");
+                }
+                self.tcp_lost_bytes += lost;
+            }
+        }
+        self.defrag.flush();
+        if let Some(loaded) = self.load_active() {
+            return Some((loaded, 0));
+        }
+        let next = self.next_flow()?;
+        self.switch_to(next, true)
+    }
+
+    /// Appends new bytes of the active flow to the buffer
+    fn load_active(&mut self) -> Option<usize> {
+        let flow = self.flows.get_mut(&self.active?)?;
+        if flow.ready.is_empty() {
+            return None;
+        }
+        self.loaded_networks = mem::take(&mut flow.ready_networks);
+        let ready = mem::take(&mut flow.ready);
+        self.buffer.reserve(ready.len());
+        Some(self.buffer.copy_from_slice(&ready))
+    }
+
+    /// Picks the earliest flow (besides the active one) which has new bytes
+    fn next_flow(&self) -> Option<FlowKey> {
+        self.flows
+            .iter()
+            .filter(|(key, flow)| Some(**key) != self.active && !flow.ready.is_empty())
+            .min_by_key(|(_, flow)| flow.id)
+            .map(|(key, _)| *key)
+    }
+
+    fn switch_to(&mut self, next: FlowKey, drop_leftover: bool) -> Option<(usize, usize)> {
+        let mut skipped = 0;
+        if let Some(active) = self.active.take() {
+            let unconsumed = self.buffer.buf().to_vec();
+            self.buffer.clear();
+            let finished = self.flows.get_mut(&active).is_some_and(|flow| {
+                if drop_leftover || flow.closed {
+                    skipped += unconsumed.len();
+                } else {
+                    flow.leftover = unconsumed;
+                }
+                flow.closed && flow.is_empty()
+            });
+            if finished {
+                self.flows.remove(&active);
+            }
+        }
+        let flow = self.flows.get_mut(&next)?;
+        trace!("switch to flow {next:?}");
+        let mut bytes = mem::take(&mut flow.leftover);
+        // Leftover bytes belong to the same endpoints as the last packet of the flow
+        self.loaded_networks = match flow.network.clone() {
+            Some(network) if !bytes.is_empty() => vec![(0, network)],
+            _ => Vec::new(),
+        };
+        for (offset, network) in mem::take(&mut flow.ready_networks) {
+            if self.loaded_networks.last().map(|(_, last)| last) != Some(&network) {
+                self.loaded_networks.push((bytes.len() + offset, network));
+            }
+        }
+        bytes.append(&mut flow.ready);
+        self.active = Some(next);
+        self.buffer.reserve(bytes.len());
+        Some((self.buffer.copy_from_slice(&bytes), skipped))
+    }
+}
+
+/// Sets the ports of the transport layer
+trait WithPorts {
+    fn with_ports(self, source_port: u16, destination_port: u16) -> Self;
+}
+
+impl WithPorts for NetworkMeta {
+    fn with_ports(self, source_port: u16, destination_port: u16) -> Self {
+        Self {
+            source_port: Some(source_port),
+            destination_port: Some(destination_port),
+            ..self
+        }
+    }
+}
+
+fn ip_addrs(net: &NetSlice) -> Option<(IpAddr, IpAddr)> {
+    match net {
+        NetSlice::Ipv4(ip) => Some((
+            IpAddr::V4(ip.header().source_addr()),
+            IpAddr::V4(ip.header().destination_addr()),
+        )),
+        NetSlice::Ipv6(ip) => Some((
+            IpAddr::V6(ip.header().source_addr()),
+            IpAddr::V6(ip.header().destination_addr()),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use etherparse::{
+        EtherType, Ethernet2Header, IpFragOffset, Ipv4Header, PacketBuilder, UdpHeader,
+    };
+
+    fn tcp_frame(src_port: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+        let builder = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+            .ipv4([192, 168, 1, 1], [192, 168, 1, 2], 20)
+            .tcp(src_port, 3490, seq, 1024);
+        let mut frame = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut frame, payload).unwrap();
+        frame
+    }
+
+    fn udp_frame(src_port: u16, payload: &[u8]) -> Vec<u8> {
+        let builder = PacketBuilder::ethernet2([1, 2, 3, 4, 5, 6], [7, 8, 9, 10, 11, 12])
+            .ipv4([192, 168, 1, 1], [192, 168, 1, 2], 20)
+            .udp(src_port, 3490);
+        let mut frame = Vec::with_capacity(builder.size(payload.len()));
+        builder.write(&mut frame, payload).unwrap();
+        frame
+    }
+
+    fn ipv4_fragment(offset: u16, more: bool, payload: &[u8]) -> Vec<u8> {
+        let mut ip = Ipv4Header::new(
+            payload.len() as u16,
+            20,
+            IpNumber::UDP,
+            [192, 168, 1, 1],
+            [192, 168, 1, 2],
+        )
+        .unwrap();
+        ip.identification = 7;
+        ip.more_fragments = more;
+        ip.fragment_offset = IpFragOffset::try_new(offset / 8).unwrap();
+        let mut frame = Vec::new();
+        Ethernet2Header {
+            source: [1, 2, 3, 4, 5, 6],
+            destination: [7, 8, 9, 10, 11, 12],
+            ether_type: EtherType::IPV4,
+        }
+        .write(&mut frame)
+        .unwrap();
+        ip.write(&mut frame).unwrap();
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_reassembler_in_order() {
+        let mut reassembler = TcpReassembler::default();
+        let mut ready = Vec::new();
+        reassembler.push(1000, true, &[], &mut ready);
+        reassembler.push(1001, false, b"abc", &mut ready);
+        reassembler.push(1004, false, b"def", &mut ready);
+        assert_eq!(ready, b"abcdef");
+    }
+
+    #[test]
+    fn test_reassembler_out_of_order_and_retransmission() {
+        let mut reassembler = TcpReassembler::default();
+        let mut ready = Vec::new();
+        reassembler.push(100, false, b"abc", &mut ready);
+        reassembler.push(106, false, b"ghi", &mut ready);
+        assert_eq!(ready, b"abc");
+        // retransmission of already delivered bytes
+        let pushed = reassembler.push(100, false, b"abc", &mut ready);
+        assert_eq!(pushed.duplicated, 3);
+        // overlapping segment fills the hole
+        let pushed = reassembler.push(102, false, b"cdefg", &mut ready);
+        assert_eq!(pushed.duplicated, 2);
+        assert_eq!(ready, b"abcdefghi");
+    }
+
+    #[test]
+    fn test_reassembler_wrapping_seq() {
+        let mut reassembler = TcpReassembler::default();
+        let mut ready = Vec::new();
+        reassembler.push(u32::MAX - 1, false, b"ab", &mut ready);
+        reassembler.push(1, false, b"d", &mut ready);
+        reassembler.push(0, false, b"c", &mut ready);
+        assert_eq!(ready, b"abcd");
+    }
+
+    #[test]
+    fn test_reassembler_gap() {
+        let mut reassembler = TcpReassembler::default();
+        let mut ready = Vec::new();
+        reassembler.push(0, false, b"ab", &mut ready);
+        reassembler.push(5, false, b"fg", &mut ready);
+        assert_eq!(reassembler.flush(&mut ready), (3, 0));
+        assert_eq!(ready, b"abfg");
+        reassembler.push(7, false, b"h", &mut ready);
+        assert_eq!(ready, b"abfgh");
+    }
+
+    #[test]
+    fn test_flows_are_separated() {
+        let mut flows = Flows::new();
+        assert_eq!(
+            flows
+                .feed(
+                    Linktype::ETHERNET,
+                    None,
+                    &tcp_frame(1, 0, b"a1"),
+                    None,
+                    None
+                )
+                .unwrap(),
+            2
+        );
+        assert_eq!(flows.load(), Some((2, 0)));
+        assert_eq!(flows.buf(), b"a1");
+        // other connection: unconsumed bytes of the first one are put aside
+        flows
+            .feed(
+                Linktype::ETHERNET,
+                None,
+                &tcp_frame(2, 0, b"b1"),
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(flows.load(), Some((2, 0)));
+        assert_eq!(flows.buf(), b"b1");
+        flows.consume(2);
+        // first connection continues with its unconsumed bytes
+        flows
+            .feed(
+                Linktype::ETHERNET,
+                None,
+                &tcp_frame(1, 2, b"a2"),
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(flows.load(), Some((4, 0)));
+        assert_eq!(flows.buf(), b"a1a2");
+        assert_eq!(flows.load(), None);
+    }
+
+    #[test]
+    fn test_datagram_flows_are_separated() {
+        let source_port = |flows: &Flows| {
+            flows
+                .networks()
+                .iter()
+                .map(|(offset, network)| (*offset, network.source_port))
+                .collect::<Vec<_>>()
+        };
+        let mut flows = Flows::new();
+        flows
+            .feed(Linktype::ETHERNET, None, &udp_frame(1, b"a1"), None, None)
+            .unwrap();
+        assert_eq!(flows.load(), Some((2, 0)));
+        assert_eq!(flows.buf(), b"a1");
+        assert_eq!(source_port(&flows), [(0, Some(1))]);
+        // other peer: unconsumed bytes of the first one are put aside
+        flows
+            .feed(Linktype::ETHERNET, None, &udp_frame(2, b"b1"), None, None)
+            .unwrap();
+        assert_eq!(flows.load(), Some((2, 0)));
+        assert_eq!(flows.buf(), b"b1");
+        assert_eq!(source_port(&flows), [(0, Some(2))]);
+        flows.consume(2);
+        // first peer continues with its unconsumed bytes and keeps its own metadata
+        flows
+            .feed(Linktype::ETHERNET, None, &udp_frame(1, b"a2"), None, None)
+            .unwrap();
+        assert_eq!(flows.load(), Some((4, 0)));
+        assert_eq!(flows.buf(), b"a1a2");
+        assert_eq!(source_port(&flows), [(0, Some(1))]);
+        assert_eq!(flows.load(), None);
+    }
+
+    #[test]
+    fn test_networks_of_loaded_packets() {
+        let mut flows = Flows::new();
+        for interface in [1, 2] {
+            flows
+                .feed(
+                    Linktype::ETHERNET,
+                    Some(interface),
+                    &udp_frame(1, b"a1"),
+                    None,
+                    None,
+                )
+                .unwrap();
+        }
+        assert_eq!(flows.load(), Some((4, 0)));
+        let interfaces = flows
+            .networks()
+            .iter()
+            .map(|(offset, network)| (*offset, network.interface))
+            .collect::<Vec<_>>();
+        assert_eq!(interfaces, [(0, Some(1)), (2, Some(2))]);
+    }
+
+    #[test]
+    fn test_flows_filter() {
+        let mut flows = Flows::new();
+        let filter = SourceFilter {
+            transport: Some(TransportProtocol::UDP),
+            remote: None,
+        };
+        assert_eq!(
+            flows
+                .feed(
+                    Linktype::ETHERNET,
+                    None,
+                    &tcp_frame(1, 0, b"a1"),
+                    None,
+                    Some(&filter)
+                )
+                .unwrap(),
+            0
+        );
+        assert_eq!(flows.load(), None);
+    }
+
+    #[test]
+    fn test_flows_ip_fragments() {
+        let message = b"hello fragmented world!!";
+        let mut datagram = UdpHeader::without_ipv4_checksum(1000, 2000, message.len())
+            .unwrap()
+            .to_bytes()
+            .to_vec();
+        datagram.extend_from_slice(message);
+        let mut flows = Flows::new();
+        flows
+            .feed(
+                Linktype::ETHERNET,
+                None,
+                &ipv4_fragment(16, false, &datagram[16..]),
+                Some(0),
+                None,
+            )
+            .unwrap();
+        assert_eq!(flows.load(), None);
+        flows
+            .feed(
+                Linktype::ETHERNET,
+                None,
+                &ipv4_fragment(0, true, &datagram[..16]),
+                Some(1),
+                None,
+            )
+            .unwrap();
+        assert_eq!(flows.load(), Some((message.len(), 0)));
+        assert_eq!(flows.buf(), message);
+        assert_eq!(
+            flows
+                .networks()
+                .first()
+                .map(|(_, network)| network.columns()),
+            Some([
+                String::from("192.168.1.1:1000"),
+                String::from("192.168.1.2:2000"),
+                String::new(),
+                String::new(),
+            ])
+        );
+        // the second half never arrives
+        flows
+            .feed(
+                Linktype::ETHERNET,
+                None,
+                &ipv4_fragment(0, true, &datagram[..16]),
+                Some(2),
+                None,
+            )
+            .unwrap();
+        assert_eq!(flows.flush(), None);
+        assert_eq!(flows.stats().ip_fragments.incomplete, 1);
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Difference between the system time and the monotonic time in ms, which is taken as an
+/// adjustment of the system time (e.g. NTP) and followed
+const RESYNC_THRESHOLD_MS: u64 = 1_000;
+
+fn system_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Source of the time of [`ReceiveClock`]; times are in ms
+pub trait TimeSource {
+    /// System time since the Unix epoch
+    fn system(&self) -> u64;
+    /// Monotonic time since an arbitrary moment
+    fn monotonic(&self) -> u64;
+}
+
+/// Time of the host
+#[derive(Debug, Clone)]
+pub struct HostTime {
+    started: Instant,
+}
+
+impl Default for HostTime {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl TimeSource for HostTime {
+    fn system(&self) -> u64 {
+        system_time_ms()
+    }
+
+    fn monotonic(&self) -> u64 {
+        self.started.elapsed().as_millis() as u64
+    }
+}
+
+/// Host clock stamping data received by live sources, in ms since the Unix epoch.
+///
+/// The time is measured with the monotonic clock, starting at the system time. A jitter of
+/// the system time doesn't affect the stamps. An adjustment of the system time forward is
+/// followed; an adjustment backward is ignored, so stamps keep increasing with the
+/// monotonic clock.
+#[derive(Debug, Clone)]
+pub struct ReceiveClock<T: TimeSource = HostTime> {
+    time: T,
+    /// Stamp at `synced`
+    origin: u64,
+    /// Monotonic time of the last synchronization with the system time
+    synced: u64,
+}
+
+impl ReceiveClock {
+    pub fn new() -> Self {
+        Self::with_time(HostTime::default())
+    }
+}
+
+impl<T: TimeSource> ReceiveClock<T> {
+    pub fn with_time(time: T) -> Self {
+        Self {
+            origin: time.system(),
+            synced: time.monotonic(),
+            time,
+        }
+    }
+
+    /// Returns the current host time
+    pub fn now(&mut self) -> u64 {
+        let monotonic = self.time.monotonic();
+        let now = self.origin + monotonic.saturating_sub(self.synced);
+        let system = self.time.system();
+        if system > now + RESYNC_THRESHOLD_MS {
+            self.origin = system;
+            self.synced = monotonic;
+            system
+        } else {
+            now
+        }
+    }
+}
+
+impl Default for ReceiveClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, thread, time::Duration};
+
+    #[derive(Default)]
+    struct FakeTime {
+        system: Cell<u64>,
+        monotonic: Cell<u64>,
+    }
+
+    impl FakeTime {
+        fn advance(&self, system: i64, monotonic: u64) {
+            self.system
+                .set(self.system.get().saturating_add_signed(system));
+            self.monotonic.set(self.monotonic.get() + monotonic);
+        }
+    }
+
+    impl TimeSource for &FakeTime {
+        fn system(&self) -> u64 {
+            self.system.get()
+        }
+
+        fn monotonic(&self) -> u64 {
+            self.monotonic.get()
+        }
+    }
+
+    #[test]
+    fn test_receive_clock() {
+        let mut clock = ReceiveClock::new();
+        let first = clock.now();
+        assert!(first.abs_diff(system_time_ms()) <= RESYNC_THRESHOLD_MS);
+        thread::sleep(Duration::from_millis(20));
+        let second = clock.now();
+        assert!(second >= first + 20);
+    }
+
+    #[test]
+    fn test_system_time_adjustments() {
+        let time = FakeTime::default();
+        time.advance(1_700_000_000_000, 5);
+        let mut clock = ReceiveClock::with_time(&time);
+        assert_eq!(clock.now(), 1_700_000_000_000);
+        // Jitter of the system time is ignored
+        time.advance(520, 20);
+        assert_eq!(clock.now(), 1_700_000_000_020);
+        // Stamps keep increasing after the system time was adjusted backwards
+        time.advance(-60_000, 10);
+        assert_eq!(clock.now(), 1_700_000_000_030);
+        time.advance(10, 10);
+        assert_eq!(clock.now(), 1_700_000_000_040);
+        // Adjustment forwards is followed
+        time.advance(120_000, 10);
+        let adjusted = time.system.get();
+        assert!(adjusted > 1_700_000_000_050 + RESYNC_THRESHOLD_MS);
+        assert_eq!(clock.now(), adjusted);
+        time.advance(10, 10);
+        assert_eq!(clock.now(), adjusted + 10);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{sde, ByteSource, Error as SourceError, ReloadInfo, SourceFilter};
+use crate::{clock::ReceiveClock, sde, ByteSource, Error as SourceError, ReloadInfo, SourceFilter};
 use buf_redux::Buffer;
 use regex::{Captures, Regex};
 use shellexpand::tilde;
@@ -34,6 +34,7 @@ pub struct ProcessSource {
     stdout: FramedRead<ChildStdout, LinesCodec>,
     stderr: FramedRead<ChildStderr, LinesCodec>,
     stdin: ChildStdin,
+    clock: ReceiveClock,
 }
 
 impl Drop for ProcessSource {
@@ -148,6 +149,7 @@ impl ProcessSource {
             stdout,
             stderr,
             stdin,
+            clock: ReceiveClock::new(),
         })
     }
 }
@@ -186,7 +188,12 @@ impl ByteSource for ProcessSource {
             self.buffer.copy_from_slice(line.as_bytes());
             self.buffer.copy_from_slice(b"\n");
             let available_bytes = self.buffer.len();
-            Ok(Some(ReloadInfo::new(stored, available_bytes, 0, None)))
+            Ok(Some(ReloadInfo::new(
+                stored,
+                available_bytes,
+                0,
+                Some(self.clock.now()),
+            )))
         } else if let Some(Err(err)) = output {
             Err(SourceError::Unrecoverable(format!("{err}")))
         } else {
@@ -214,6 +221,9 @@ impl ByteSource for ProcessSource {
         let bytes = match request {
             sde::SdeRequest::WriteText(ref str) => str.as_bytes(),
             sde::SdeRequest::WriteBytes(ref bytes) => bytes,
+            sde::SdeRequest::Replay(_) | sde::SdeRequest::DltControl(_) => {
+                return Err(SourceError::NotSupported)
+            }
         };
         self.stdin.write_all(bytes).await.map_err(SourceError::Io)?;
         Ok(sde::SdeResponse { bytes: bytes.len() })
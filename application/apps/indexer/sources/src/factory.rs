@@ -1,7 +1,9 @@
+use crate::AddressFilter;
 use indexer_base::config::MulticastInfo;
-use parsers::dlt;
+use parsers::{dlt, timestamp::TimeCorrection};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 use uuid::Uuid;
 
 #[allow(clippy::large_enum_variant)]
@@ -73,6 +75,11 @@ pub enum Transport {
     TCP(TCPTransportConfig),
     UDP(UDPTransportConfig),
     Serial(SerialTransportConfig),
+    Unix(UnixTransportConfig),
+    Fifo(FifoTransportConfig),
+    Replay(ReplayTransportConfig),
+    /// Standard input of the process; the operation ends with the end of the input
+    Stdin,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -92,17 +99,135 @@ pub struct SerialTransportConfig {
     pub stop_bits: u8,
     pub send_data_delay: u8,
     pub exclusive: bool,
+    #[serde(default)]
+    pub reconnect: Option<ReconnectPolicy>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TCPTransportConfig {
     pub bind_addr: String,
+    #[serde(default)]
+    pub mode: TCPTransportMode,
+    #[serde(default)]
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Clients accepted in [`TCPTransportMode::Listen`] are dropped if not matching; in
+    /// [`TCPTransportMode::Connect`] the data of a not matching remote peer is dropped
+    #[serde(default)]
+    pub filter: AddressFilter,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub enum TCPTransportMode {
+    /// Connect to the remote peer defined by `bind_addr`
+    #[default]
+    Connect,
+    /// Bind `bind_addr` and accept incoming clients. Each client is observed as its own source.
+    Listen,
+}
+
+/// Defines how a stream transport is re-established after its connection was lost
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReconnectPolicy {
+    /// Maximum count of attempts in a row before giving up; `None` - try forever
+    pub max_attempts: Option<u32>,
+    /// Delay before the first attempt in ms
+    pub initial_delay_ms: u64,
+    /// Upper limit of the delay between two attempts in ms
+    pub max_delay_ms: u64,
+    /// Fraction of the delay (0.0..=1.0) used to randomly spread it
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: None,
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Returns the delay before the given (1-based) attempt. The delay is doubled with each
+    /// attempt until `max_delay_ms` is reached and randomized with `jitter`.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .initial_delay_ms
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_ms) as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            1.0 + rand::thread_rng().gen_range(-jitter..=jitter)
+        } else {
+            1.0
+        };
+        Duration::from_millis((delay * factor) as u64)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UnixTransportConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub kind: UnixSocketKind,
+    #[serde(default)]
+    pub mode: UnixSocketMode,
+    /// Used for [`UnixSocketKind::Stream`] in [`UnixSocketMode::Connect`] only
+    #[serde(default)]
+    pub reconnect: Option<ReconnectPolicy>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub enum UnixSocketKind {
+    #[default]
+    Stream,
+    Datagram,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub enum UnixSocketMode {
+    /// Connect to the socket at `path`
+    #[default]
+    Connect,
+    /// Bind `path`; a stream socket accepts incoming clients and observes each client as its
+    /// own source. A socket file left at `path` by a previous listener is replaced.
+    Listen,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FifoTransportConfig {
+    pub path: PathBuf,
+}
+
+/// Replays a file with the original timing of its messages, as if it were received from a
+/// live target (see [`crate::replay::ReplaySource`])
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplayTransportConfig {
+    pub path: PathBuf,
+    pub format: FileFormat,
+    /// Factor of the replay speed, e.g. `2.0` replays twice as fast as recorded
+    #[serde(default = "default_replay_speed")]
+    pub speed: f64,
+}
+
+fn default_replay_speed() -> f64 {
+    1.0
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UDPTransportConfig {
     pub bind_addr: String,
     pub multicast: Vec<MulticastInfo>,
+    /// Datagrams of remote peers which aren't matching are dropped
+    #[serde(default)]
+    pub filter: AddressFilter,
+    /// Peer receiving the data sent via SDE as `host:port`, host names are resolved once on
+    /// start; without it, data goes to the peer of the last received datagram
+    #[serde(default)]
+    pub send_to: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -116,14 +241,130 @@ pub enum FileFormat {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ObserveOrigin {
     File(String, FileFormat, PathBuf),
-    Concat(Vec<(String, FileFormat, PathBuf)>),
+    Concat(Vec<ConcatFile>),
     Stream(String, Transport),
+    /// Members of a zip or tar archive; each member is defined by uuid, format and its path
+    /// inside of the archive. Several members are concatenated in the given order.
+    Archive(PathBuf, Vec<(String, FileFormat, String)>),
+    /// Files of a folder matching a glob; files are concatenated in the defined order.
+    /// Each file is registered as its own source, with the given uuid as prefix.
+    Directory(String, DirectoryConfig),
+}
+
+/// File of a concatenated session. Each file can be parsed with its own parser; files without
+/// parser are parsed with the parser of [`ObserveOptions`].
+///
+/// Besides of a map, a file can be defined as `[uuid, format, path]` or
+/// `[uuid, format, path, parser]`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(from = "ConcatFileDef")]
+pub struct ConcatFile {
+    pub uuid: String,
+    pub format: FileFormat,
+    pub path: PathBuf,
+    pub parser: Option<ParserType>,
+}
+
+impl ConcatFile {
+    pub fn new(uuid: String, format: FileFormat, path: PathBuf) -> Self {
+        Self {
+            uuid,
+            format,
+            path,
+            parser: None,
+        }
+    }
+
+    /// Parser of the file; `default` if the file has no own parser
+    pub fn parser<'a>(&'a self, default: &'a ParserType) -> &'a ParserType {
+        self.parser.as_ref().unwrap_or(default)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConcatFileDef {
+    File {
+        uuid: String,
+        format: FileFormat,
+        path: PathBuf,
+        #[serde(default)]
+        parser: Option<ParserType>,
+    },
+    WithParser(String, FileFormat, PathBuf, Option<ParserType>),
+    Shared(String, FileFormat, PathBuf),
+}
+
+impl From<ConcatFileDef> for ConcatFile {
+    fn from(def: ConcatFileDef) -> Self {
+        match def {
+            ConcatFileDef::File {
+                uuid,
+                format,
+                path,
+                parser,
+            }
+            | ConcatFileDef::WithParser(uuid, format, path, parser) => Self {
+                uuid,
+                format,
+                path,
+                parser,
+            },
+            ConcatFileDef::Shared(uuid, format, path) => Self::new(uuid, format, path),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DirectoryConfig {
+    pub path: PathBuf,
+    /// Glob of file names, e.g. `app.log*` or `trace.{dlt,pcap}`; `*` and `?` don't match
+    /// path separators. Files of nested folders aren't included.
+    pub glob: String,
+    pub format: FileFormat,
+    #[serde(default)]
+    pub order: FileOrder,
+    /// Keep watching the folder and append newly created files to the session. The most
+    /// recent file is tailed until a newer file appears.
+    #[serde(default)]
+    pub watch: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub enum FileOrder {
+    /// Alphabetical order of file names
+    #[default]
+    Name,
+    /// Order of rotated files from the oldest to the current one, e.g. `app.log.2`,
+    /// `app.log.1`, `app.log`
+    Rotation,
+    /// Modification time, oldest first
+    ModificationTime,
+    /// Timestamp of the first message, oldest first. Files without timestamp are placed last.
+    FirstTimestamp,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ObserveOptions {
     pub origin: ObserveOrigin,
     pub parser: ParserType,
+    /// Time corrections of sources by uuid. The correction of a uuid applies to the sources
+    /// registered with the uuid as prefix (`<uuid>:<name>`) as well.
+    #[serde(default)]
+    pub corrections: HashMap<String, TimeCorrection>,
+    /// File the received data of a stream is recorded to (see [`crate::recorder`]). The
+    /// recording can be opened later as a file and makes the raw export possible.
+    /// Ignored for other origins.
+    #[serde(default)]
+    pub recording: Option<PathBuf>,
+    /// Defines whether the tail of the rotated file (`<path>.1`) is read after a tailed file
+    /// was truncated in place (`copytruncate`). Ignored for other origins than a file.
+    #[serde(default = "default_pick_up_rotated")]
+    pub pick_up_rotated: bool,
+}
+
+fn default_pick_up_rotated() -> bool {
+    true
 }
 
 impl ObserveOptions {
@@ -131,6 +372,63 @@ impl ObserveOptions {
         ObserveOptions {
             origin: ObserveOrigin::File(Uuid::new_v4().to_string(), file_origin, filename),
             parser,
+            corrections: HashMap::new(),
+            recording: None,
+            pick_up_rotated: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concat_file_definitions() {
+        let files: Vec<ConcatFile> = serde_json::from_str(
+            r#"[
+                ["a", "Text", "/tmp/a.log"],
+                ["b", "PcapNG", "/tmp/b.pcapng", {"SomeIp": {"fibex_file_paths": null}}],
+                {"uuid": "c", "format": "Binary", "path": "/tmp/c.dlt", "parser": "Text"}
+            ]"#,
+        )
+        .unwrap();
+        assert!(files[0].parser.is_none());
+        assert!(matches!(files[1].parser, Some(ParserType::SomeIp(_))));
+        assert!(matches!(files[2].parser, Some(ParserType::Text)));
+        assert!(matches!(files[2].format, FileFormat::Binary));
+        let serialized = serde_json::to_string(&files[1]).unwrap();
+        let restored: ConcatFile = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(restored.uuid, "b");
+        assert!(matches!(restored.parser, Some(ParserType::SomeIp(_))));
+    }
+
+    #[test]
+    fn test_reconnect_delay() {
+        let policy = ReconnectPolicy {
+            max_attempts: Some(10),
+            initial_delay_ms: 100,
+            max_delay_ms: 1_000,
+            jitter: 0.0,
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(200));
+        assert_eq!(policy.delay(4), Duration::from_millis(800));
+        assert_eq!(policy.delay(5), Duration::from_millis(1_000));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn test_reconnect_delay_jitter() {
+        let policy = ReconnectPolicy {
+            max_attempts: None,
+            initial_delay_ms: 1_000,
+            max_delay_ms: 1_000,
+            jitter: 0.5,
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_millis(1_500));
         }
     }
 }
@@ -0,0 +1,127 @@
+use crate::{clock::ReceiveClock, ByteSource, Error as SourceError, ReloadInfo, SourceFilter};
+use buf_redux::Buffer;
+use std::{io, path::Path};
+use tokio::net::unix::pipe::{OpenOptions, Receiver};
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Source reading a named pipe (FIFO). The pipe is read-only, SDE isn't supported.
+pub struct FifoSource {
+    buffer: Buffer,
+    pipe: Receiver,
+    tmp_buffer: Vec<u8>,
+    clock: ReceiveClock,
+}
+
+impl FifoSource {
+    /// Opens the FIFO `path` for reading. Fails if `path` isn't a FIFO.
+    ///
+    /// On Linux the pipe is opened in read-write mode, so the source keeps waiting for data
+    /// when the last writer closes the pipe, and writers can come and go. On other platforms
+    /// the source is done as soon as all writers are closed.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let mut options = OpenOptions::new();
+        #[cfg(target_os = "linux")]
+        options.read_write(true);
+        Ok(Self {
+            buffer: Buffer::new(),
+            pipe: options.open_receiver(path)?,
+            tmp_buffer: vec![0u8; READ_CHUNK_SIZE],
+            clock: ReceiveClock::new(),
+        })
+    }
+}
+
+impl ByteSource for FifoSource {
+    async fn load(
+        &mut self,
+        _filter: Option<&SourceFilter>,
+    ) -> Result<Option<ReloadInfo>, SourceError> {
+        loop {
+            self.pipe
+                .readable()
+                .await
+                .map_err(|e| SourceError::Unrecoverable(format!("{e}")))?;
+            match self.pipe.try_read(&mut self.tmp_buffer) {
+                Ok(len) => {
+                    trace!("---> Received {} bytes", len);
+                    if len > 0 {
+                        self.buffer.copy_from_slice(&self.tmp_buffer[..len]);
+                    }
+                    let available_bytes = self.buffer.len();
+                    return Ok(Some(ReloadInfo::new(
+                        len,
+                        available_bytes,
+                        0,
+                        Some(self.clock.now()),
+                    )));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    continue;
+                }
+                Err(e) => {
+                    return Err(SourceError::Setup(format!("{e}")));
+                }
+            }
+        }
+    }
+
+    fn current_slice(&self) -> &[u8] {
+        self.buffer.buf()
+    }
+
+    fn consume(&mut self, offset: usize) {
+        self.buffer.consume(offset)
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::general_source_reload_test;
+    use std::{fs, path::PathBuf, process::Command, time::Duration};
+    use tokio::{io::AsyncWriteExt, time::sleep};
+
+    static MESSAGES: &[&str] = &["one", "two", "three"];
+
+    fn fifo_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("chipmunk_{name}_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let status = Command::new("mkfifo")
+            .arg(&path)
+            .status()
+            .expect("mkfifo is available");
+        assert!(status.success());
+        path
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let path = fifo_path("fifo_reload");
+        let mut source = FifoSource::new(&path).unwrap();
+        let mut sender = OpenOptions::new().open_sender(&path).unwrap();
+        tokio::spawn(async move {
+            for msg in MESSAGES {
+                sender
+                    .write_all(msg.as_bytes())
+                    .await
+                    .expect("could not write into fifo");
+                sleep(Duration::from_millis(100)).await;
+            }
+        });
+        general_source_reload_test(&mut source).await;
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_not_a_fifo() {
+        let path = std::env::temp_dir().join(format!("chipmunk_not_fifo_{}", std::process::id()));
+        fs::write(&path, b"data").unwrap();
+        assert!(FifoSource::new(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
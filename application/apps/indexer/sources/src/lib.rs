@@ -1,6 +1,9 @@
 // Rust can't currently distinguish between dev and none-dev dependencies at the moment. There is
 // an open issue for this case: "https://github.com/rust-lang/rust/issues/129637"
 
+use parsers::NetworkMeta;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
 use thiserror::Error;
 
 #[macro_use]
@@ -13,14 +16,20 @@ extern crate log;
 mod tests;
 
 pub mod binary;
+pub mod clock;
 pub mod command;
 pub mod factory;
+#[cfg(unix)]
+pub mod fifo;
 pub mod producer;
+pub mod recorder;
+pub mod replay;
 pub mod sde;
 pub mod serial;
 pub mod socket;
+pub mod stdin;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TransportProtocol {
     TCP,
     UDP,
@@ -37,9 +46,72 @@ impl From<etherparse::TransportSlice<'_>> for TransportProtocol {
     }
 }
 
-#[derive(Debug)]
+/// Transport protocol and addresses of data received over a network connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Endpoints {
+    pub protocol: TransportProtocol,
+    /// Address of the peer which sent the data
+    pub remote: SocketAddr,
+    /// Address the data was received on
+    pub local: SocketAddr,
+}
+
+#[derive(Debug, Default)]
 pub struct SourceFilter {
     transport: Option<TransportProtocol>,
+    remote: Option<AddressFilter>,
+}
+
+impl SourceFilter {
+    /// Creates a filter which accepts only data sent by remote peers matching `remote`
+    pub fn remote(remote: AddressFilter) -> Self {
+        Self {
+            transport: None,
+            remote: Some(remote),
+        }
+    }
+
+    /// Returns `true` if data from `addr` should be accepted
+    pub fn accepts_remote(&self, addr: &SocketAddr) -> bool {
+        self.remote
+            .as_ref()
+            .map_or(true, |remote| remote.matches(addr))
+    }
+}
+
+/// Allow- and deny-lists of remote IP addresses and ports.
+///
+/// A peer is rejected if its address or port is listed in a deny-list. If an allow-list
+/// isn't empty, the peer has to be listed there as well to be accepted.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AddressFilter {
+    #[serde(default)]
+    pub allow_addrs: Vec<IpAddr>,
+    #[serde(default)]
+    pub deny_addrs: Vec<IpAddr>,
+    #[serde(default)]
+    pub allow_ports: Vec<u16>,
+    #[serde(default)]
+    pub deny_ports: Vec<u16>,
+}
+
+impl AddressFilter {
+    pub fn is_empty(&self) -> bool {
+        self.allow_addrs.is_empty()
+            && self.deny_addrs.is_empty()
+            && self.allow_ports.is_empty()
+            && self.deny_ports.is_empty()
+    }
+
+    pub fn matches(&self, addr: &SocketAddr) -> bool {
+        let ip = addr.ip().to_canonical();
+        let port = addr.port();
+        if self.deny_addrs.contains(&ip) || self.deny_ports.contains(&port) {
+            return false;
+        }
+        (self.allow_addrs.is_empty() || self.allow_addrs.contains(&ip))
+            && (self.allow_ports.is_empty() || self.allow_ports.contains(&port))
+    }
 }
 
 #[derive(Debug)]
@@ -48,6 +120,9 @@ pub struct ReloadInfo {
     pub available_bytes: usize,
     pub skipped_bytes: usize,
     pub last_known_ts: Option<u64>,
+    /// Network metadata of the packets the newly loaded bytes were received in. Each entry
+    /// applies from its offset within the newly loaded bytes up to the next entry.
+    pub networks: Vec<(usize, NetworkMeta)>,
 }
 
 impl ReloadInfo {
@@ -62,8 +137,24 @@ impl ReloadInfo {
             available_bytes,
             skipped_bytes,
             last_known_ts,
+            networks: Vec::new(),
         }
     }
+
+    /// Sets the network metadata of all newly loaded bytes
+    pub fn with_network(self, network: Option<NetworkMeta>) -> Self {
+        self.with_networks(
+            network
+                .map(|network| vec![(0, network)])
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Sets the network metadata of the newly loaded bytes by their offset
+    pub fn with_networks(mut self, networks: Vec<(usize, NetworkMeta)>) -> Self {
+        self.networks = networks;
+        self
+    }
 }
 
 #[derive(Error, Debug)]
@@ -90,7 +181,7 @@ pub(crate) const DEFAULT_MIN_BUFFER_SPACE: usize = 10 * 1024;
 /// want to extract the data part from certain frames, the `relaod` method will load only the relevant
 /// data into an internal buffer.
 /// This data can then be accessed via the `current_slice` method.
-pub trait ByteSource: Send + Sync {
+pub trait ByteSource: Send {
     /// Indicate that we have consumed a certain amount of data from our internal
     /// buffer and that this part can be discarded
     fn consume(&mut self, offset: usize);
@@ -105,6 +196,19 @@ pub trait ByteSource: Send + Sync {
         self.len() == 0
     }
 
+    /// Describes problems found so far while reading the source, which don't stop reading
+    /// but should be reported to the user (e.g. captured packets which couldn't be
+    /// reassembled). Returns `None` if there are no problems.
+    fn problems(&self) -> Option<String> {
+        None
+    }
+
+    /// Endpoints of the connection the data loaded last was received over. Returns `None`
+    /// if the source doesn't receive data over a network connection or they are unknown.
+    fn endpoints(&self) -> Option<Endpoints> {
+        None
+    }
+
     /// will load more bytes from the underlying source
     /// when the source has reached it's end, this function
     /// will return Ok((None, _))
@@ -117,7 +221,8 @@ pub trait ByteSource: Send + Sync {
     ///
     ///
     /// If the source has access to some timestamp (e.g. timestamp of network package),
-    /// this timestamp is passed on additionally (`last_known_ts`)
+    /// this timestamp is passed on additionally (`last_known_ts`). Live sources pass the
+    /// host time the data was received at (see [`clock::ReceiveClock`]).
     ///
     /// # Note:
     ///
@@ -4,8 +4,8 @@ mod tests;
 use crate::{sde::SdeMsg, ByteSource, ReloadInfo, SourceFilter};
 use async_stream::stream;
 use log::warn;
-use parsers::{Error as ParserError, LogMessage, MessageStreamItem, Parser};
-use std::marker::PhantomData;
+use parsers::{Error as ParserError, LogMessage, MessageStreamItem, NetworkMeta, Parser};
+use std::{collections::VecDeque, marker::PhantomData};
 use tokio::{
     select,
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
@@ -37,6 +37,10 @@ where
     total_skipped: usize,
     done: bool,
     rx_sde: Option<SdeReceiver>,
+    /// Network metadata of the bytes of the source by their offset in the current slice
+    networks: VecDeque<(usize, NetworkMeta)>,
+    /// Network metadata the parser was given last
+    network: Option<NetworkMeta>,
 }
 
 impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
@@ -53,8 +57,22 @@ impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
             total_skipped: 0,
             done: false,
             rx_sde,
+            networks: VecDeque::new(),
+            network: None,
         }
     }
+
+    /// Lets the producer continue after its source ran out of bytes, e.g. because the
+    /// tailed file has grown since. A new stream has to be created with [`Self::as_stream`].
+    pub fn resume(&mut self) {
+        self.done = false;
+    }
+
+    /// Problems found so far by the byte source, see [`ByteSource::problems`]
+    pub fn problems(&self) -> Option<String> {
+        self.byte_source.problems()
+    }
+
     /// create a stream of pairs that contain the count of all consumed bytes and the
     /// MessageStreamItems in a boxed slice
     pub fn as_stream(&mut self) -> impl Stream<Item = Box<[(usize, MessageStreamItem<T>)]>> + '_ {
@@ -142,6 +160,7 @@ impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
             // reference to self.
             let mut total_consumed = 0;
 
+            self.apply_network();
             match self
                 .parser
                 .parse(self.byte_source.current_slice(), self.last_seen_ts)
@@ -170,12 +189,12 @@ impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
                     .collect::<Box<[_]>>()
                 }) {
                 Ok(items) => {
-                    self.byte_source.consume(total_consumed);
+                    self.consume(total_consumed);
                     return Some(items);
                 }
                 Err(ParserError::Incomplete) => {
                     trace!("not enough bytes to parse a message");
-                    let (newly_loaded, _available_bytes, skipped) = self.load().await?;
+                    let (newly_loaded, available_bytes, skipped) = self.load().await?;
 
                     // Stop if there is no new available bytes.
                     if newly_loaded == 0 {
@@ -187,7 +206,9 @@ impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
                     }
 
                     trace!("New bytes has been loaded, trying parsing again.");
-                    available += newly_loaded;
+                    // Sources could replace not consumed bytes (e.g. switching between TCP
+                    // connections), therefore available bytes are taken from the source.
+                    available = available_bytes;
                     skipped_bytes += skipped;
                     continue;
                 }
@@ -206,7 +227,7 @@ impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
                     s, available, skipped_bytes
                 );
                     // skip all currently available bytes
-                    self.byte_source.consume(available);
+                    self.consume(available);
                     skipped_bytes += available;
                     available = self.byte_source.len();
 
@@ -231,6 +252,36 @@ impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
         }
     }
 
+    /// Consumes `offset` bytes of the byte source and moves the network metadata along
+    fn consume(&mut self, offset: usize) {
+        self.byte_source.consume(offset);
+        // Metadata of the first remaining byte is kept at offset 0
+        while self
+            .networks
+            .get(1)
+            .is_some_and(|(next, _)| *next <= offset)
+        {
+            self.networks.pop_front();
+        }
+        for (start, _) in self.networks.iter_mut() {
+            *start = start.saturating_sub(offset);
+        }
+    }
+
+    /// Passes the network metadata of the next message to the parser. Parsers yield one
+    /// message per call, so each message gets the metadata of the packet it starts in.
+    fn apply_network(&mut self) {
+        let network = self
+            .networks
+            .front()
+            .filter(|(offset, _)| *offset == 0)
+            .map(|(_, network)| network.clone());
+        if network != self.network {
+            self.network = network.clone();
+            self.parser.set_network_meta(network);
+        }
+    }
+
     /// Calls load on the underline byte source filling it with more bytes.
     /// Returning information about the state of the byte counts, Or None if
     /// the reload call fails.
@@ -245,12 +296,28 @@ impl<T: LogMessage, P: Parser<T>, D: ByteSource> MessageProducer<T, P, D> {
                 available_bytes,
                 skipped_bytes,
                 last_known_ts,
+                networks,
             })) => {
                 self.total_loaded += newly_loaded_bytes;
                 self.total_skipped += skipped_bytes;
                 if let Some(ts) = last_known_ts {
                     self.last_seen_ts = Some(ts);
                 }
+                // Newly loaded bytes are at the end of the slice; they may replace bytes
+                // which weren't consumed
+                let start = available_bytes.saturating_sub(newly_loaded_bytes);
+                while self
+                    .networks
+                    .back()
+                    .is_some_and(|(offset, _)| *offset >= start)
+                {
+                    self.networks.pop_back();
+                }
+                self.networks.extend(
+                    networks
+                        .into_iter()
+                        .map(|(offset, network)| (start + offset, network)),
+                );
                 trace!(
                     "did a do_reload, skipped {} bytes, loaded {} more bytes (total loaded and skipped: {})",
                     skipped_bytes, newly_loaded_bytes, self.total_loaded + self.total_skipped
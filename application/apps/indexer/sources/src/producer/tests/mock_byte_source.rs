@@ -106,6 +106,9 @@ impl ByteSource for MockByteSource {
         let bytes = match &msg {
             sde::SdeRequest::WriteText(text) => text.as_bytes(),
             sde::SdeRequest::WriteBytes(bytes) => bytes,
+            sde::SdeRequest::Replay(_) | sde::SdeRequest::DltControl(_) => {
+                return Err(Error::NotSupported)
+            }
         };
         Ok(sde::SdeResponse { bytes: bytes.len() })
     }
@@ -138,8 +141,9 @@ async fn general_test_mock_byte_source() {
             newly_loaded_bytes: 5,
             available_bytes: 11,
             skipped_bytes: 0,
-            last_known_ts: None
-        }))
+            last_known_ts: None,
+            ref networks,
+        })) if networks.is_empty()
     ));
     assert_eq!(source.len(), 11);
     assert_eq!(source.current_slice(), &[b'a'; 11]);
@@ -151,8 +155,9 @@ async fn general_test_mock_byte_source() {
             newly_loaded_bytes: 1,
             available_bytes: 12,
             skipped_bytes: 2,
-            last_known_ts: Some(4)
-        }))
+            last_known_ts: Some(4),
+            ref networks,
+        })) if networks.is_empty()
     ));
 
     let third_reload = source.load(None).await;
@@ -493,3 +493,100 @@ async fn sde_communication() {
     // Returned bytes' length must match the length of the sent data.
     assert_eq!(sde_response.bytes, SDE_TEXT.len());
 }
+
+/// Parser yielding a message of 2 bytes with the source port of its network metadata
+#[derive(Default)]
+struct PortParser {
+    network: Option<NetworkMeta>,
+}
+
+impl Parser<MockMessage> for PortParser {
+    fn parse(
+        &mut self,
+        input: &[u8],
+        _timestamp: Option<u64>,
+    ) -> Result<impl Iterator<Item = (usize, Option<ParseYield<MockMessage>>)>, ParseError> {
+        if input.len() < 2 {
+            return Err(ParseError::Incomplete);
+        }
+        let port = self
+            .network
+            .as_ref()
+            .and_then(|network| network.source_port)
+            .unwrap_or_default();
+        Ok(std::iter::once((
+            2,
+            Some(ParseYield::Message(MockMessage::from(port as usize))),
+        )))
+    }
+
+    fn set_network_meta(&mut self, meta: Option<NetworkMeta>) {
+        self.network = meta;
+    }
+}
+
+/// Chunk of bytes with the network metadata of the packets it contains
+type PacketLoad = (&'static [u8], Vec<(usize, NetworkMeta)>);
+
+/// Source loading chunks of several packets with their network metadata
+struct PacketSource {
+    loads: VecDeque<PacketLoad>,
+    buffer: Vec<u8>,
+}
+
+impl ByteSource for PacketSource {
+    fn consume(&mut self, offset: usize) {
+        self.buffer.drain(..offset);
+    }
+
+    fn current_slice(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    async fn load(&mut self, _filter: Option<&SourceFilter>) -> Result<Option<ReloadInfo>, Error> {
+        let Some((bytes, networks)) = self.loads.pop_front() else {
+            return Ok(None);
+        };
+        self.buffer.extend_from_slice(bytes);
+        Ok(Some(
+            ReloadInfo::new(bytes.len(), self.buffer.len(), 0, None).with_networks(networks),
+        ))
+    }
+}
+
+#[tokio::test]
+async fn network_meta_per_packet() {
+    let network = |port: u16| NetworkMeta {
+        source: "192.168.1.1".parse().unwrap(),
+        source_port: Some(port),
+        destination: "192.168.1.2".parse().unwrap(),
+        destination_port: Some(3490),
+        vlan: None,
+        interface: None,
+    };
+    // Two packets in one load; the second packet continues in the next load, which
+    // contains a third packet
+    let source = PacketSource {
+        loads: VecDeque::from([
+            (&b"aab"[..], vec![(0, network(1)), (2, network(2))]),
+            (&b"bcc"[..], vec![(1, network(3))]),
+        ]),
+        buffer: Vec::new(),
+    };
+    let mut producer = MessageProducer::new(PortParser::default(), source, None);
+    let stream = producer.as_stream();
+    pin_mut!(stream);
+    let mut ports = Vec::new();
+    while let Some(items) = stream.next().await {
+        for (_, item) in items.iter() {
+            if let MessageStreamItem::Item(ParseYield::Message(msg)) = item {
+                ports.push(msg.content);
+            }
+        }
+    }
+    assert_eq!(ports, [1, 2, 3]);
+}
@@ -0,0 +1,631 @@
+use crate::{
+    clock::{HostTime, ReceiveClock, TimeSource},
+    factory::{FileFormat, ParserType, Transport},
+    sde, ByteSource, Endpoints, Error as SourceError, ReloadInfo, SourceFilter, TransportProtocol,
+};
+use etherparse::{IpHeaders, PacketBuilder, PacketBuilderStep};
+use log::warn;
+use parsers::{
+    dlt::{dlt_message_len, storage_header, DLT_FRAME_HEADER_LEN},
+    NetworkMeta,
+};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{self, BufWriter, Write},
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    path::Path,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+use tokio::{
+    runtime::Handle,
+    sync::mpsc::{channel, Receiver, Sender},
+    task,
+};
+
+/// Number of records queued for writing; sources wait for the file when it's exceeded
+const RECORDING_QUEUE_LEN: usize = 256;
+/// Link type of recorded packets: IP packets without link layer header
+const LINKTYPE_RAW: u16 = 101;
+/// Maximum payload of one recorded TCP segment
+const MAX_SEGMENT_LEN: usize = 16 * 1024;
+/// Time to live (hop limit) of recorded IP packets
+const TTL: u8 = 64;
+/// Window size of recorded TCP segments
+const TCP_WINDOW: u16 = u16::MAX;
+
+/// Format of a recorded stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// Bytes as they were received
+    Raw,
+    /// DLT messages, each prefixed with a storage header stamped with the receive time
+    Dlt,
+    /// PcapNG capture of IP packets carrying the received bytes
+    PcapNG,
+}
+
+impl RecordingFormat {
+    /// Format a stream received over `transport` and parsed with `parser` is recorded in.
+    /// DLT streams are recorded as DLT files, other streams of network transports as
+    /// captures and everything else as it was received.
+    pub fn of_stream(transport: &Transport, parser: &ParserType) -> Self {
+        match (transport, parser) {
+            (_, ParserType::Dlt(settings)) if settings.with_storage_header => Self::Raw,
+            (_, ParserType::Dlt(_)) => Self::Dlt,
+            (Transport::TCP(_) | Transport::UDP(_), _) => Self::PcapNG,
+            _ => Self::Raw,
+        }
+    }
+
+    /// Parser and file format to open a recording of a stream, which was parsed with `parser`
+    pub fn reopen(&self, parser: &ParserType) -> (ParserType, FileFormat) {
+        match (self, parser) {
+            (Self::Dlt, ParserType::Dlt(settings)) => {
+                let mut settings = settings.clone();
+                settings.with_storage_header = true;
+                (ParserType::Dlt(settings), FileFormat::Binary)
+            }
+            (Self::PcapNG, _) => (parser.clone(), FileFormat::PcapNG),
+            (_, ParserType::Text) => (parser.clone(), FileFormat::Text),
+            _ => (parser.clone(), FileFormat::Binary),
+        }
+    }
+}
+
+/// File a stream is recorded to. Records are written by a dedicated thread, so sources
+/// wait for the file only if too many records are pending. Clones write into the same file, so all sources of one stream
+/// (e.g. clients of a TCP listener) are recorded together.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    format: RecordingFormat,
+    writer: Arc<RecordWriter>,
+}
+
+/// Thread writing records into the file of a recording. Records are flushed as soon as no
+/// more records are pending; the thread is finished as soon as the recording is dropped.
+#[derive(Debug)]
+struct RecordWriter {
+    tx: Option<Sender<Vec<u8>>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RecordWriter {
+    /// Closes the channel, so the thread writes the pending records and exits. Returns the
+    /// thread to join.
+    fn close(&mut self) -> Option<JoinHandle<()>> {
+        self.tx.take();
+        self.thread.take()
+    }
+}
+
+impl Drop for RecordWriter {
+    fn drop(&mut self) {
+        let Some(thread) = self.close() else {
+            return;
+        };
+        // Joining would block a worker of the runtime until the pending records are written
+        match Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(move || join(thread));
+            }
+            Err(_) => join(thread),
+        }
+    }
+}
+
+fn join(thread: JoinHandle<()>) {
+    if thread.join().is_err() {
+        warn!("Recording thread panicked");
+    }
+}
+
+impl Recording {
+    /// Creates the file of the recording; an existing file is overwritten
+    pub fn create(path: &Path, format: RecordingFormat) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == RecordingFormat::PcapNG {
+            writer.write_all(&pcapng_header())?;
+        }
+        let (tx, rx) = channel(RECORDING_QUEUE_LEN);
+        let name = path.display().to_string();
+        let thread = thread::Builder::new()
+            .name(String::from("recording"))
+            .spawn(move || {
+                if let Err(err) = write_records(writer, rx) {
+                    warn!("Fail to write recording {name}: {err}");
+                }
+            })?;
+        Ok(Self {
+            format,
+            writer: Arc::new(RecordWriter {
+                tx: Some(tx),
+                thread: Some(thread),
+            }),
+        })
+    }
+
+    pub fn format(&self) -> RecordingFormat {
+        self.format
+    }
+
+    /// Wraps `source` to record every chunk of bytes it loads
+    pub fn tee<S: ByteSource>(&self, source: S) -> RecordingSource<S> {
+        self.tee_with_time(source, HostTime::default())
+    }
+
+    /// Wraps `source` to record every chunk of bytes it loads. Chunks without a timestamp
+    /// are stamped with a [`ReceiveClock`] on `time`.
+    pub fn tee_with_time<S: ByteSource, T: TimeSource>(
+        &self,
+        source: S,
+        time: T,
+    ) -> RecordingSource<S, T> {
+        RecordingSource {
+            source,
+            recording: self.clone(),
+            encoder: Encoder {
+                format: self.format,
+                clock: ReceiveClock::with_time(time),
+                pending: Vec::new(),
+                seq: None,
+            },
+        }
+    }
+
+    /// Waits until all records are written into the file. If other clones of the recording
+    /// are still alive, the file is written on until they are dropped.
+    pub async fn finish(self) {
+        let Some(thread) = Arc::into_inner(self.writer).and_then(|mut writer| writer.close())
+        else {
+            return;
+        };
+        if let Err(err) = task::spawn_blocking(move || join(thread)).await {
+            warn!("Fail to wait for recording: {err}");
+        }
+    }
+
+    /// Queues `record` to be written at once, so the records of several sources aren't
+    /// interleaved. Waits if the queue is full.
+    async fn write(&self, record: Vec<u8>) -> io::Result<()> {
+        let stopped = || io::Error::other("recording is stopped after a write error");
+        let tx = self.writer.tx.as_ref().ok_or_else(stopped)?;
+        tx.send(record).await.map_err(|_| stopped())
+    }
+}
+
+/// Writes records received from `rx` until all senders are dropped
+fn write_records(mut writer: BufWriter<File>, mut rx: Receiver<Vec<u8>>) -> io::Result<()> {
+    while let Some(record) = rx.blocking_recv() {
+        writer.write_all(&record)?;
+        while let Ok(record) = rx.try_recv() {
+            writer.write_all(&record)?;
+        }
+        writer.flush()?;
+    }
+    writer.flush()
+}
+
+/// [`ByteSource`] passing through the data of another source, while recording it into a
+/// [`Recording`]
+pub struct RecordingSource<S: ByteSource, T: TimeSource = HostTime> {
+    source: S,
+    recording: Recording,
+    encoder: Encoder<T>,
+}
+
+impl<S: ByteSource, T: TimeSource + Send> ByteSource for RecordingSource<S, T> {
+    async fn load(
+        &mut self,
+        filter: Option<&SourceFilter>,
+    ) -> Result<Option<ReloadInfo>, SourceError> {
+        let info = self.source.load(filter).await?;
+        if let Some(info) = info.as_ref() {
+            let slice = self.source.current_slice();
+            let chunk = &slice[slice.len().saturating_sub(info.newly_loaded_bytes)..];
+            if !chunk.is_empty() {
+                let record = self
+                    .encoder
+                    .encode(chunk, info, self.source.endpoints())
+                    .map_err(SourceError::Io)?;
+                self.recording
+                    .write(record)
+                    .await
+                    .map_err(SourceError::Io)?;
+            }
+        }
+        Ok(info)
+    }
+
+    fn current_slice(&self) -> &[u8] {
+        self.source.current_slice()
+    }
+
+    fn consume(&mut self, offset: usize) {
+        self.source.consume(offset)
+    }
+
+    fn len(&self) -> usize {
+        self.source.len()
+    }
+
+    fn problems(&self) -> Option<String> {
+        self.source.problems()
+    }
+
+    fn endpoints(&self) -> Option<Endpoints> {
+        self.source.endpoints()
+    }
+
+    async fn cancel(&mut self) -> Result<(), SourceError> {
+        self.source.cancel().await
+    }
+
+    async fn income(&mut self, msg: sde::SdeRequest) -> Result<sde::SdeResponse, SourceError> {
+        self.source.income(msg).await
+    }
+}
+
+/// Converts loaded chunks into the records of a recording
+struct Encoder<T: TimeSource> {
+    format: RecordingFormat,
+    /// Stamps chunks, which the source doesn't provide a timestamp for
+    clock: ReceiveClock<T>,
+    /// Received bytes of a DLT message, which isn't complete yet
+    pending: Vec<u8>,
+    /// Sequence number of the next TCP segment; `None` until the SYN is recorded
+    seq: Option<u32>,
+}
+
+impl<T: TimeSource> Encoder<T> {
+    /// Encodes a chunk of bytes loaded by a source, which received it over `endpoints`
+    fn encode(
+        &mut self,
+        chunk: &[u8],
+        info: &ReloadInfo,
+        endpoints: Option<Endpoints>,
+    ) -> io::Result<Vec<u8>> {
+        let ts = info.last_known_ts.unwrap_or_else(|| self.clock.now());
+        match self.format {
+            RecordingFormat::Raw => Ok(chunk.to_vec()),
+            RecordingFormat::Dlt => Ok(self.encode_dlt(chunk, ts)),
+            RecordingFormat::PcapNG => {
+                // Bytes of different packets are recorded with their own metadata
+                let mut ranges = info
+                    .networks
+                    .iter()
+                    .map(|(offset, network)| (*offset, endpoints_of(network, endpoints)))
+                    .collect::<Vec<_>>();
+                if !matches!(ranges.first(), Some((0, _))) {
+                    ranges.insert(0, (0, endpoints));
+                }
+                let mut records = Vec::new();
+                for (i, (start, network)) in ranges.iter().enumerate() {
+                    let end = ranges
+                        .get(i + 1)
+                        .map_or(chunk.len(), |(offset, _)| *offset)
+                        .min(chunk.len());
+                    if *start < end {
+                        // Packets can't be built without addresses
+                        let endpoints = network.ok_or_else(|| {
+                            io::Error::other("addresses of the received data are unknown")
+                        })?;
+                        records.extend(self.encode_packets(
+                            &chunk[*start..end],
+                            &endpoints,
+                            ts * 1000,
+                        )?);
+                    }
+                }
+                Ok(records)
+            }
+        }
+    }
+
+    /// Frames complete DLT messages and prefixes each of them with a storage header. Bytes
+    /// which can't start a message are dropped.
+    fn encode_dlt(&mut self, chunk: &[u8], ts: u64) -> Vec<u8> {
+        self.pending.extend_from_slice(chunk);
+        let mut records = Vec::new();
+        let mut start = 0;
+        while let Some(len) = dlt_message_len(&self.pending[start..]) {
+            if len < DLT_FRAME_HEADER_LEN {
+                start += 1;
+                continue;
+            }
+            let Some(msg) = self.pending.get(start..start + len) else {
+                break;
+            };
+            records.extend_from_slice(&storage_header(ts, msg));
+            records.extend_from_slice(msg);
+            start += len;
+        }
+        self.pending.drain(..start);
+        records
+    }
+
+    /// Wraps the chunk into IP packets. A chunk of a datagram source is recorded as one
+    /// datagram; chunks of TCP streams are split into segments following a SYN.
+    fn encode_packets(
+        &mut self,
+        chunk: &[u8],
+        endpoints: &Endpoints,
+        ts_us: u64,
+    ) -> io::Result<Vec<u8>> {
+        let (source, destination) = (endpoints.remote, endpoints.local);
+        let ip = || ip_builder(source.ip(), destination.ip());
+        let mut records = Vec::new();
+        if endpoints.protocol == TransportProtocol::TCP {
+            let mut seq = match self.seq {
+                Some(seq) => seq,
+                None => {
+                    let mut packet = Vec::new();
+                    ip().tcp(source.port(), destination.port(), 0, TCP_WINDOW)
+                        .syn()
+                        .write(&mut packet, &[])
+                        .map_err(packet_error)?;
+                    write_packet(&mut records, ts_us, &packet);
+                    1
+                }
+            };
+            for segment in chunk.chunks(MAX_SEGMENT_LEN) {
+                let mut packet = Vec::new();
+                ip().tcp(source.port(), destination.port(), seq, TCP_WINDOW)
+                    .psh()
+                    .write(&mut packet, segment)
+                    .map_err(packet_error)?;
+                write_packet(&mut records, ts_us, &packet);
+                seq = seq.wrapping_add(segment.len() as u32);
+            }
+            self.seq = Some(seq);
+        } else {
+            let mut packet = Vec::new();
+            ip().udp(source.port(), destination.port())
+                .write(&mut packet, chunk)
+                .map_err(packet_error)?;
+            write_packet(&mut records, ts_us, &packet);
+        }
+        Ok(records)
+    }
+}
+
+/// Endpoints of a packet the source provided network metadata for. The transport protocol
+/// is taken from the source, as datagrams are recorded if it's unknown.
+fn endpoints_of(network: &NetworkMeta, endpoints: Option<Endpoints>) -> Option<Endpoints> {
+    Some(Endpoints {
+        protocol: endpoints.map_or(TransportProtocol::UDP, |endpoints| endpoints.protocol),
+        remote: SocketAddr::new(network.source, network.source_port?),
+        local: SocketAddr::new(network.destination, network.destination_port?),
+    })
+}
+
+/// IPv4 packets are built if both addresses are IPv4 addresses, IPv6 packets otherwise
+fn ip_builder(source: IpAddr, destination: IpAddr) -> PacketBuilderStep<IpHeaders> {
+    let v6 = |addr: IpAddr| -> Ipv6Addr {
+        match addr {
+            IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+            IpAddr::V6(addr) => addr,
+        }
+    };
+    match (source, destination) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            PacketBuilder::ipv4(source.octets(), destination.octets(), TTL)
+        }
+        (source, destination) => {
+            PacketBuilder::ipv6(v6(source).octets(), v6(destination).octets(), TTL)
+        }
+    }
+}
+
+fn packet_error(err: impl Display) -> io::Error {
+    io::Error::other(format!("fail to build packet: {err}"))
+}
+
+/// Section header and the description of the only interface of a PcapNG recording
+fn pcapng_header() -> Vec<u8> {
+    let mut header = Vec::with_capacity(48);
+    // Section header block: byte-order magic, version 1.0, unknown section length
+    header.extend_from_slice(&0x0a0d_0d0a_u32.to_le_bytes());
+    header.extend_from_slice(&28_u32.to_le_bytes());
+    header.extend_from_slice(&0x1a2b_3c4d_u32.to_le_bytes());
+    header.extend_from_slice(&1_u16.to_le_bytes());
+    header.extend_from_slice(&0_u16.to_le_bytes());
+    header.extend_from_slice(&(-1_i64).to_le_bytes());
+    header.extend_from_slice(&28_u32.to_le_bytes());
+    // Interface description block: raw IP packets without snapshot length limit
+    header.extend_from_slice(&1_u32.to_le_bytes());
+    header.extend_from_slice(&20_u32.to_le_bytes());
+    header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+    header.extend_from_slice(&0_u16.to_le_bytes());
+    header.extend_from_slice(&0_u32.to_le_bytes());
+    header.extend_from_slice(&20_u32.to_le_bytes());
+    header
+}
+
+/// Appends an enhanced packet block with a timestamp in microseconds
+fn write_packet(records: &mut Vec<u8>, ts_us: u64, packet: &[u8]) {
+    let padded = packet.len().next_multiple_of(4);
+    let block_len = (32 + padded) as u32;
+    records.extend_from_slice(&6_u32.to_le_bytes());
+    records.extend_from_slice(&block_len.to_le_bytes());
+    records.extend_from_slice(&0_u32.to_le_bytes());
+    records.extend_from_slice(&((ts_us >> 32) as u32).to_le_bytes());
+    records.extend_from_slice(&(ts_us as u32).to_le_bytes());
+    records.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    records.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+    records.extend_from_slice(packet);
+    records.resize(records.len() + padded - packet.len(), 0);
+    records.extend_from_slice(&block_len.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::pcap::ng::PcapngByteSource;
+    use parsers::dlt::DLT_STORAGE_PATTERN;
+    use std::path::PathBuf;
+
+    const TS_MS: u64 = 1_700_000_000_123;
+
+    /// Source loading the given chunks one by one
+    struct Chunks {
+        chunks: Vec<Vec<u8>>,
+        buffer: Vec<u8>,
+        ts: Option<u64>,
+        endpoints: Option<Endpoints>,
+    }
+
+    impl Chunks {
+        fn new(chunks: Vec<Vec<u8>>) -> Self {
+            Self {
+                chunks,
+                buffer: Vec::new(),
+                ts: Some(TS_MS),
+                endpoints: None,
+            }
+        }
+    }
+
+    /// Time source which is stopped at [`TS_MS`]
+    struct StoppedTime;
+
+    impl TimeSource for StoppedTime {
+        fn system(&self) -> u64 {
+            TS_MS
+        }
+
+        fn monotonic(&self) -> u64 {
+            0
+        }
+    }
+
+    impl ByteSource for Chunks {
+        fn consume(&mut self, offset: usize) {
+            self.buffer.drain(..offset);
+        }
+
+        fn current_slice(&self) -> &[u8] {
+            &self.buffer
+        }
+
+        fn len(&self) -> usize {
+            self.buffer.len()
+        }
+
+        fn endpoints(&self) -> Option<Endpoints> {
+            self.endpoints
+        }
+
+        async fn load(
+            &mut self,
+            _filter: Option<&SourceFilter>,
+        ) -> Result<Option<ReloadInfo>, SourceError> {
+            if self.chunks.is_empty() {
+                return Ok(None);
+            }
+            let chunk = self.chunks.remove(0);
+            self.buffer.extend_from_slice(&chunk);
+            Ok(Some(ReloadInfo::new(
+                chunk.len(),
+                self.buffer.len(),
+                0,
+                self.ts,
+            )))
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{}_{name}", uuid::Uuid::new_v4()))
+    }
+
+    async fn record(name: &str, format: RecordingFormat, source: Chunks) -> PathBuf {
+        let path = temp_path(name);
+        let recording = Recording::create(&path, format).unwrap();
+        let chunks = source.chunks.clone();
+        let mut source = recording.tee_with_time(source, StoppedTime);
+        for chunk in chunks {
+            source.load(None).await.unwrap();
+            // Data is passed through unchanged
+            assert!(source.current_slice().ends_with(&chunk));
+        }
+        drop(source);
+        recording.finish().await;
+        path
+    }
+
+    #[tokio::test]
+    async fn test_dlt_recording() {
+        let first: &[u8] = &[0x21, 0x00, 0x00, 0x06, 0xaa, 0xbb];
+        // With ECU id in the standard header
+        let second: &[u8] = &[0x25, 0x01, 0x00, 0x09, b'E', b'C', b'U', b'1', 0xcc];
+        let mut source = Chunks::new(vec![[first, &second[..3]].concat(), second[3..].to_vec()]);
+        // Stamped by the clock of the recording
+        source.ts = None;
+        let path = record("recording.dlt", RecordingFormat::Dlt, source).await;
+        let recorded = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let storage_header = |ecu: &[u8]| {
+            let mut header = DLT_STORAGE_PATTERN.to_vec();
+            header.extend_from_slice(&1_700_000_000_u32.to_le_bytes());
+            header.extend_from_slice(&123_000_i32.to_le_bytes());
+            header.extend_from_slice(ecu);
+            header
+        };
+        let expected = [
+            storage_header(&[0; 4]),
+            first.to_vec(),
+            storage_header(b"ECU1"),
+            second.to_vec(),
+        ]
+        .concat();
+        assert_eq!(recorded, expected);
+    }
+
+    #[tokio::test]
+    async fn test_pcapng_recording() {
+        let payload: Vec<u8> = (0..MAX_SEGMENT_LEN + 100).map(|i| i as u8).collect();
+        let mut source = Chunks::new(vec![b"first".to_vec(), payload.clone()]);
+        source.endpoints = Some(Endpoints {
+            protocol: TransportProtocol::TCP,
+            remote: "192.168.1.1:3490".parse().unwrap(),
+            local: "192.168.1.2:40000".parse().unwrap(),
+        });
+        let path = record("recording.pcapng", RecordingFormat::PcapNG, source).await;
+        let mut source = PcapngByteSource::new(File::open(&path).unwrap()).unwrap();
+        let mut received = Vec::new();
+        while let Some(info) = source.load(None).await.unwrap() {
+            assert_eq!(info.last_known_ts, Some(TS_MS));
+            for (_, network) in info.networks {
+                assert_eq!(network.source_port, Some(3490));
+            }
+            received.extend_from_slice(source.current_slice());
+            source.consume(source.len());
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(received, [b"first".to_vec(), payload].concat());
+    }
+
+    #[test]
+    fn test_reopen_recording() {
+        let dlt = ParserType::Dlt(crate::factory::DltParserSettings {
+            filter_config: None,
+            fibex_file_paths: None,
+            with_storage_header: false,
+            tz: None,
+            fibex_metadata: None,
+        });
+        assert!(matches!(
+            RecordingFormat::Dlt.reopen(&dlt),
+            (ParserType::Dlt(settings), FileFormat::Binary) if settings.with_storage_header
+        ));
+        assert!(matches!(
+            RecordingFormat::PcapNG.reopen(&ParserType::Text),
+            (ParserType::Text, FileFormat::PcapNG)
+        ));
+        assert!(matches!(
+            RecordingFormat::Raw.reopen(&ParserType::Text),
+            (ParserType::Text, FileFormat::Text)
+        ));
+    }
+}
@@ -0,0 +1,398 @@
+use crate::{
+    sde::{self, ReplayControl},
+    ByteSource, Error as SourceError, ReloadInfo, SourceFilter,
+};
+use parsers::{
+    dlt::{find_storage_header, storage_header_frame, DLT_STORAGE_PATTERN},
+    timestamp::{detect_file_format, TimestampFormat},
+    NetworkMeta,
+};
+use std::{collections::VecDeque, path::Path, time::Duration};
+use tokio::time::{sleep_until, Instant};
+
+/// Defines how the data of a replayed file is split into messages and where their
+/// timestamps are taken from
+#[derive(Debug, Clone)]
+pub enum ReplayFraming {
+    /// Each load of the file source is a message with the timestamp and the network metadata
+    /// of the source (pcap)
+    Packets,
+    /// DLT messages with storage headers
+    Dlt,
+    /// Lines of text with timestamps of the given format
+    Lines(Option<TimestampFormat>),
+}
+
+impl ReplayFraming {
+    /// Lines of the text file `path`, timed with the timestamp format detected in the file
+    pub fn lines_of(path: &Path) -> Result<Self, SourceError> {
+        let format = detect_file_format(path)
+            .map_err(SourceError::Io)?
+            .and_then(|detected| TimestampFormat::new(&detected.format).ok());
+        Ok(Self::Lines(format))
+    }
+
+    /// Length and timestamp of the first message of `staged`; `None` if the message isn't
+    /// complete yet. Bytes in front of a DLT storage header make up a message without
+    /// timestamp. After the file source is `done`, the last line doesn't need a line break.
+    fn next(&self, staged: &[u8], packets: &VecDeque<Packet>, done: bool) -> Option<Message> {
+        match self {
+            Self::Packets => packets.front().map(|(message, _)| *message),
+            Self::Dlt => match find_storage_header(staged) {
+                Some(0) => {
+                    let (timestamp, len) = storage_header_frame(staged)?;
+                    (staged.len() >= len).then_some((len, Some(timestamp)))
+                }
+                Some(start) => Some((start, None)),
+                // The end could be the beginning of a storage header
+                None => staged
+                    .len()
+                    .checked_sub(DLT_STORAGE_PATTERN.len() - 1)
+                    .filter(|len| *len > 0)
+                    .map(|len| (len, None)),
+            },
+            Self::Lines(format) => {
+                let len = match staged.iter().position(|b| *b == b'\n') {
+                    Some(end) => end + 1,
+                    None if done && !staged.is_empty() => staged.len(),
+                    None => return None,
+                };
+                let timestamp = format.as_ref().and_then(|format| {
+                    format
+                        .extract(&String::from_utf8_lossy(&staged[..len]), None)
+                        .and_then(|ts| u64::try_from(ts).ok())
+                });
+                Some((len, timestamp))
+            }
+        }
+    }
+}
+
+/// Length and timestamp (ms) of a replayed message
+type Message = (usize, Option<u64>);
+/// Message of [`ReplayFraming::Packets`] with the network metadata of its packet
+type Packet = (Message, Vec<(usize, NetworkMeta)>);
+
+/// [`ByteSource`] replaying the messages of a file source with the original timing between
+/// them, as if they were received from a live target. Messages without timestamp are passed
+/// right away.
+///
+/// The replay is controlled with [`ReplayControl`] requests via SDE: it can be paused,
+/// resumed, stepped message by message and sped up or slowed down.
+pub struct ReplaySource<S: ByteSource> {
+    source: S,
+    framing: ReplayFraming,
+    /// Bytes loaded from the file source, which aren't replayed yet
+    staged: Vec<u8>,
+    /// Messages in `staged` for [`ReplayFraming::Packets`]
+    packets: VecDeque<Packet>,
+    /// Replayed bytes
+    buffer: Vec<u8>,
+    speed: f64,
+    paused: bool,
+    /// Count of messages to replay while paused
+    steps: usize,
+    /// Instant the message with the timestamp was replayed at; the timing of following
+    /// messages is relative to it
+    reference: Option<(Instant, u64)>,
+    last_timestamp: Option<u64>,
+    /// The file source is exhausted
+    done: bool,
+}
+
+impl<S: ByteSource> ReplaySource<S> {
+    /// `speed` is the factor of the replay speed, e.g. `2.0` replays twice as fast
+    pub fn new(source: S, framing: ReplayFraming, speed: f64) -> Result<Self, SourceError> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(SourceError::Setup(format!("invalid replay speed: {speed}")));
+        }
+        Ok(Self {
+            source,
+            framing,
+            staged: Vec::new(),
+            packets: VecDeque::new(),
+            buffer: Vec::new(),
+            speed,
+            paused: false,
+            steps: 0,
+            reference: None,
+            last_timestamp: None,
+            done: false,
+        })
+    }
+
+    /// Continues the timing from the last replayed message on
+    fn rebase(&mut self) {
+        self.reference = self.last_timestamp.map(|ts| (Instant::now(), ts));
+    }
+
+    /// Instant the message with `timestamp` is due at
+    fn due(&self, timestamp: Option<u64>) -> Option<Instant> {
+        let (instant, reference) = self.reference?;
+        let ahead = timestamp?.checked_sub(reference)?;
+        Some(instant + Duration::from_secs_f64(ahead as f64 / 1000.0 / self.speed))
+    }
+
+    fn replay(&mut self, (len, timestamp): Message) -> ReloadInfo {
+        self.buffer.extend(self.staged.drain(..len));
+        let networks = if matches!(self.framing, ReplayFraming::Packets) {
+            self.packets
+                .pop_front()
+                .map(|(_, networks)| networks)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        if let Some(timestamp) = timestamp {
+            if self.reference.is_none() {
+                self.reference = Some((Instant::now(), timestamp));
+            }
+            self.last_timestamp = Some(timestamp);
+        }
+        if self.paused {
+            self.steps = self.steps.saturating_sub(1);
+        }
+        ReloadInfo::new(len, self.buffer.len(), 0, self.last_timestamp).with_networks(networks)
+    }
+}
+
+impl<S: ByteSource> ByteSource for ReplaySource<S> {
+    // Cancel-safe: all state is kept in the source, so waiting for the next message can be
+    // interrupted at any time.
+    async fn load(
+        &mut self,
+        filter: Option<&SourceFilter>,
+    ) -> Result<Option<ReloadInfo>, SourceError> {
+        loop {
+            let next = self
+                .framing
+                .next(&self.staged, &self.packets, self.done)
+                .or_else(|| {
+                    // Rest of an incomplete message at the end of the file
+                    (self.done && !self.staged.is_empty()).then_some((self.staged.len(), None))
+                });
+            if let Some(message) = next {
+                if self.paused && self.steps == 0 {
+                    // Waiting to be resumed or stepped via SDE
+                    std::future::pending::<()>().await;
+                }
+                if !self.paused {
+                    if let Some(due) = self.due(message.1) {
+                        sleep_until(due).await;
+                    }
+                }
+                return Ok(Some(self.replay(message)));
+            }
+            if self.done {
+                return Ok(None);
+            }
+            match self.source.load(filter).await? {
+                Some(info) if info.newly_loaded_bytes > 0 => {
+                    let loaded = self.source.current_slice();
+                    self.staged.extend_from_slice(loaded);
+                    if matches!(self.framing, ReplayFraming::Packets) {
+                        self.packets
+                            .push_back(((loaded.len(), info.last_known_ts), info.networks));
+                    }
+                    let len = loaded.len();
+                    self.source.consume(len);
+                }
+                _ => self.done = true,
+            }
+        }
+    }
+
+    fn current_slice(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    fn consume(&mut self, offset: usize) {
+        self.buffer.drain(..offset);
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    async fn income(&mut self, msg: sde::SdeRequest) -> Result<sde::SdeResponse, SourceError> {
+        let sde::SdeRequest::Replay(control) = msg else {
+            return Err(SourceError::NotSupported);
+        };
+        match control {
+            ReplayControl::Pause => self.paused = true,
+            ReplayControl::Resume => {
+                self.paused = false;
+                self.steps = 0;
+                self.rebase();
+            }
+            ReplayControl::Step => self.steps += 1,
+            ReplayControl::Speed(speed) => {
+                if !(speed.is_finite() && speed > 0.0) {
+                    return Err(SourceError::Setup(format!("invalid replay speed: {speed}")));
+                }
+                self.speed = speed;
+                self.rebase();
+            }
+        }
+        Ok(sde::SdeResponse { bytes: 0 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{binary::raw::BinaryByteSource, sde::SdeRequest};
+    use parsers::dlt::DLT_FRAME_HEADER_LEN;
+    use std::io::Cursor;
+    use tokio::time::timeout;
+
+    fn replay(data: &[u8], framing: ReplayFraming, speed: f64) -> impl ByteSource {
+        ReplaySource::new(
+            BinaryByteSource::new(Cursor::new(data.to_vec())),
+            framing,
+            speed,
+        )
+        .unwrap()
+    }
+
+    fn storage_message(secs: u32, micros: i32, payload: &[u8]) -> Vec<u8> {
+        let mut msg = DLT_STORAGE_PATTERN.to_vec();
+        msg.extend_from_slice(&secs.to_le_bytes());
+        msg.extend_from_slice(&micros.to_le_bytes());
+        msg.extend_from_slice(b"ECU1");
+        msg.extend_from_slice(&[0x21, 0x00]);
+        msg.extend_from_slice(&((DLT_FRAME_HEADER_LEN + payload.len()) as u16).to_be_bytes());
+        msg.extend_from_slice(payload);
+        msg
+    }
+
+    #[tokio::test]
+    async fn test_replay_dlt() {
+        let first = storage_message(100, 0, b"ab");
+        // 500 ms after the first message
+        let second = storage_message(100, 500_000, b"cd");
+        let mut source = replay(
+            &[first.clone(), second.clone()].concat(),
+            ReplayFraming::Dlt,
+            10.0,
+        );
+        let started = Instant::now();
+        let info = source.load(None).await.unwrap().unwrap();
+        assert_eq!(info.newly_loaded_bytes, first.len());
+        assert_eq!(info.last_known_ts, Some(100_000));
+        assert_eq!(source.current_slice(), first);
+        source.consume(first.len());
+        let info = source.load(None).await.unwrap().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(info.last_known_ts, Some(100_500));
+        assert_eq!(source.current_slice(), second);
+        source.consume(second.len());
+        assert!(source.load(None).await.unwrap().is_none());
+    }
+
+    /// Source loading one packet per load
+    struct Packets(VecDeque<(Vec<u8>, NetworkMeta)>, Vec<u8>);
+
+    impl ByteSource for Packets {
+        fn consume(&mut self, offset: usize) {
+            self.1.drain(..offset);
+        }
+
+        fn current_slice(&self) -> &[u8] {
+            &self.1
+        }
+
+        fn len(&self) -> usize {
+            self.1.len()
+        }
+
+        async fn load(
+            &mut self,
+            _filter: Option<&SourceFilter>,
+        ) -> Result<Option<ReloadInfo>, SourceError> {
+            let Some((packet, network)) = self.0.pop_front() else {
+                return Ok(None);
+            };
+            self.1.extend_from_slice(&packet);
+            Ok(Some(
+                ReloadInfo::new(packet.len(), self.1.len(), 0, Some(1_000))
+                    .with_network(Some(network)),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_packets_with_network() {
+        let network = |port: u16| NetworkMeta {
+            source: "192.168.1.1".parse().unwrap(),
+            source_port: Some(port),
+            destination: "192.168.1.2".parse().unwrap(),
+            destination_port: Some(3490),
+            vlan: None,
+            interface: Some(0),
+        };
+        let packets = Packets(
+            VecDeque::from([(b"ab".to_vec(), network(1)), (b"cd".to_vec(), network(2))]),
+            Vec::new(),
+        );
+        let mut source = ReplaySource::new(packets, ReplayFraming::Packets, 1.0).unwrap();
+        for (payload, port) in [(b"ab", 1), (b"cd", 2)] {
+            let info = source.load(None).await.unwrap().unwrap();
+            assert_eq!(source.current_slice(), payload);
+            assert_eq!(
+                info.networks
+                    .iter()
+                    .map(|(offset, network)| (*offset, network.source_port))
+                    .collect::<Vec<_>>(),
+                [(0, Some(port))]
+            );
+            source.consume(payload.len());
+        }
+        assert!(source.load(None).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_replay_control() {
+        let text = "2024-03-05 10:11:12.000 a\nno timestamp\n\
+            2024-03-05 10:11:22.000 b\n2024-03-05 10:11:32.000 c";
+        let format = TimestampFormat::new("YYYY-MM-DD hh:mm:ss.s").unwrap();
+        let mut source = replay(text.as_bytes(), ReplayFraming::Lines(Some(format)), 1.0);
+        let control = |control| SdeRequest::Replay(control);
+        source.load(None).await.unwrap();
+        // Lines without timestamp are replayed right away
+        source.load(None).await.unwrap();
+        assert!(source.current_slice().ends_with(b"a\nno timestamp\n"));
+        // The next line is due in 10 s
+        assert!(timeout(Duration::from_millis(50), source.load(None))
+            .await
+            .is_err());
+        source.income(control(ReplayControl::Pause)).await.unwrap();
+        source.income(control(ReplayControl::Step)).await.unwrap();
+        timeout(Duration::from_millis(50), source.load(None))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(source.current_slice().ends_with(b"b\n"));
+        // Paused again after the step
+        assert!(timeout(Duration::from_millis(50), source.load(None))
+            .await
+            .is_err());
+        source
+            .income(control(ReplayControl::Speed(1_000.0)))
+            .await
+            .unwrap();
+        source.income(control(ReplayControl::Resume)).await.unwrap();
+        timeout(Duration::from_secs(1), source.load(None))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(source
+            .current_slice()
+            .ends_with(b"b\n2024-03-05 10:11:32.000 c"));
+        assert!(source.load(None).await.unwrap().is_none());
+        assert!(source
+            .income(control(ReplayControl::Speed(0.0)))
+            .await
+            .is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use parsers::dlt::control::ControlRequest;
 use serde::{Deserialize, Serialize};
 use tokio::sync::oneshot;
 
@@ -9,6 +10,20 @@ pub type SdeMsg = (SdeRequest, oneshot::Sender<Result<SdeResponse, String>>);
 pub enum SdeRequest {
     WriteText(String),
     WriteBytes(Vec<u8>),
+    /// Controls a replayed file (see [`crate::replay::ReplaySource`])
+    Replay(ReplayControl),
+    /// Sends a control request to a dlt-daemon (see [`crate::socket::tcp::TcpSource`])
+    DltControl(ControlRequest),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub enum ReplayControl {
+    Pause,
+    Resume,
+    /// Replays the next message while paused
+    Step,
+    /// Changes the factor of the replay speed
+    Speed(f64),
 }
 
 #[derive(Deserialize, Serialize)]
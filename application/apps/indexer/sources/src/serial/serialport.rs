@@ -1,5 +1,6 @@
 use crate::{
-    factory::SerialTransportConfig, sde, ByteSource, Error as SourceError, ReloadInfo, SourceFilter,
+    clock::ReceiveClock, factory::SerialTransportConfig, sde, ByteSource, Error as SourceError,
+    ReloadInfo, SourceFilter,
 };
 use buf_redux::Buffer;
 use bytes::{BufMut, BytesMut};
@@ -84,6 +85,7 @@ pub struct SerialSource {
     buffer: Buffer,
     amount: usize,
     send_data_delay: u8,
+    clock: ReceiveClock,
 }
 
 // Do we need to do some actions of destructor?
@@ -120,6 +122,7 @@ impl SerialSource {
                     buffer: Buffer::new(),
                     amount: 0,
                     send_data_delay: config.send_data_delay,
+                    clock: ReceiveClock::new(),
                 })
             }
             Err(err) => Err(SourceError::Setup(format!(
@@ -157,7 +160,12 @@ impl ByteSource for SerialSource {
         }
 
         let available_bytes = self.buffer.len();
-        Ok(Some(ReloadInfo::new(self.amount, available_bytes, 0, None)))
+        Ok(Some(ReloadInfo::new(
+            self.amount,
+            available_bytes,
+            0,
+            Some(self.clock.now()),
+        )))
     }
 
     fn current_slice(&self) -> &[u8] {
@@ -214,6 +222,9 @@ impl ByteSource for SerialSource {
                 }
                 sde::SdeResponse { bytes: len }
             }
+            sde::SdeRequest::Replay(_) | sde::SdeRequest::DltControl(_) => {
+                return Err(SourceError::NotSupported)
+            }
         })
     }
 }
@@ -1,2 +1,4 @@
 pub mod tcp;
 pub mod udp;
+#[cfg(unix)]
+pub mod unix;
@@ -1,31 +1,73 @@
-use crate::{ByteSource, Error as SourceError, ReloadInfo, SourceFilter};
+use crate::{
+    clock::ReceiveClock, sde, ByteSource, Endpoints, Error as SourceError, ReloadInfo,
+    SourceFilter, TransportProtocol,
+};
 use buf_redux::Buffer;
-use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpStream, ToSocketAddrs},
+};
 
+/// Source reading a TCP connection. Data sent via SDE is written back over the same
+/// connection; DLT control requests are written as DLT control frames, e.g. to a dlt-daemon.
 pub struct TcpSource {
     buffer: Buffer,
     socket: TcpStream,
     tmp_buffer: Vec<u8>,
+    filter: Option<SourceFilter>,
+    /// Endpoints of the connection, which are kept once the peer is disconnected
+    endpoints: Option<Endpoints>,
+    clock: ReceiveClock,
 }
 
 const MAX_DATAGRAM_SIZE: usize = 65_507;
 
 impl TcpSource {
     pub async fn new<A: ToSocketAddrs>(addr: A) -> Result<Self, std::io::Error> {
-        Ok(Self {
+        Ok(Self::from_stream(TcpStream::connect(addr).await?))
+    }
+
+    /// Creates a source on top of an already established connection, e.g. a client
+    /// which was accepted by a listening socket.
+    pub fn from_stream(socket: TcpStream) -> Self {
+        let endpoints = socket
+            .peer_addr()
+            .and_then(|remote| {
+                Ok(Endpoints {
+                    protocol: TransportProtocol::TCP,
+                    remote,
+                    local: socket.local_addr()?,
+                })
+            })
+            .ok();
+        Self {
             buffer: Buffer::new(),
-            socket: TcpStream::connect(addr).await?,
+            socket,
             tmp_buffer: vec![0u8; MAX_DATAGRAM_SIZE],
-        })
+            filter: None,
+            endpoints,
+            clock: ReceiveClock::new(),
+        }
+    }
+
+    /// Sets the filter which is used on loading if no other filter is given by the caller
+    pub fn with_filter(mut self, filter: SourceFilter) -> Self {
+        self.filter = Some(filter);
+        self
     }
 }
 
 impl ByteSource for TcpSource {
     async fn load(
         &mut self,
-        _filter: Option<&SourceFilter>,
+        filter: Option<&SourceFilter>,
     ) -> Result<Option<ReloadInfo>, SourceError> {
-        // TODO use filter
+        let filter = filter.or(self.filter.as_ref());
+        let accepted = match (filter, self.socket.peer_addr()) {
+            (Some(filter), Ok(remote_addr)) => filter.accepts_remote(&remote_addr),
+            _ => true,
+        };
+        let mut skipped = 0;
         loop {
             debug!("Wait for tcp socket to become readable");
             self.socket
@@ -34,13 +76,24 @@ impl ByteSource for TcpSource {
                 .map_err(|e| SourceError::Unrecoverable(format!("{e}")))?;
             debug!("Socket ready to read");
             match self.socket.try_read(&mut self.tmp_buffer) {
+                Ok(len) if len > 0 && !accepted => {
+                    // Data of rejected peers is dropped until the connection is closed
+                    trace!("---> Dropped {} bytes", len);
+                    skipped += len;
+                    continue;
+                }
                 Ok(len) => {
                     trace!("---> Received {} bytes", len);
                     if len > 0 {
                         self.buffer.copy_from_slice(&self.tmp_buffer[..len]);
                     }
                     let available_bytes = self.buffer.len();
-                    return Ok(Some(ReloadInfo::new(len, available_bytes, 0, None)));
+                    return Ok(Some(ReloadInfo::new(
+                        len,
+                        available_bytes,
+                        skipped,
+                        Some(self.clock.now()),
+                    )));
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     continue;
@@ -67,15 +120,39 @@ impl ByteSource for TcpSource {
     fn len(&self) -> usize {
         self.buffer.len()
     }
+
+    fn endpoints(&self) -> Option<Endpoints> {
+        self.endpoints
+    }
+
+    async fn income(&mut self, request: sde::SdeRequest) -> Result<sde::SdeResponse, SourceError> {
+        let frame;
+        let bytes = match request {
+            sde::SdeRequest::WriteText(ref str) => str.as_bytes(),
+            sde::SdeRequest::WriteBytes(ref bytes) => bytes,
+            sde::SdeRequest::DltControl(ref control) => {
+                frame = control
+                    .to_frame()
+                    .map_err(|e| SourceError::Setup(e.to_string()))?;
+                &frame
+            }
+            sde::SdeRequest::Replay(_) => return Err(SourceError::NotSupported),
+        };
+        self.socket
+            .write_all(bytes)
+            .await
+            .map_err(SourceError::Io)?;
+        Ok(sde::SdeResponse { bytes: bytes.len() })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::tests::general_source_reload_test;
+    use crate::{tests::general_source_reload_test, AddressFilter};
     use std::time::Duration;
-    use tokio::{io::AsyncWriteExt, net::TcpListener, time::sleep};
+    use tokio::{io::AsyncReadExt, net::TcpListener, time::sleep};
 
     static MESSAGES: &[&str] = &["one", "two", "three"];
 
@@ -139,4 +216,98 @@ mod tests {
 
         general_source_reload_test(&mut tcp_source).await;
     }
+
+    #[tokio::test]
+    async fn test_accepted_stream_reload() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut client = TcpStream::connect(server).await.unwrap();
+            for msg in MESSAGES {
+                client
+                    .write_all(msg.as_bytes())
+                    .await
+                    .expect("could not send on socket");
+                client.flush().await.expect("flush message should work");
+                sleep(Duration::from_millis(100)).await;
+            }
+        });
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut tcp_source = TcpSource::from_stream(stream);
+
+        general_source_reload_test(&mut tcp_source).await;
+    }
+
+    #[tokio::test]
+    async fn test_tcp_sde() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut tcp_source = TcpSource::new(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut target, _) = listener.accept().await.unwrap();
+        let response = tcp_source
+            .income(sde::SdeRequest::WriteText(String::from("ping")))
+            .await
+            .unwrap();
+        assert_eq!(response.bytes, 4);
+        let mut received = [0u8; 4];
+        target.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"ping");
+        // The connection is still read after writing to it
+        target.write_all(b"pong").await.unwrap();
+        tcp_source.load(None).await.unwrap();
+        assert_eq!(tcp_source.current_slice(), b"pong");
+        assert!(matches!(
+            tcp_source
+                .income(sde::SdeRequest::Replay(sde::ReplayControl::Pause))
+                .await,
+            Err(SourceError::NotSupported)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tcp_dlt_control() {
+        use parsers::dlt::control::{ControlLogLevel, ControlRequest};
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut tcp_source = TcpSource::new(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (mut daemon, _) = listener.accept().await.unwrap();
+        let request = ControlRequest::SetLogLevel {
+            app_id: String::from("APP1"),
+            context_id: None,
+            level: ControlLogLevel::Debug,
+        };
+        let frame = request.to_frame().unwrap();
+        let response = tcp_source
+            .income(sde::SdeRequest::DltControl(request))
+            .await
+            .unwrap();
+        assert_eq!(response.bytes, frame.len());
+        let mut received = vec![0u8; frame.len()];
+        daemon.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, frame);
+    }
+
+    #[tokio::test]
+    async fn test_tcp_remote_filter() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server = listener.local_addr().unwrap();
+        let mut tcp_source =
+            TcpSource::new(server)
+                .await
+                .unwrap()
+                .with_filter(SourceFilter::remote(AddressFilter {
+                    deny_ports: vec![server.port()],
+                    ..Default::default()
+                }));
+        let (mut remote, _) = listener.accept().await.unwrap();
+        remote.write_all(b"denied").await.unwrap();
+        drop(remote);
+        // Data of the rejected remote is dropped until the connection is closed
+        let info = tcp_source.load(None).await.unwrap().unwrap();
+        assert_eq!(info.newly_loaded_bytes, 0);
+        assert_eq!(info.skipped_bytes, 6);
+        assert!(tcp_source.current_slice().is_empty());
+    }
 }
@@ -1,8 +1,11 @@
-use crate::{ByteSource, Error as SourceError, ReloadInfo, SourceFilter};
+use crate::{
+    clock::ReceiveClock, sde, ByteSource, Endpoints, Error as SourceError, ReloadInfo,
+    SourceFilter, TransportProtocol,
+};
 use buf_redux::Buffer;
 use indexer_base::config::MulticastInfo;
 use log::trace;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use thiserror::Error;
 use tokio::net::{ToSocketAddrs, UdpSocket};
 
@@ -20,10 +23,16 @@ pub enum UdpSourceError {
     Config(indexer_base::config::Error),
 }
 
+/// Source receiving UDP datagrams. Data sent via SDE goes to the configured peer or, if
+/// there is none, to the peer which sent the last accepted datagram.
 pub struct UdpSource {
     buffer: Buffer,
     socket: UdpSocket,
     tmp_buffer: Vec<u8>,
+    filter: Option<SourceFilter>,
+    peer: Option<SocketAddr>,
+    last_peer: Option<SocketAddr>,
+    clock: ReceiveClock,
 }
 
 const MAX_DATAGRAM_SIZE: usize = 65_507;
@@ -70,33 +79,63 @@ impl UdpSource {
             buffer: Buffer::new(),
             socket,
             tmp_buffer: vec![0u8; MAX_DATAGRAM_SIZE],
+            filter: None,
+            peer: None,
+            last_peer: None,
+            clock: ReceiveClock::new(),
         })
     }
+
+    /// Sets the filter which is used on loading if no other filter is given by the caller
+    pub fn with_filter(mut self, filter: SourceFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Sets the peer which receives the data sent via SDE
+    pub fn with_peer(mut self, peer: SocketAddr) -> Self {
+        self.peer = Some(peer);
+        self
+    }
 }
 
 impl ByteSource for UdpSource {
     async fn load(
         &mut self,
-        _filter: Option<&SourceFilter>,
+        filter: Option<&SourceFilter>,
     ) -> Result<Option<ReloadInfo>, SourceError> {
-        // TODO use filter
-        let (len, remote_addr) = self
-            .socket
-            .recv_from(&mut self.tmp_buffer)
-            .await
-            .map_err(|e| SourceError::Setup(format!("{e}")))?;
-        trace!(
-            "---> Received {} bytes from {:?}: {}",
-            len,
-            remote_addr,
-            String::from_utf8_lossy(&self.tmp_buffer[..len])
-        );
-        if len > 0 {
-            self.buffer.copy_from_slice(&self.tmp_buffer[..len]);
-        }
-        let available_bytes = self.buffer.len();
+        let filter = filter.or(self.filter.as_ref());
+        let mut skipped = 0;
+        loop {
+            let (len, remote_addr) = self
+                .socket
+                .recv_from(&mut self.tmp_buffer)
+                .await
+                .map_err(|e| SourceError::Setup(format!("{e}")))?;
+            if filter.is_some_and(|filter| !filter.accepts_remote(&remote_addr)) {
+                trace!("---> Dropped {len} bytes from {remote_addr:?}");
+                skipped += len;
+                continue;
+            }
+            trace!(
+                "---> Received {} bytes from {:?}: {}",
+                len,
+                remote_addr,
+                String::from_utf8_lossy(&self.tmp_buffer[..len])
+            );
+            if len > 0 {
+                self.buffer.copy_from_slice(&self.tmp_buffer[..len]);
+            }
+            self.last_peer = Some(remote_addr);
+            let available_bytes = self.buffer.len();
 
-        Ok(Some(ReloadInfo::new(len, available_bytes, 0, None)))
+            return Ok(Some(ReloadInfo::new(
+                len,
+                available_bytes,
+                skipped,
+                Some(self.clock.now()),
+            )));
+        }
     }
 
     fn current_slice(&self) -> &[u8] {
@@ -110,12 +149,40 @@ impl ByteSource for UdpSource {
     fn len(&self) -> usize {
         self.buffer.len()
     }
+
+    fn endpoints(&self) -> Option<Endpoints> {
+        Some(Endpoints {
+            protocol: TransportProtocol::UDP,
+            remote: self.last_peer?,
+            local: self.socket.local_addr().ok()?,
+        })
+    }
+
+    async fn income(&mut self, request: sde::SdeRequest) -> Result<sde::SdeResponse, SourceError> {
+        let bytes = match request {
+            sde::SdeRequest::WriteText(ref str) => str.as_bytes(),
+            sde::SdeRequest::WriteBytes(ref bytes) => bytes,
+            sde::SdeRequest::Replay(_) | sde::SdeRequest::DltControl(_) => {
+                return Err(SourceError::NotSupported)
+            }
+        };
+        let Some(peer) = self.peer.or(self.last_peer) else {
+            return Err(SourceError::NotSupported);
+        };
+        Ok(sde::SdeResponse {
+            bytes: self
+                .socket
+                .send_to(bytes, peer)
+                .await
+                .map_err(SourceError::Io)?,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::general_source_reload_test;
+    use crate::{tests::general_source_reload_test, AddressFilter};
 
     static MESSAGES: &[&str] = &["one", "two", "three"];
 
@@ -168,4 +235,64 @@ mod tests {
 
         general_source_reload_test(&mut udp_source).await;
     }
+
+    #[tokio::test]
+    async fn test_udp_remote_filter() {
+        let allowed_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let denied_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut udp_source = UdpSource::new("127.0.0.1:0", vec![])
+            .await
+            .unwrap()
+            .with_filter(SourceFilter::remote(AddressFilter {
+                deny_ports: vec![denied_socket.local_addr().unwrap().port()],
+                ..Default::default()
+            }));
+        let receiver = udp_source.socket.local_addr().unwrap();
+        denied_socket.send_to(b"denied", receiver).await.unwrap();
+        allowed_socket.send_to(b"allowed", receiver).await.unwrap();
+        let info = udp_source.load(None).await.unwrap().unwrap();
+        assert_eq!(info.newly_loaded_bytes, 7);
+        assert_eq!(info.skipped_bytes, 6);
+        assert_eq!(udp_source.current_slice(), b"allowed");
+    }
+
+    #[tokio::test]
+    async fn test_udp_reply() {
+        let peer_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut udp_source = UdpSource::new("127.0.0.1:0", vec![]).await.unwrap();
+        let receiver = udp_source.socket.local_addr().unwrap();
+        // Nobody to send to before the first datagram
+        assert!(matches!(
+            udp_source
+                .income(sde::SdeRequest::WriteText(String::from("lost")))
+                .await,
+            Err(SourceError::NotSupported)
+        ));
+        peer_socket.send_to(b"hello", receiver).await.unwrap();
+        udp_source.load(None).await.unwrap();
+        let response = udp_source
+            .income(sde::SdeRequest::WriteBytes(b"reply".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(response.bytes, 5);
+        let mut received = [0u8; 16];
+        let len = peer_socket.recv(&mut received).await.unwrap();
+        assert_eq!(&received[..len], b"reply");
+    }
+
+    #[tokio::test]
+    async fn test_udp_configured_peer() {
+        let peer_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut udp_source = UdpSource::new("127.0.0.1:0", vec![])
+            .await
+            .unwrap()
+            .with_peer(peer_socket.local_addr().unwrap());
+        udp_source
+            .income(sde::SdeRequest::WriteText(String::from("command")))
+            .await
+            .unwrap();
+        let mut received = [0u8; 16];
+        let len = peer_socket.recv(&mut received).await.unwrap();
+        assert_eq!(&received[..len], b"command");
+    }
 }
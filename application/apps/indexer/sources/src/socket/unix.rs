@@ -0,0 +1,308 @@
+use crate::{clock::ReceiveClock, sde, ByteSource, Error as SourceError, ReloadInfo, SourceFilter};
+use buf_redux::Buffer;
+use std::{
+    fs, io,
+    os::unix::{fs::FileTypeExt, net::SocketAddr},
+    path::{Path, PathBuf},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{UnixDatagram, UnixListener, UnixStream},
+};
+
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Removes a socket file which was left by a previous listener, so the path can be bound
+/// again. Other kinds of files are never removed.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+/// Binds a listener for Unix stream sockets on `path`
+pub fn listen(path: &Path) -> io::Result<UnixListener> {
+    remove_stale_socket(path)?;
+    UnixListener::bind(path)
+}
+
+/// Source reading a Unix domain stream socket. Data sent via SDE is written into the socket.
+pub struct UnixStreamSource {
+    buffer: Buffer,
+    socket: UnixStream,
+    tmp_buffer: Vec<u8>,
+    clock: ReceiveClock,
+}
+
+impl UnixStreamSource {
+    pub async fn new<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        Ok(Self::from_stream(UnixStream::connect(path).await?))
+    }
+
+    /// Creates a source on top of an already established connection, e.g. a client
+    /// which was accepted by a listening socket.
+    pub fn from_stream(socket: UnixStream) -> Self {
+        Self {
+            buffer: Buffer::new(),
+            socket,
+            tmp_buffer: vec![0u8; MAX_DATAGRAM_SIZE],
+            clock: ReceiveClock::new(),
+        }
+    }
+}
+
+impl ByteSource for UnixStreamSource {
+    async fn load(
+        &mut self,
+        _filter: Option<&SourceFilter>,
+    ) -> Result<Option<ReloadInfo>, SourceError> {
+        loop {
+            self.socket
+                .readable()
+                .await
+                .map_err(|e| SourceError::Unrecoverable(format!("{e}")))?;
+            match self.socket.try_read(&mut self.tmp_buffer) {
+                Ok(len) => {
+                    trace!("---> Received {} bytes", len);
+                    if len > 0 {
+                        self.buffer.copy_from_slice(&self.tmp_buffer[..len]);
+                    }
+                    let available_bytes = self.buffer.len();
+                    return Ok(Some(ReloadInfo::new(
+                        len,
+                        available_bytes,
+                        0,
+                        Some(self.clock.now()),
+                    )));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    continue;
+                }
+                Err(e) => {
+                    return Err(SourceError::Setup(format!("{e}")));
+                }
+            }
+        }
+    }
+
+    fn current_slice(&self) -> &[u8] {
+        self.buffer.buf()
+    }
+
+    fn consume(&mut self, offset: usize) {
+        self.buffer.consume(offset)
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    async fn income(&mut self, request: sde::SdeRequest) -> Result<sde::SdeResponse, SourceError> {
+        let bytes = match request {
+            sde::SdeRequest::WriteText(ref str) => str.as_bytes(),
+            sde::SdeRequest::WriteBytes(ref bytes) => bytes,
+            sde::SdeRequest::Replay(_) | sde::SdeRequest::DltControl(_) => {
+                return Err(SourceError::NotSupported)
+            }
+        };
+        self.socket
+            .write_all(bytes)
+            .await
+            .map_err(SourceError::Io)?;
+        Ok(sde::SdeResponse { bytes: bytes.len() })
+    }
+}
+
+/// Source reading a Unix domain datagram socket. Data sent via SDE goes to the connected
+/// peer or, on a bound socket, to the peer which sent the last datagram (if that peer has
+/// a path to reply to).
+pub struct UnixDatagramSource {
+    buffer: Buffer,
+    socket: UnixDatagram,
+    tmp_buffer: Vec<u8>,
+    /// Path which was bound by this source; removed on drop
+    bound: Option<PathBuf>,
+    connected: bool,
+    last_peer: Option<SocketAddr>,
+    clock: ReceiveClock,
+}
+
+impl UnixDatagramSource {
+    /// Binds `path` and receives datagrams sent to it
+    pub fn bind<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let path = path.as_ref();
+        remove_stale_socket(path)?;
+        let socket = UnixDatagram::bind(path)?;
+        Ok(Self::from_socket(socket, Some(path.to_owned()), false))
+    }
+
+    /// Connects to the datagram socket `path`
+    pub fn connect<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Self::from_socket(socket, None, true))
+    }
+
+    fn from_socket(socket: UnixDatagram, bound: Option<PathBuf>, connected: bool) -> Self {
+        Self {
+            buffer: Buffer::new(),
+            socket,
+            tmp_buffer: vec![0u8; MAX_DATAGRAM_SIZE],
+            bound,
+            connected,
+            last_peer: None,
+            clock: ReceiveClock::new(),
+        }
+    }
+}
+
+impl Drop for UnixDatagramSource {
+    fn drop(&mut self) {
+        if let Some(path) = self.bound.take() {
+            if let Err(err) = fs::remove_file(&path) {
+                warn!("Fail to remove socket file {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+impl ByteSource for UnixDatagramSource {
+    async fn load(
+        &mut self,
+        _filter: Option<&SourceFilter>,
+    ) -> Result<Option<ReloadInfo>, SourceError> {
+        let (len, peer) = self
+            .socket
+            .recv_from(&mut self.tmp_buffer)
+            .await
+            .map_err(|e| SourceError::Setup(format!("{e}")))?;
+        trace!("---> Received {} bytes from {:?}", len, peer);
+        if len > 0 {
+            self.buffer.copy_from_slice(&self.tmp_buffer[..len]);
+        }
+        self.last_peer = Some(peer);
+        let available_bytes = self.buffer.len();
+        Ok(Some(ReloadInfo::new(
+            len,
+            available_bytes,
+            0,
+            Some(self.clock.now()),
+        )))
+    }
+
+    fn current_slice(&self) -> &[u8] {
+        self.buffer.buf()
+    }
+
+    fn consume(&mut self, offset: usize) {
+        self.buffer.consume(offset)
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    async fn income(&mut self, request: sde::SdeRequest) -> Result<sde::SdeResponse, SourceError> {
+        let bytes = match request {
+            sde::SdeRequest::WriteText(ref str) => str.as_bytes(),
+            sde::SdeRequest::WriteBytes(ref bytes) => bytes,
+            sde::SdeRequest::Replay(_) | sde::SdeRequest::DltControl(_) => {
+                return Err(SourceError::NotSupported)
+            }
+        };
+        let sent = if self.connected {
+            self.socket.send(bytes).await
+        } else {
+            match self.last_peer.as_ref().and_then(|peer| peer.as_pathname()) {
+                Some(peer) => self.socket.send_to(bytes, peer).await,
+                None => return Err(SourceError::NotSupported),
+            }
+        };
+        Ok(sde::SdeResponse {
+            bytes: sent.map_err(SourceError::Io)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::general_source_reload_test;
+    use std::time::Duration;
+    use tokio::{io::AsyncReadExt, time::sleep};
+
+    static MESSAGES: &[&str] = &["one", "two", "three"];
+
+    fn socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chipmunk_{name}_{}.sock", std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn test_stream_reload() {
+        let path = socket_path("stream_reload");
+        let listener = listen(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            for msg in MESSAGES {
+                stream
+                    .write_all(msg.as_bytes())
+                    .await
+                    .expect("could not send on socket");
+                sleep(Duration::from_millis(100)).await;
+            }
+        });
+        let mut source = UnixStreamSource::new(&path).await.unwrap();
+        general_source_reload_test(&mut source).await;
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_sde() {
+        let path = socket_path("stream_sde");
+        let listener = listen(&path).unwrap();
+        let mut client = UnixStream::connect(&path).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut source = UnixStreamSource::from_stream(stream);
+        let response = source
+            .income(sde::SdeRequest::WriteText(String::from("ping")))
+            .await
+            .unwrap();
+        assert_eq!(response.bytes, 4);
+        let mut received = [0u8; 4];
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"ping");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_datagram_reload() {
+        let path = socket_path("datagram_reload");
+        let mut source = UnixDatagramSource::bind(&path).unwrap();
+        let sender = UnixDatagram::unbound().unwrap();
+        for msg in MESSAGES {
+            sender.send_to(msg.as_bytes(), &path).await.unwrap();
+        }
+        general_source_reload_test(&mut source).await;
+        drop(source);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_datagram_reply() {
+        let path = socket_path("datagram_reply");
+        let peer_path = socket_path("datagram_reply_peer");
+        let mut source = UnixDatagramSource::bind(&path).unwrap();
+        let peer = UnixDatagramSource::bind(&peer_path).unwrap();
+        peer.socket.send_to(b"hello", &path).await.unwrap();
+        source.load(None).await.unwrap();
+        assert_eq!(source.current_slice(), b"hello");
+        source
+            .income(sde::SdeRequest::WriteBytes(b"reply".to_vec()))
+            .await
+            .unwrap();
+        let mut received = [0u8; 16];
+        let len = peer.socket.recv(&mut received).await.unwrap();
+        assert_eq!(&received[..len], b"reply");
+    }
+}
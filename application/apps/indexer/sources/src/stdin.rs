@@ -0,0 +1,133 @@
+use crate::{clock::ReceiveClock, ByteSource, Error as SourceError, ReloadInfo, SourceFilter};
+use buf_redux::Buffer;
+use std::{
+    io::{self, Read},
+    thread,
+};
+use tokio::sync::mpsc::{channel, Receiver};
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+const CHUNKS_IN_FLIGHT: usize = 4;
+
+/// Source reading the standard input of the process, e.g. the output of another command
+/// piped in. The source is done at the end of the input. SDE isn't supported.
+///
+/// Reading the standard input blocks and cannot be cancelled, therefore the input is read
+/// in a dedicated thread and passed in chunks to the source. Loading stays cancel safe.
+pub struct StdinSource {
+    buffer: Buffer,
+    rx: Receiver<io::Result<Vec<u8>>>,
+    clock: ReceiveClock,
+}
+
+impl StdinSource {
+    pub fn new() -> Self {
+        Self::from_reader(io::stdin())
+    }
+
+    /// Reads `reader` instead of the standard input
+    pub fn from_reader<R: Read + Send + 'static>(mut reader: R) -> Self {
+        let (tx, rx) = channel(CHUNKS_IN_FLIGHT);
+        thread::spawn(move || loop {
+            let mut chunk = vec![0u8; READ_CHUNK_SIZE];
+            let chunk = match reader.read(&mut chunk) {
+                // Sender is dropped at the end of the input
+                Ok(0) => break,
+                Ok(len) => {
+                    chunk.truncate(len);
+                    Ok(chunk)
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => Err(err),
+            };
+            let failed = chunk.is_err();
+            // Fails if the source is dropped
+            if tx.blocking_send(chunk).is_err() || failed {
+                break;
+            }
+        });
+        Self {
+            buffer: Buffer::new(),
+            rx,
+            clock: ReceiveClock::new(),
+        }
+    }
+}
+
+impl Default for StdinSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ByteSource for StdinSource {
+    async fn load(
+        &mut self,
+        _filter: Option<&SourceFilter>,
+    ) -> Result<Option<ReloadInfo>, SourceError> {
+        // Nothing is loaded at the end of the input, which ends the producer
+        let len = match self.rx.recv().await {
+            Some(chunk) => {
+                let chunk = chunk.map_err(SourceError::Io)?;
+                self.buffer.copy_from_slice(&chunk);
+                chunk.len()
+            }
+            None => 0,
+        };
+        trace!("---> Received {} bytes", len);
+        Ok(Some(ReloadInfo::new(
+            len,
+            self.buffer.len(),
+            0,
+            Some(self.clock.now()),
+        )))
+    }
+
+    fn current_slice(&self) -> &[u8] {
+        self.buffer.buf()
+    }
+
+    fn consume(&mut self, offset: usize) {
+        self.buffer.consume(offset)
+    }
+
+    fn len(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::general_source_reload_test;
+
+    #[tokio::test]
+    async fn test_general_source_reload() {
+        let reader = (&b"one\ntwo\n"[..]).chain(&b"three\n"[..]);
+        let mut source = StdinSource::from_reader(reader);
+        general_source_reload_test(&mut source).await;
+    }
+
+    #[tokio::test]
+    async fn test_end_of_input() {
+        let mut source = StdinSource::from_reader(&b"one\ntwo\n"[..]);
+        let info = source.load(None).await.unwrap().unwrap();
+        assert_eq!(info.newly_loaded_bytes, 8);
+        source.consume(4);
+        let info = source.load(None).await.unwrap().unwrap();
+        assert_eq!(info.newly_loaded_bytes, 0);
+        assert_eq!(source.current_slice(), b"two\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_error() {
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken pipe"))
+            }
+        }
+        let mut source = StdinSource::from_reader(Failing);
+        assert!(matches!(source.load(None).await, Err(SourceError::Io(_))));
+    }
+}
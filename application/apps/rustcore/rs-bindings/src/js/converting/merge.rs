@@ -1,4 +1,4 @@
-use merging::merger::FileMergeOptions;
+use merging::merger::{FileMergeOptions, MergeInputKind};
 use node_bindgen::{
     core::{
         val::{JsEnv, JsObject},
@@ -6,16 +6,17 @@ use node_bindgen::{
     },
     sys::napi_value,
 };
+use parsers::timestamp::TimeCorrection;
 use serde::Serialize;
 
 #[derive(Serialize, Debug, Clone)]
 pub struct WrappedFileMergeOptions(FileMergeOptions);
 
-// impl WrappedFileMergeOptions {
-//     pub fn as_file_merge_options(&self) -> FileMergeOptions {
-//         self.0.clone()
-//     }
-// }
+impl WrappedFileMergeOptions {
+    pub fn as_file_merge_options(&self) -> FileMergeOptions {
+        self.0.clone()
+    }
+}
 
 impl JSValue<'_> for WrappedFileMergeOptions {
     fn convert_to_rust(env: &JsEnv, n_value: napi_value) -> Result<Self, NjError> {
@@ -38,11 +39,10 @@ impl JSValue<'_> for WrappedFileMergeOptions {
                     return Err(e);
                 }
             };
+            // Without format the timestamp format of the file is detected
             let format: String = match js_obj.get_property("format") {
                 Ok(Some(value)) => value.as_value()?,
-                Ok(None) => {
-                    return Err(NjError::Other("[format] property is not found".to_owned()));
-                }
+                Ok(None) => String::new(),
                 Err(e) => {
                     return Err(e);
                 }
@@ -61,12 +61,49 @@ impl JSValue<'_> for WrappedFileMergeOptions {
                     return Err(e);
                 }
             };
+            let kind: MergeInputKind = match js_obj.get_property("kind") {
+                Ok(Some(value)) => match value.as_value::<String>()?.as_str() {
+                    "Text" => MergeInputKind::Text,
+                    "Dlt" => MergeInputKind::Dlt,
+                    kind => {
+                        return Err(NjError::Other(format!("[kind] {kind} isn't supported")));
+                    }
+                },
+                Ok(None) => MergeInputKind::default(),
+                Err(e) => {
+                    return Err(e);
+                }
+            };
+            // Missing fields of the correction are 0
+            let correction: Option<TimeCorrection> = match js_obj.get_property("correction") {
+                Ok(Some(value)) => {
+                    let value = value.as_value::<JsObject>()?;
+                    let number = |name: &str| -> Result<f64, NjError> {
+                        match value.get_property(name) {
+                            Ok(Some(value)) => value.as_value(),
+                            Ok(None) => Ok(0.0),
+                            Err(e) => Err(e),
+                        }
+                    };
+                    Some(TimeCorrection {
+                        offset: number("offset")? as i64,
+                        drift: number("drift")?,
+                        origin: number("origin")? as i64,
+                    })
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    return Err(e);
+                }
+            };
             Ok(WrappedFileMergeOptions(FileMergeOptions {
                 path,
                 offset,
                 year,
                 tag,
                 format,
+                kind,
+                correction,
             }))
         } else {
             Err(NjError::Other("not valid format".to_owned()))
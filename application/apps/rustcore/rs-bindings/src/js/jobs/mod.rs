@@ -150,6 +150,21 @@ impl UnboundJobs {
             .map(CommandOutcomeWrapper)
     }
 
+    #[node_bindgen]
+    async fn detect_timestamp_format(
+        &self,
+        id: i64,
+        file_path: String,
+    ) -> Result<CommandOutcomeWrapper<String>, ComputationErrorWrapper> {
+        self.api
+            .as_ref()
+            .ok_or(ComputationError::SessionUnavailable)?
+            .detect_timestamp_format(u64_from_i64(id)?, file_path)
+            .await
+            .map_err(ComputationErrorWrapper)
+            .map(CommandOutcomeWrapper)
+    }
+
     #[node_bindgen]
     async fn spawn_process(
         &self,
@@ -196,6 +211,21 @@ impl UnboundJobs {
             .map(CommandOutcomeWrapper)
     }
 
+    #[node_bindgen]
+    async fn get_archive_members(
+        &self,
+        id: i64,
+        archive: String,
+    ) -> Result<CommandOutcomeWrapper<String>, ComputationErrorWrapper> {
+        self.api
+            .as_ref()
+            .ok_or(ComputationError::SessionUnavailable)?
+            .get_archive_members(u64_from_i64(id)?, archive)
+            .await
+            .map_err(ComputationErrorWrapper)
+            .map(CommandOutcomeWrapper)
+    }
+
     #[node_bindgen]
     async fn get_someip_statistic(
         &self,
@@ -3,7 +3,10 @@ pub mod progress_tracker;
 
 use crate::{
     js::{
-        converting::{filter::WrappedSearchFilter, source::WrappedSourceDefinition},
+        converting::{
+            filter::WrappedSearchFilter, merge::WrappedFileMergeOptions,
+            source::WrappedSourceDefinition,
+        },
         session::events::ComputationErrorWrapper,
     },
     logging::targets,
@@ -11,6 +14,7 @@ use crate::{
 use events::CallbackEventWrapper;
 use log::{debug, error, info, warn};
 use node_bindgen::derive::node_bindgen;
+use parsers::timestamp::TimeCorrection;
 use processor::grabber::LineRange;
 use session::{
     events::{CallbackEvent, ComputationError, NativeError, NativeErrorKind},
@@ -18,12 +22,29 @@ use session::{
     operations,
     progress::Severity,
     session::Session,
+    state::DeltasRequest,
 };
 use sources::sde;
 use std::{convert::TryFrom, ops::RangeInclusive, path::PathBuf, thread};
 use tokio::{runtime::Runtime, sync::oneshot};
 use uuid::Uuid;
 
+/// Builds the request of time deltas of grabbed rows; `reference` is the row to compute
+/// deltas to
+fn deltas_request(
+    deltas: Option<bool>,
+    reference: Option<i64>,
+) -> Result<Option<DeltasRequest>, ComputationErrorWrapper> {
+    if !deltas.unwrap_or(false) {
+        return Ok(None);
+    }
+    let reference = reference
+        .map(u64::try_from)
+        .transpose()
+        .map_err(|_| ComputationErrorWrapper(ComputationError::InvalidData))?;
+    Ok(Some(DeltasRequest { reference }))
+}
+
 struct RustSession {
     session: Option<Session>,
     uuid: Uuid,
@@ -298,14 +319,17 @@ impl RustSession {
         &self,
         start_line_index: i64,
         number_of_lines: i64,
+        deltas: Option<bool>,
+        reference: Option<i64>,
     ) -> Result<String, ComputationErrorWrapper> {
         let start = u64::try_from(start_line_index)
             .map_err(|_| ComputationErrorWrapper(ComputationError::InvalidData))?;
         let end = u64::try_from(start_line_index + number_of_lines - 1)
             .map_err(|_| ComputationErrorWrapper(ComputationError::InvalidData))?;
+        let deltas = deltas_request(deltas, reference)?;
         if let Some(ref session) = self.session {
             let grabbed = session
-                .grab(LineRange::from(start..=end))
+                .grab(LineRange::from(start..=end), deltas)
                 .await
                 .map_err(ComputationErrorWrapper)?;
             Ok(serde_json::to_string(&grabbed)?)
@@ -321,14 +345,17 @@ impl RustSession {
         &self,
         start_line_index: i64,
         number_of_lines: i64,
+        deltas: Option<bool>,
+        reference: Option<i64>,
     ) -> Result<String, ComputationErrorWrapper> {
         let start = u64::try_from(start_line_index)
             .map_err(|_| ComputationErrorWrapper(ComputationError::InvalidData))?;
         let end = u64::try_from(start_line_index + number_of_lines - 1)
             .map_err(|_| ComputationErrorWrapper(ComputationError::InvalidData))?;
+        let deltas = deltas_request(deltas, reference)?;
         if let Some(ref session) = self.session {
             let grabbed = session
-                .grab_indexed(RangeInclusive::<u64>::new(start, end))
+                .grab_indexed(RangeInclusive::<u64>::new(start, end), deltas)
                 .await
                 .map_err(ComputationErrorWrapper)?;
             Ok(serde_json::to_string(&grabbed)?)
@@ -534,6 +561,31 @@ impl RustSession {
         }
     }
 
+    #[node_bindgen]
+    async fn merge(
+        &self,
+        files: Vec<WrappedFileMergeOptions>,
+        source_id: String,
+        operation_id: String,
+    ) -> Result<(), ComputationErrorWrapper> {
+        if let Some(ref session) = self.session {
+            session
+                .merge(
+                    operations::uuid_from_str(&operation_id)?,
+                    files
+                        .iter()
+                        .map(|file| file.as_file_merge_options())
+                        .collect(),
+                    source_id,
+                )
+                .map_err(ComputationErrorWrapper)
+        } else {
+            Err(ComputationErrorWrapper(
+                ComputationError::SessionUnavailable,
+            ))
+        }
+    }
+
     #[node_bindgen]
     async fn apply_search_filters(
         &self,
@@ -613,6 +665,73 @@ impl RustSession {
         }
     }
 
+    /// Sets the time correction of the source given as JSON; without correction the source
+    /// isn't corrected anymore
+    #[node_bindgen]
+    async fn set_time_correction(
+        &self,
+        source_id: i32,
+        correction: Option<String>,
+    ) -> Result<(), ComputationErrorWrapper> {
+        let source_id = u16::try_from(source_id)
+            .map_err(|_| ComputationErrorWrapper(ComputationError::InvalidData))?;
+        let correction: Option<TimeCorrection> = correction
+            .map(|correction| serde_json::from_str(&correction))
+            .transpose()
+            .map_err(|e| ComputationError::Process(format!("Cannot parse time correction: {e}")))?;
+        if let Some(ref session) = self.session {
+            session
+                .set_time_correction(source_id, correction)
+                .await
+                .map_err(ComputationErrorWrapper)
+        } else {
+            Err(ComputationErrorWrapper(
+                ComputationError::SessionUnavailable,
+            ))
+        }
+    }
+
+    /// Fits the time correction of the source to anchors given as JSON (`[[row, reference]]`)
+    /// and returns the set correction as JSON
+    #[node_bindgen]
+    async fn fit_time_correction(
+        &self,
+        source_id: i32,
+        anchors: String,
+    ) -> Result<String, ComputationErrorWrapper> {
+        let source_id = u16::try_from(source_id)
+            .map_err(|_| ComputationErrorWrapper(ComputationError::InvalidData))?;
+        let anchors: Vec<(u64, u64)> = serde_json::from_str(&anchors)
+            .map_err(|e| ComputationError::Process(format!("Cannot parse anchors: {e}")))?;
+        if let Some(ref session) = self.session {
+            let correction = session
+                .fit_time_correction(source_id, anchors)
+                .await
+                .map_err(ComputationErrorWrapper)?;
+            Ok(serde_json::to_string(&correction)?)
+        } else {
+            Err(ComputationErrorWrapper(
+                ComputationError::SessionUnavailable,
+            ))
+        }
+    }
+
+    /// Returns the time corrections by alias of the source as JSON
+    #[node_bindgen]
+    async fn get_time_corrections(&self) -> Result<String, ComputationErrorWrapper> {
+        if let Some(ref session) = self.session {
+            let corrections = session
+                .get_time_corrections()
+                .await
+                .map_err(ComputationErrorWrapper)?;
+            Ok(serde_json::to_string(&corrections)?)
+        } else {
+            Err(ComputationErrorWrapper(
+                ComputationError::SessionUnavailable,
+            ))
+        }
+    }
+
     #[node_bindgen]
     async fn extract_matches(
         &self,
@@ -48,6 +48,17 @@ export interface IAttachmentsUpdatedUpdated {
     attachment: IAttachment;
 }
 
+export type TStreamConnectionState =
+    | 'Disconnected'
+    | { Reconnecting: { attempt: number } }
+    | { Reconnected: { attempt: number } };
+
+export interface IStreamConnectionEvent {
+    uuid: string;
+    source_id: number;
+    state: TStreamConnectionState;
+}
+
 export interface ISessionEvents {
     StreamUpdated: Subject<number>;
     FileRead: Subject<void>;
@@ -59,6 +70,7 @@ export interface ISessionEvents {
     MatchesUpdated: Subject<IEventMatchesUpdated>;
     Progress: Subject<IProgressEvent>;
     AttachmentsUpdated: Subject<IAttachmentsUpdatedUpdated>;
+    StreamConnection: Subject<IStreamConnectionEvent>;
     SessionError: Subject<IError>;
     OperationError: Subject<IErrorEvent>;
     SessionDestroyed: Subject<void>;
@@ -80,6 +92,7 @@ interface ISessionEventsSignatures {
     MatchesUpdated: 'MatchesUpdated';
     Progress: 'Progress';
     AttachmentsUpdated: 'AttachmentsUpdated';
+    StreamConnection: 'StreamConnection';
     SessionError: 'SessionError';
     OperationError: 'OperationError';
     SessionDestroyed: 'SessionDestroyed';
@@ -99,6 +112,7 @@ const SessionEventsSignatures: ISessionEventsSignatures = {
     MatchesUpdated: 'MatchesUpdated',
     AttachmentsUpdated: 'AttachmentsUpdated',
     Progress: 'Progress',
+    StreamConnection: 'StreamConnection',
     SessionError: 'SessionError',
     OperationError: 'OperationError',
     SessionDestroyed: 'SessionDestroyed',
@@ -125,6 +139,12 @@ interface ISessionEventsInterfaces {
         ];
     };
     AttachmentsUpdated: { self: 'object'; len: 'number'; attachment: typeof Object };
+    StreamConnection: {
+        self: 'object';
+        uuid: 'string';
+        source_id: 'number';
+        state: ['string', typeof Object];
+    };
     SessionError: { self: 'object'; severity: 'string'; message: 'string'; kind: 'string' };
     OperationError: {
         self: 'object';
@@ -155,6 +175,12 @@ const SessionEventsInterfaces: ISessionEventsInterfaces = {
         ],
     },
     AttachmentsUpdated: { self: 'object', len: 'number', attachment: Object },
+    StreamConnection: {
+        self: 'object',
+        uuid: 'string',
+        source_id: 'number',
+        state: ['string', Object],
+    },
     SessionError: { self: 'object', severity: 'string', message: 'string', kind: 'string' },
     OperationError: {
         self: 'object',
@@ -183,6 +209,7 @@ export class EventProvider extends Computation<
         MatchesUpdated: new Subject<IEventMatchesUpdated>(), // dummy
         Progress: new Subject<IProgressEvent>(),
         AttachmentsUpdated: new Subject<IAttachmentsUpdatedUpdated>(),
+        StreamConnection: new Subject<IStreamConnectionEvent>(),
         SessionError: new Subject<IError>(),
         OperationError: new Subject<IErrorEvent>(),
         SessionDestroyed: new Subject<void>(),
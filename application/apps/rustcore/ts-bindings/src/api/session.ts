@@ -19,6 +19,8 @@ export {
     IEventMapUpdated,
     IEventMatchesUpdated,
     IEventIndexedMapUpdated,
+    IStreamConnectionEvent,
+    TStreamConnectionState,
 } from '../api/session.provider';
 export { EventProvider, SessionStream, SessionSearch };
 
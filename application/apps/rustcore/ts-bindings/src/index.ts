@@ -21,6 +21,8 @@ export {
     IEventMapUpdated,
     IEventMatchesUpdated,
     IEventIndexedMapUpdated,
+    IStreamConnectionEvent,
+    TStreamConnectionState,
 } from './api/session';
 export { Jobs } from './api/jobs';
 export { Tracker } from './api/tracker';
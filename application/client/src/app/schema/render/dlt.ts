@@ -52,6 +52,22 @@ export class Implementation extends Render<Columns> {
             caption: 'PAYLOAD',
             desc: 'Payload',
         },
+        {
+            caption: 'SRC',
+            desc: 'Source address and port of the captured packet',
+        },
+        {
+            caption: 'DST',
+            desc: 'Destination address and port of the captured packet',
+        },
+        {
+            caption: 'VLAN',
+            desc: 'VLAN id of the captured packet',
+        },
+        {
+            caption: 'IF',
+            desc: 'Capture interface of the packet',
+        },
     ];
 
     constructor() {
@@ -60,7 +76,7 @@ export class Implementation extends Render<Columns> {
             new Columns(
                 Implementation.HEADERS,
                 true,
-                [150, 20, 20, 20, 20, 20, 20, 20, 20, 20, -1],
+                [150, 20, 20, 20, 20, 20, 20, 20, 20, 20, -1, 120, 120, 20, 20],
                 MIN_COLUMN_WIDTH,
                 MAX_COLUMN_WIDTH,
             ),
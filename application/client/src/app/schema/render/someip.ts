@@ -47,6 +47,22 @@ export class Implementation extends Render<Columns> {
             caption: 'PAYLOAD',
             desc: 'Payload',
         },
+        {
+            caption: 'SRC',
+            desc: 'Source address and port of the captured packet',
+        },
+        {
+            caption: 'DST',
+            desc: 'Destination address and port of the captured packet',
+        },
+        {
+            caption: 'VLAN',
+            desc: 'VLAN id of the captured packet',
+        },
+        {
+            caption: 'IF',
+            desc: 'Capture interface of the packet',
+        },
     ];
 
     constructor() {
@@ -55,7 +71,7 @@ export class Implementation extends Render<Columns> {
             new Columns(
                 Implementation.HEADERS,
                 true,
-                [50, 50, 50, 30, 30, 30, 30, 30, 30, -1],
+                [50, 50, 50, 30, 30, 30, 30, 30, 30, -1, 120, 120, 30, 30],
                 MIN_COLUMN_WIDTH,
                 MAX_COLUMN_WIDTH,
             ),